@@ -1,6 +1,9 @@
 //! Emulation of the Microvision's Piezo buzzer.
 
-use crate::common::{line_type, Ms};
+use crate::{
+    common::{line_type, Ms},
+    snapshot::{self, Reader, State, Writer},
+};
 
 line_type! {
     /// The buzzer pulse line.
@@ -13,6 +16,378 @@ line_type! {
     BuzzerPulse
 }
 
+/// The maximum amount of pulse line edges buffered between renders.
+pub const MAX_EDGES: usize = 512;
+
+/// The amplitude of the rendered square wave.
+const AMPLITUDE: i64 = 8192;
+
+/// The amount of microseconds within a second.
+const SECOND: u64 = 1_000_000;
+
+/// A PCM audio generator, synthesizing the output of the buzzer pulse line.
+///
+/// # Logic
+///
+/// The buzzer is driven by a square wave on the pulse line, the time of every
+/// edge (`0->1` or `1->0` transition) of this wave is recorded, and then rendered
+/// into PCM samples at any sample rate. Every sample is the average level of the
+/// wave over the period of the sample, and the DC offset of the wave is filtered
+/// out, so a silent buzzer converges to zero.
+///
+/// Averaging is a box filter rather than a band-limit: it keeps edges between
+/// samples from snapping to the sample grid, but it only attenuates the harmonics
+/// above half of the sample rate, so high pitches still alias slightly.
+///
+/// # Note
+///
+/// At most [`MAX_EDGES`] edges are buffered between renders, the oldest edges
+/// are discarded once this is exceeded, so samples should be rendered at least
+/// once every frame. The generator is not part of snapshots, it restarts from
+/// the current level of the pulse line when a snapshot is loaded.
+#[derive(Debug, Clone)]
+pub struct Pcm {
+    /// The ring buffer of edge times.
+    edges: [Ms; MAX_EDGES],
+    /// The index of the oldest edge.
+    head: usize,
+    /// The amount of buffered edges.
+    len: usize,
+    /// The level of the pulse line at the current render position.
+    level: bool,
+    /// The time rendering began at, once the generator has been clocked.
+    start: Option<Ms>,
+    /// The current time.
+    now: Ms,
+    /// The sample rate of the last render.
+    rate: u32,
+    /// The render position, in microseconds (since the start) times the rate.
+    pos: u64,
+    /// The previous (unfiltered) sample.
+    prev_in: i64,
+    /// The previous (filtered) sample.
+    prev_out: i64,
+}
+
+impl Pcm {
+    /// Create a new PCM audio generator.
+    #[must_use]
+    fn new() -> Self {
+        Self {
+            edges: [Ms(0); MAX_EDGES],
+            head: 0,
+            len: 0,
+            level: false,
+            start: None,
+            now: Ms(0),
+            rate: 0,
+            pos: 0,
+            prev_in: 0,
+            prev_out: 0,
+        }
+    }
+
+    /// Restart this PCM audio generator at the given level of the pulse line,
+    /// from the time it is next clocked.
+    fn restart(&mut self, level: bool) {
+        *self = Self {
+            level,
+            ..Self::new()
+        };
+    }
+
+    /// Clock (update) this PCM audio generator.
+    ///
+    /// # Logic
+    ///
+    /// This advances the time of the generator, recording an edge if the pulse line
+    /// has changed.
+    fn clock(&mut self, now: Ms, edge: bool) {
+        self.start.get_or_insert(now);
+        self.now = now;
+
+        if edge {
+            if self.len == MAX_EDGES {
+                // Discard the oldest edge, as if it was already rendered.
+                self.pop();
+            }
+            self.edges[(self.head + self.len) % MAX_EDGES] = now;
+            self.len += 1;
+        }
+    }
+
+    /// Remove the oldest edge, updating the level of the pulse line.
+    fn pop(&mut self) {
+        self.head = (self.head + 1) % MAX_EDGES;
+        self.len -= 1;
+        self.level = !self.level;
+    }
+
+    /// Return the amount of samples which can be rendered at the given sample rate.
+    #[must_use]
+    pub fn available(&self, rate: u32) -> usize {
+        let Some(start) = self.start else {
+            return 0;
+        };
+
+        let pos = self.pos / u64::from(self.rate.max(1)) * u64::from(rate);
+        let end = (self.now.0 - start.0) as u64 * u64::from(rate);
+        usize::try_from(end.saturating_sub(pos) / SECOND).unwrap_or(usize::MAX)
+    }
+
+    /// Render samples at the given sample rate into a buffer.
+    ///
+    /// This returns the amount of samples written, which is the smaller of the
+    /// buffer length and the amount of [`available`](Self::available) samples.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the sample rate is zero.
+    pub fn render(&mut self, rate: u32, out: &mut [i16]) -> usize {
+        assert!(rate > 0, "the sample rate must not be zero");
+
+        let Some(start) = self.start else {
+            return 0;
+        };
+
+        if rate != self.rate {
+            self.pos = self.pos / u64::from(self.rate.max(1)) * u64::from(rate);
+            self.rate = rate;
+        }
+
+        let rate = u64::from(rate);
+        let count = self.available(self.rate).min(out.len());
+
+        for sample in &mut out[..count] {
+            let (mut at, end) = (self.pos, self.pos + SECOND);
+            let mut high = 0;
+
+            while self.len > 0 {
+                let edge = (self.edges[self.head].0 - start.0) as u64 * rate;
+                if edge >= end {
+                    break;
+                }
+
+                let edge = edge.max(at);
+                if self.level {
+                    high += edge - at;
+                }
+                at = edge;
+                self.pop();
+            }
+            if self.level {
+                high += end - at;
+            }
+
+            // The average level, from `-AMPLITUDE` to `AMPLITUDE`.
+            let input = high.cast_signed() * 2 * AMPLITUDE / SECOND.cast_signed() - AMPLITUDE;
+
+            // A first-order high-pass (DC blocking) filter.
+            let output = input - self.prev_in + self.prev_out * 255 / 256;
+            self.prev_in = input;
+            self.prev_out = output;
+
+            *sample =
+                i16::try_from(output.clamp(i16::MIN.into(), i16::MAX.into())).unwrap_or_default();
+            self.pos = end;
+        }
+
+        count
+    }
+}
+
+/// The parameters of the [`Piezo`] acoustic model.
+///
+/// All frequencies are given in hertz, and are limited to just below half of
+/// the sample rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PiezoParams {
+    /// The resonant frequency of the piezo element.
+    pub resonance: f32,
+    /// The quality factor of the resonance, higher values give a narrower peak.
+    pub q: f32,
+    /// The gain of the resonant peak, relative to the rest of the spectrum.
+    pub peak: f32,
+    /// The frequency below which the response rolls off.
+    pub low_cut: f32,
+    /// The frequency above which the response rolls off.
+    pub high_cut: f32,
+    /// The overall output gain.
+    pub gain: f32,
+}
+
+impl PiezoParams {
+    /// A preset approximating the small piezo element of the Microvision, which
+    /// has a strong resonance around 3khz and very little low-end response.
+    ///
+    /// These values are a guess, based on the typical response of a small piezo
+    /// disc, they have not been measured on an actual Microvision.
+    pub const MICROVISION: Self = Self {
+        resonance: 3000.0,
+        q: 3.0,
+        peak: 2.5,
+        low_cut: 500.0,
+        high_cut: 7000.0,
+        gain: 0.5,
+    };
+}
+
+impl Default for PiezoParams {
+    fn default() -> Self {
+        Self::MICROVISION
+    }
+}
+
+/// Return the tangent of an angle within `0..PI/2`, which is used instead of
+/// the platform's math library, ensuring the same results everywhere.
+fn tan(x: f32) -> f32 {
+    let x2 = x * x;
+    let sin = x * (1.0 - x2 / 6.0 * (1.0 - x2 / 20.0 * (1.0 - x2 / 42.0 * (1.0 - x2 / 72.0))));
+    let cos = 1.0
+        - x2 / 2.0 * (1.0 - x2 / 12.0 * (1.0 - x2 / 30.0 * (1.0 - x2 / 56.0 * (1.0 - x2 / 90.0))));
+    sin / cos
+}
+
+/// Return the (pre-warped) integrator gain of a filter at the given cutoff.
+fn cutoff_gain(cutoff: f32, rate: u32) -> f32 {
+    #[allow(clippy::cast_precision_loss)]
+    let rate = rate as f32;
+    let cutoff = cutoff.clamp(1.0, rate * 0.49);
+
+    tan(core::f32::consts::PI * cutoff / rate)
+}
+
+/// A first-order (6db/octave) filter.
+#[derive(Debug, Clone, Copy)]
+struct OnePole {
+    /// The gain of the integrator.
+    g: f32,
+    /// The state of the integrator.
+    s: f32,
+}
+
+impl OnePole {
+    /// Create a new first-order filter at the given cutoff.
+    fn new(cutoff: f32, rate: u32) -> Self {
+        let g = cutoff_gain(cutoff, rate);
+        Self {
+            g: g / (1.0 + g),
+            s: 0.0,
+        }
+    }
+
+    /// Filter a single sample, returning the low-pass output.
+    fn low_pass(&mut self, x: f32) -> f32 {
+        let v = (x - self.s) * self.g;
+        let lp = v + self.s;
+        self.s = lp + v;
+        lp
+    }
+}
+
+/// A resonant second-order (state variable) band-pass filter.
+#[derive(Debug, Clone, Copy)]
+struct Resonator {
+    /// The damping, the inverse of the quality factor.
+    k: f32,
+    /// The filter coefficients.
+    a: [f32; 3],
+    /// The state of both integrators.
+    s: [f32; 2],
+}
+
+impl Resonator {
+    /// Create a new resonator at the given frequency and quality factor.
+    fn new(resonance: f32, q: f32, rate: u32) -> Self {
+        let g = cutoff_gain(resonance, rate);
+        let k = 1.0 / q.max(0.1);
+        let a1 = 1.0 / (1.0 + g * (g + k));
+
+        Self {
+            k,
+            a: [a1, g * a1, g * g * a1],
+            s: [0.0; 2],
+        }
+    }
+
+    /// Filter a single sample, returning the band-pass output, with unity gain at
+    /// the resonant frequency.
+    fn band_pass(&mut self, x: f32) -> f32 {
+        let [a1, a2, a3] = self.a;
+        let v3 = x - self.s[1];
+        let v1 = a1 * self.s[0] + a2 * v3;
+        let v2 = self.s[1] + a2 * self.s[0] + a3 * v3;
+        self.s = [2.0 * v1 - self.s[0], 2.0 * v2 - self.s[1]];
+        self.k * v1
+    }
+}
+
+/// An acoustic model of the Microvision's piezo element.
+///
+/// # Logic
+///
+/// This filters the samples rendered by the [`Pcm`] audio generator, rolling off
+/// both low and high frequencies, and adding a resonant peak. Only plain `f32`
+/// arithmetic is used, so the output is deterministic.
+#[derive(Debug, Clone)]
+pub struct Piezo {
+    /// The parameters of the model.
+    params: PiezoParams,
+    /// The low-frequency roll-off, as the low-pass whose output is subtracted.
+    low: OnePole,
+    /// The high-frequency roll-off.
+    high: OnePole,
+    /// The resonant peak.
+    resonator: Resonator,
+}
+
+impl Piezo {
+    /// Create a new piezo acoustic model for samples at the given sample rate.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the sample rate is zero.
+    #[must_use]
+    pub fn new(params: PiezoParams, rate: u32) -> Self {
+        assert!(rate > 0, "the sample rate must not be zero");
+
+        Self {
+            params,
+            low: OnePole::new(params.low_cut, rate),
+            high: OnePole::new(params.high_cut, rate),
+            resonator: Resonator::new(params.resonance, params.q, rate),
+        }
+    }
+
+    /// Return the parameters of this piezo acoustic model.
+    #[must_use]
+    pub fn params(&self) -> PiezoParams {
+        self.params
+    }
+
+    /// Reset the filter state of this piezo acoustic model, e.g. after a gap in
+    /// the audio stream.
+    pub fn reset(&mut self) {
+        self.low.s = 0.0;
+        self.high.s = 0.0;
+        self.resonator.s = [0.0; 2];
+    }
+
+    /// Filter a buffer of samples in place.
+    pub fn process(&mut self, samples: &mut [i16]) {
+        for sample in samples {
+            let x = f32::from(*sample);
+            let broad = x - self.low.low_pass(x);
+            let peaked = broad + self.params.peak * self.resonator.band_pass(x);
+            let y = self.high.low_pass(peaked) * self.params.gain;
+
+            #[allow(clippy::cast_possible_truncation)]
+            let y = y.clamp(f32::from(i16::MIN), f32::from(i16::MAX)) as i16;
+            *sample = y;
+        }
+    }
+}
+
 /// An emulated Piezo buzzer.
 #[derive(Debug, Clone)]
 pub struct Buzzer {
@@ -24,6 +399,8 @@ pub struct Buzzer {
     pub start: Ms,
     /// The end of the buzzer pulse period.
     pub end: Ms,
+    /// The PCM audio generator.
+    pub pcm: Pcm,
 }
 
 impl Buzzer {
@@ -35,6 +412,7 @@ impl Buzzer {
             pulse: false.into(),
             start: Ms(0),
             end: Ms(0),
+            pcm: Pcm::new(),
         }
     }
 
@@ -47,8 +425,12 @@ impl Buzzer {
     ///
     /// # Logic
     ///
-    /// This updates the buzzer's timing logic using the pulse line.
+    /// This updates the buzzer's timing logic and PCM audio generator using the
+    /// pulse line.
     pub(crate) fn clock(&mut self, pulse: BuzzerPulse, current_time: Ms) {
+        self.pcm
+            .clock(current_time, self.pulse.value() != pulse.value());
+
         if self.pulse.update_rising(pulse) {
             if self.pulse_times == 0 {
                 self.start = current_time;
@@ -85,6 +467,26 @@ impl Buzzer {
     }
 }
 
+impl State for Buzzer {
+    const SIZE: usize = 25;
+
+    fn save(&self, w: &mut Writer) {
+        w.bool(self.pulse.0);
+        w.usize(self.pulse_times);
+        w.usize(self.start.0);
+        w.usize(self.end.0);
+    }
+
+    fn load(&mut self, r: &mut Reader) -> Result<(), snapshot::Error> {
+        self.pulse.0 = r.bool()?;
+        self.pulse_times = r.usize()?;
+        self.start.0 = r.usize()?;
+        self.end.0 = r.usize()?;
+        self.pcm.restart(self.pulse.0);
+        Ok(())
+    }
+}
+
 /// An abstract (frontend agnostic) Piezo buzzer.
 pub trait Api {
     /// Enable the sound output of this buzzer.
@@ -95,3 +497,114 @@ pub trait Api {
     /// Disable the sound output of this buzzer.
     fn disable(&mut self);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Render 10 milliseconds of the given pulse line levels, clocked every 10
+    /// microseconds, into samples at 10khz.
+    fn render(level: impl Fn(usize) -> bool) -> [i16; 99] {
+        let mut buzzer = Buzzer::new();
+        for time in 0..1000 {
+            buzzer.clock(level(time).into(), Ms(time * 10));
+        }
+
+        let mut out = [0; 99];
+        assert_eq!(buzzer.pcm.available(10_000), 99);
+        assert_eq!(buzzer.pcm.render(10_000, &mut out), 99);
+        assert_eq!(buzzer.pcm.available(10_000), 0);
+        out
+    }
+
+    #[test]
+    fn renders_pcm() {
+        // A 1khz square wave. Every sample covers 100 microseconds, the edges fall on
+        // sample boundaries.
+        let out = render(|time| time / 50 % 2 == 1);
+        for (idx, sample) in out.iter().enumerate() {
+            assert_eq!(*sample > 0, idx / 5 % 2 == 1, "sample {idx}: {sample}");
+        }
+
+        // The same wave, delayed by half a sample. The samples covering an edge
+        // average both levels, so they lie between their neighbours.
+        let out = render(|time| (time + 5) / 50 % 2 == 1);
+        for idx in (14..99).step_by(10) {
+            assert!(
+                out[idx - 1] < out[idx] && out[idx] < out[idx + 1],
+                "sample {idx}"
+            );
+        }
+        for idx in (19..98).step_by(10) {
+            assert!(
+                out[idx - 1] > out[idx] && out[idx] > out[idx + 1],
+                "sample {idx}"
+            );
+        }
+    }
+
+    #[test]
+    fn renders_duty_cycle() {
+        // A 2.5khz wave which is high for a quarter of every period.
+        let out = render(|time| time % 40 < 10);
+        for (idx, sample) in out.iter().enumerate() {
+            assert_eq!(*sample > 0, idx % 4 == 0, "sample {idx}: {sample}");
+        }
+    }
+
+    #[test]
+    fn renders_pitch_change() {
+        // A 1khz square wave, changing to 2.5khz halfway through.
+        let out = render(|time| {
+            if time < 500 {
+                time / 50 % 2 == 1
+            } else {
+                time / 20 % 2 == 1
+            }
+        });
+        for (idx, sample) in out.iter().enumerate() {
+            let high = if idx < 50 {
+                idx / 5 % 2 == 1
+            } else {
+                idx / 2 % 2 == 1
+            };
+            assert_eq!(*sample > 0, high, "sample {idx}: {sample}");
+        }
+    }
+
+    #[test]
+    fn piezo_resonates() {
+        /// Return the energy of a filtered square wave with the given half-period.
+        fn energy(half: usize) -> i64 {
+            let mut piezo = Piezo::new(PiezoParams::MICROVISION, 48_000);
+            let mut samples = [0; 4800];
+            for (idx, sample) in samples.iter_mut().enumerate() {
+                *sample = if (idx / half).is_multiple_of(2) {
+                    4096
+                } else {
+                    -4096
+                };
+            }
+
+            piezo.process(&mut samples);
+            samples[480..].iter().map(|x| i64::from(*x).pow(2)).sum()
+        }
+
+        // A 3khz wave is at the resonance, a 300hz wave is rolled off.
+        assert!(energy(8) > energy(80) * 2);
+
+        // Waves of 8khz and 12khz, above the high cutoff, are rolled off further
+        // the higher they are.
+        assert!(energy(8) > energy(3) * 10);
+        assert!(energy(3) > energy(2) * 2);
+    }
+
+    #[test]
+    fn piezo_is_deterministic() {
+        // A single period of a 6khz square wave, the output only depends on plain
+        // `f32` arithmetic, so it is the same on every platform.
+        let mut samples = [4096, 4096, 4096, 4096, -4096, -4096, -4096, -4096];
+        Piezo::new(PiezoParams::MICROVISION, 48_000).process(&mut samples);
+        assert_eq!(samples, [756, 1904, 2504, 2793, 1330, -1115, -2627, -3622]);
+    }
+}
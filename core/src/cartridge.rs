@@ -5,7 +5,13 @@
 //! The Microvision is the first console that used/supported interchangeable
 //! cartridges and is therefore, in a sense, reprogrammable.
 
-use crate::tms1100::mem::{Ram, Rom};
+use crate::{
+    error::Error,
+    tms1100::mem::{Ram, Rom},
+};
+
+pub mod container;
+pub mod database;
 
 /// Cartridge-specific settings/features.
 ///
@@ -13,7 +19,16 @@ use crate::tms1100::mem::{Ram, Rom};
 /// the same "settings" as others. Some modify the charge supplied to chips like
 /// the rotary controller and others reverse the output decoder of the TMS1100.
 pub mod settings {
-    use crate::{display::DataLine, tms1100::pinio};
+    use crate::{
+        display::DataLine,
+        error::Error,
+        snapshot::{self, Reader, State, Writer},
+        tms1100::{
+            pinio,
+            pla::{self, MicroPla},
+            Model,
+        },
+    };
 
     use arbitrary_int::u4;
 
@@ -40,42 +55,275 @@ pub mod settings {
 
     /// The decode PLA for the O output of the TMS1100.
     ///
-    /// This is used for decided what will end up on the [`DataLine`] lines of
-    /// the Hughes 0488 LCD driver.
-    #[derive(Default, Debug, Clone, Copy)]
-    pub enum OutputPla {
-        /// The O output is simply forwarded through to the LCD driver.
-        Normal,
-        /// The O output is reversed then sent to the LCD driver.
-        #[default]
-        Reversed,
+    /// This maps every 5-bit value of the O output latch, i.e. the status bit and
+    /// the `A` accumulator written by `TDO`, to the 8-bit output of the O pins.
+    /// The lower 4 pins are used for decided what will end up on the [`DataLine`]
+    /// lines of the Hughes 0488 LCD driver.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct OutputPla {
+        /// The 8-bit pin output of every 5-bit O output latch value.
+        table: [u8; 32],
     }
 
     impl OutputPla {
+        /// The O output is simply forwarded through to the LCD driver.
+        pub const NORMAL: Self = Self::preset(false);
+
+        /// The O output is reversed then sent to the LCD driver.
+        pub const REVERSED: Self = Self::preset(true);
+
+        /// Create a preset output PLA, which forwards the O output latch to the
+        /// O pins, optionally reversing the lower 4 bits.
+        const fn preset(reversed: bool) -> Self {
+            let mut table = [0; 32];
+            let mut val = 0u8;
+            while val < 32 {
+                let low = if reversed {
+                    (val & 0xf).reverse_bits() >> 4
+                } else {
+                    val & 0xf
+                };
+                table[val as usize] = (val & 0x10) | low;
+                val += 1;
+            }
+
+            Self { table }
+        }
+
+        /// Create a new output PLA from the pin output of every O output latch
+        /// value.
+        #[must_use]
+        pub fn new(table: [u8; 32]) -> Self {
+            Self { table }
+        }
+
+        /// Parse an output PLA from a MAME `opla` dump.
+        ///
+        /// These dumps use the Berkeley PLA text format, declaring 5 inputs, the
+        /// O output latch with its status bit first, and 8 outputs, the O pins
+        /// starting at `O0`.
+        ///
+        /// # Errors
+        ///
+        /// If the dump contains a malformed term or declares a different amount
+        /// of inputs or outputs, an error is returned.
+        pub fn parse_mame(text: &str) -> Result<Self, pla::Error> {
+            let mut masks = [0u16; 32];
+            pla::parse(text, 5, 8, &mut masks)?;
+
+            let mut table = [0; 32];
+            for (entry, mask) in table.iter_mut().zip(masks) {
+                // The output only consists of 8 bits.
+                *entry = mask.to_le_bytes()[0];
+            }
+
+            Ok(Self { table })
+        }
+
+        /// Decode the O output of the TMS1100 into the 8-bit output of the O
+        /// pins.
+        #[must_use]
+        pub fn decode(&self, o: pinio::O) -> u8 {
+            self.table[usize::from(o.0.value())]
+        }
+
         /// Modify the O output of the TMS1100 into the [`DataLine`] input of
         /// the LCD driver.
         #[must_use]
-        pub(crate) fn modify(self, o: pinio::O) -> DataLine {
-            match self {
-                Self::Normal => DataLine(u4::new(o.0.value() & 0xf)),
-                Self::Reversed => DataLine(u4::new(o.0.value() & 0xf).reverse_bits()),
+        pub(crate) fn modify(&self, o: pinio::O) -> DataLine {
+            DataLine(u4::new(self.decode(o) & 0xf))
+        }
+    }
+
+    impl Default for OutputPla {
+        fn default() -> Self {
+            Self::REVERSED
+        }
+    }
+
+    impl State for OutputPla {
+        const SIZE: usize = 32;
+
+        fn save(&self, w: &mut Writer) {
+            w.bytes(&self.table);
+        }
+
+        fn load(&mut self, r: &mut Reader) -> Result<(), snapshot::Error> {
+            self.table = r.array()?;
+            Ok(())
+        }
+    }
+
+    /// The type of micro-processor on the cartridge.
+    ///
+    /// Most Microvision cartridges use a TMS1100, however a handful of them use
+    /// an Intel 8021 instead. The remaining members of the TMS1000 family are
+    /// supported for homebrew cartridges.
+    ///
+    /// The wiring of the Intel 8021 to the console is unverified, see
+    /// [`Console::clock`](crate::Console::clock).
+    #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum CpuType {
+        /// A TMS1100 micro-processor.
+        #[default]
+        Tms1100,
+        /// An Intel 8021 micro-controller.
+        I8021,
+        /// A TMS1000 micro-processor.
+        Tms1000,
+        /// A TMS1070 micro-processor.
+        Tms1070,
+        /// A TMS1200 micro-processor.
+        Tms1200,
+        /// A TMS1270 micro-processor.
+        Tms1270,
+        /// A TMS1300 micro-processor.
+        Tms1300,
+        /// A TMS1370 micro-processor.
+        Tms1370,
+    }
+
+    impl CpuType {
+        /// Return the TMS1000 family model of this micro-processor.
+        ///
+        /// This returns [None] if the micro-processor is not a member of the
+        /// TMS1000 family, i.e. an Intel 8021.
+        #[must_use]
+        pub fn model(self) -> Option<Model> {
+            let model = match self {
+                Self::Tms1100 => Model::Tms1100,
+                Self::I8021 => return None,
+                Self::Tms1000 => Model::Tms1000,
+                Self::Tms1070 => Model::Tms1070,
+                Self::Tms1200 => Model::Tms1200,
+                Self::Tms1270 => Model::Tms1270,
+                Self::Tms1300 => Model::Tms1300,
+                Self::Tms1370 => Model::Tms1370,
+            };
+
+            Some(model)
+        }
+
+        /// Return the size of the ROM used by this micro-processor, in bytes.
+        #[must_use]
+        pub fn rom_size(self) -> usize {
+            self.model().map_or(0x400, Model::rom_size)
+        }
+
+        /// Check if ROM data of the given size fits into the ROM used by this
+        /// micro-processor.
+        ///
+        /// # Errors
+        ///
+        /// If the size is larger than the ROM, an error is returned.
+        pub fn check_rom_size(self, size: usize) -> Result<(), Error> {
+            let max = self.rom_size();
+            if size > max {
+                return Err(Error::RomTooLarge { size, max });
+            }
+            Ok(())
+        }
+
+        /// Return the index of this micro-processor type.
+        ///
+        /// This is used as the stable encoding of the type, e.g. within snapshots.
+        #[must_use]
+        pub fn index(self) -> u8 {
+            self as u8
+        }
+
+        /// Return the micro-processor type with the given index.
+        ///
+        /// This is the inverse of [index](Self::index), and returns [None] if
+        /// the index is not within the range of `0..=7`.
+        #[must_use]
+        pub fn from_index(idx: u8) -> Option<Self> {
+            let kind = match idx {
+                0 => Self::Tms1100,
+                1 => Self::I8021,
+                2 => Self::Tms1000,
+                3 => Self::Tms1070,
+                4 => Self::Tms1200,
+                5 => Self::Tms1270,
+                6 => Self::Tms1300,
+                7 => Self::Tms1370,
+                _ => return None,
+            };
+
+            Some(kind)
+        }
+    }
+
+    impl From<Model> for CpuType {
+        fn from(model: Model) -> Self {
+            match model {
+                Model::Tms1000 => Self::Tms1000,
+                Model::Tms1070 => Self::Tms1070,
+                Model::Tms1100 => Self::Tms1100,
+                Model::Tms1200 => Self::Tms1200,
+                Model::Tms1270 => Self::Tms1270,
+                Model::Tms1300 => Self::Tms1300,
+                Model::Tms1370 => Self::Tms1370,
             }
         }
     }
 
     /// The cartridge-specific settings.
-    #[derive(Debug, Clone, Copy)]
+    #[derive(Debug, Clone, Copy, Default)]
     pub struct Settings {
+        /// The type of micro-processor on the cartridge.
+        pub cpu: CpuType,
         /// The settings of the charge line to the rotary controller.
         pub charge_info: ChargeInfo,
         /// The decode PLA for the O output of the TMS1100.
         pub output_pla: OutputPla,
+        /// The micro-instruction decode PLA of the TMS1100.
+        ///
+        /// If this is [None], the standard PLA of the micro-processor is used.
+        pub micro_pla: Option<MicroPla>,
         /// A flag determining if the rotary controller is enabled.
         pub rotary_enabled: bool,
     }
+
+    impl State for Settings {
+        const SIZE: usize = 19 + <OutputPla as State>::SIZE + <MicroPla as State>::SIZE;
+
+        fn save(&self, w: &mut Writer) {
+            w.u8(self.cpu.index());
+            w.usize(self.charge_info.offset);
+            w.usize(self.charge_info.scale);
+            self.output_pla.save(w);
+            w.bool(self.rotary_enabled);
+            w.bool(self.micro_pla.is_some());
+            match &self.micro_pla {
+                Some(pla) => pla.save(w),
+                None => w.zeros(<MicroPla as State>::SIZE),
+            }
+        }
+
+        fn load(&mut self, r: &mut Reader) -> Result<(), snapshot::Error> {
+            self.cpu = CpuType::from_index(r.u8()?).ok_or(snapshot::Error::Corrupt)?;
+            self.charge_info.offset = r.usize()?;
+            self.charge_info.scale = r.usize()?;
+            self.output_pla.load(r)?;
+            self.rotary_enabled = r.bool()?;
+
+            let present = r.bool()?;
+            let mut pla = MicroPla::tms1100();
+            pla.load(r)?;
+            self.micro_pla = present.then_some(pla);
+            Ok(())
+        }
+    }
 }
 
 /// An interchangeable game-cartridge for the Microvision.
+///
+/// # Note
+///
+/// The [`Rom`] and [`Ram`] of a cartridge are only used by the TMS1100, the
+/// Intel 8021 only uses the first 1kb of the ROM data and has its own internal
+/// RAM.
 #[derive(Debug, Clone)]
 pub struct Cartridge {
     /// The ROM data.
@@ -85,3 +333,93 @@ pub struct Cartridge {
     /// The game-specific settings of this cartridge.
     pub settings: settings::Settings,
 }
+
+impl Cartridge {
+    /// Create a new cartridge with the given ROM data and settings, and cleared
+    /// RAM.
+    ///
+    /// # Panics
+    ///
+    /// If the given ROM data is larger than the ROM used by the micro-processor
+    /// of the cartridge, this function will panic.
+    #[must_use]
+    pub fn new(rom: &[u8], settings: settings::Settings) -> Self {
+        match Self::try_new(rom, settings) {
+            Ok(cart) => cart,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    /// Create a new cartridge with the given ROM data and settings, and cleared
+    /// RAM.
+    ///
+    /// # Errors
+    ///
+    /// If the given ROM data is larger than the ROM used by the micro-processor
+    /// of the cartridge, an error is returned.
+    pub fn try_new(rom: &[u8], settings: settings::Settings) -> Result<Self, Error> {
+        settings.cpu.check_rom_size(rom.len())?;
+
+        let mut cart = Self {
+            rom: Rom::new(),
+            ram: Ram::new(),
+            settings,
+        };
+        cart.rom.copy(rom);
+        Ok(cart)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        settings::{ChargeInfo, CpuType, OutputPla, Settings},
+        Cartridge,
+    };
+    use crate::{error::Error, tms1100::pinio};
+
+    use arbitrary_int::u5;
+
+    #[test]
+    fn output_pla_presets() {
+        let o = pinio::O(u5::new(0b1_0011));
+        assert_eq!(OutputPla::NORMAL.decode(o), 0b1_0011);
+        assert_eq!(OutputPla::REVERSED.decode(o), 0b1_1100);
+        assert_eq!(OutputPla::REVERSED.modify(o).0.value(), 0b1100);
+    }
+
+    #[test]
+    fn rom_size() {
+        let settings = Settings {
+            cpu: CpuType::I8021,
+            charge_info: ChargeInfo::default(),
+            output_pla: OutputPla::default(),
+            micro_pla: None,
+            rotary_enabled: false,
+        };
+
+        let cart = Cartridge::try_new(&[0x12; 0x400], settings).unwrap();
+        assert_eq!(cart.rom.data[0x3ff..0x401], [0x12, 0]);
+        assert_eq!(
+            Cartridge::try_new(&[0; 0x401], settings).unwrap_err(),
+            Error::RomTooLarge {
+                size: 0x401,
+                max: 0x400
+            }
+        );
+    }
+
+    #[test]
+    fn parse_mame_output_pla() {
+        let dump = "\
+            .i 5
+            .o 8
+            1---- 10000000 # O0 follows the status bit
+            0--11 11110000
+        ";
+        let pla = OutputPla::parse_mame(dump).unwrap();
+        assert_eq!(pla.decode(pinio::O(u5::new(0b1_0000))), 0b0000_0001);
+        assert_eq!(pla.decode(pinio::O(u5::new(0b0_0111))), 0b0000_1111);
+        assert_eq!(pla.decode(pinio::O(u5::new(0b0_0110))), 0);
+    }
+}
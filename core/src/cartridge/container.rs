@@ -0,0 +1,645 @@
+//! A self-describing container format for Microvision cartridges.
+//!
+//! Unlike a raw ROM dump, a container holds everything needed to run a cartridge,
+//! i.e. the ROM and every cartridge [setting](Settings), along with metadata such
+//! as the title of the game and its [overlay](Overlay).
+//!
+//! # Format
+//!
+//! Containers use a little-endian binary format, which begins with a short header:
+//!
+//! | Offset | Size | Contents                                        |
+//! |--------|------|-------------------------------------------------|
+//! | `0`    | `4`  | The [`MAGIC`] bytes.                            |
+//! | `4`    | `1`  | The format [`VERSION`].                         |
+//! | `5`    | `1`  | The type of micro-processor.                    |
+//! | `6`    | `1`  | The flags, bit 0 enables the rotary controller. |
+//!
+//! The header is followed by a list of chunks, each chunk begins with a 4-byte tag
+//! and the 16-bit length of its contents:
+//!
+//! | Tag    | Contents                                                          |
+//! |--------|-------------------------------------------------------------------|
+//! | `ROM ` | The ROM data, up to the ROM size of the micro-processor, this     |
+//! |        | chunk is required.                                                |
+//! | `OPLA` | The 32 entries of the output PLA.                                 |
+//! | `MPLA` | The 128 16-bit entries of the micro-instruction PLA.              |
+//! | `CHRG` | The 64-bit offset and scale of the rotary charge calibration.     |
+//! | `NAME` | The UTF-8 title of the game.                                      |
+//! | `YEAR` | The 16-bit year the game was released.                            |
+//! | `KEYS` | The UTF-8 label of every key of the overlay, in the order of      |
+//! |        | [`Key::ALL`], each prefixed by its 8-bit length.                  |
+//! | `RGNS` | The colour regions of the overlay, up to [`MAX_REGIONS`], each as |
+//! |        | its 8-bit X and Y position, width and height, and RGBA colour.    |
+//!
+//! [`Key::ALL`]: crate::keypad::Key::ALL
+//!
+//! Every chunk may appear at most once, and unknown chunks are skipped, which
+//! allows adding chunks without breaking older parsers.
+
+use super::{
+    settings::{ChargeInfo, CpuType, OutputPla, Settings},
+    Cartridge,
+};
+use crate::{
+    error,
+    overlay::{Overlay, Region, MAX_REGIONS},
+    snapshot::{self, Reader, State, Writer},
+    tms1100::pla::MicroPla,
+};
+
+use core::{fmt, str};
+
+/// The magic bytes at the beginning of every container.
+pub const MAGIC: [u8; 4] = *b"MLTC";
+
+/// The current version of the container format.
+pub const VERSION: u8 = 1;
+
+/// The size of the container header, in bytes.
+const HEADER_SIZE: usize = 7;
+
+/// The size of a chunk tag and length, in bytes.
+const CHUNK_SIZE: usize = 6;
+
+/// The flag enabling the rotary controller.
+const ROTARY: u8 = 1;
+
+/// The size of a colour region within the `RGNS` chunk, in bytes.
+const REGION_SIZE: usize = 8;
+
+/// The tags of the known chunks.
+const TAGS: [[u8; 4]; 8] = [
+    *b"ROM ", *b"OPLA", *b"MPLA", *b"CHRG", *b"NAME", *b"YEAR", *b"KEYS", *b"RGNS",
+];
+
+/// An error encountered while parsing or writing a container.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The given buffer is too small to hold the container.
+    BufferTooSmall,
+    /// The container ends in the middle of the header or a chunk.
+    Truncated,
+    /// The data does not begin with the expected [`MAGIC`] bytes.
+    BadMagic,
+    /// The container was written with a different version of the format.
+    VersionMismatch {
+        /// The version stored in the container.
+        found: u8,
+    },
+    /// The type of micro-processor is unknown.
+    UnknownCpu {
+        /// The stored type of micro-processor.
+        found: u8,
+    },
+    /// The container sets unknown flags.
+    UnknownFlags {
+        /// The stored flags.
+        found: u8,
+    },
+    /// A chunk appears more than once.
+    DuplicateChunk {
+        /// The tag of the chunk.
+        tag: [u8; 4],
+    },
+    /// A chunk has the wrong length for its contents.
+    BadChunk {
+        /// The tag of the chunk.
+        tag: [u8; 4],
+    },
+    /// The container has no ROM chunk.
+    MissingRom,
+    /// The container holds an invalid value, e.g. a ROM larger than the ROM of
+    /// the micro-processor.
+    Invalid(error::Error),
+    /// A string is not valid UTF-8, or too long to be stored.
+    BadString,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        /// Display a chunk tag.
+        fn tag(tag: &[u8; 4]) -> &str {
+            str::from_utf8(tag).unwrap_or("????")
+        }
+
+        match self {
+            Self::BufferTooSmall => write!(f, "the buffer is too small to hold the container"),
+            Self::Truncated => write!(f, "the container is truncated"),
+            Self::BadMagic => write!(f, "the data is not a cartridge container"),
+            Self::VersionMismatch { found } => write!(
+                f,
+                "the container has version {found}, expected version {VERSION}"
+            ),
+            Self::UnknownCpu { found } => write!(f, "unknown micro-processor type {found}"),
+            Self::UnknownFlags { found } => write!(f, "unknown flags {found:#04x}"),
+            Self::DuplicateChunk { tag: t } => write!(f, "the `{}` chunk appears twice", tag(t)),
+            Self::BadChunk { tag: t } => write!(f, "the `{}` chunk is malformed", tag(t)),
+            Self::MissingRom => write!(f, "the container has no rom"),
+            Self::Invalid(err) => write!(f, "{err}"),
+            Self::BadString => write!(f, "a string is not valid utf-8 or too long"),
+        }
+    }
+}
+
+impl From<error::Error> for Error {
+    fn from(err: error::Error) -> Self {
+        Self::Invalid(err)
+    }
+}
+
+/// A cartridge, along with its metadata.
+#[derive(Debug, Clone, Copy)]
+pub struct Container<'a> {
+    /// The ROM data.
+    pub rom: &'a [u8],
+    /// The cartridge-specific settings.
+    pub settings: Settings,
+    /// The title of the game, if known.
+    pub title: Option<&'a str>,
+    /// The year the game was released, if known.
+    pub year: Option<u16>,
+    /// The overlay of the cartridge, if known.
+    ///
+    /// Only its colour regions and key labels are stored, its title is the title
+    /// of the container.
+    pub overlay: Option<Overlay<'a>>,
+}
+
+impl<'a> Container<'a> {
+    /// Create a new container holding the given ROM data and settings, without
+    /// any metadata.
+    #[must_use]
+    pub fn new(rom: &'a [u8], settings: Settings) -> Self {
+        Self {
+            rom,
+            settings,
+            title: None,
+            year: None,
+            overlay: None,
+        }
+    }
+
+    /// Create a new cartridge from this container, with cleared RAM.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the ROM is larger than the ROM of the
+    /// micro-processor, which is never the case for parsed containers.
+    pub fn cartridge(&self) -> Result<Cartridge, error::Error> {
+        Cartridge::try_new(self.rom, self.settings)
+    }
+
+    /// Parse a container.
+    ///
+    /// The ROM and metadata of the returned container borrow from the given data.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the data is not a valid container.
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        let mut r = Reader::new(data);
+
+        if read(r.array())? != MAGIC {
+            return Err(Error::BadMagic);
+        }
+        let version = read(r.u8())?;
+        if version != VERSION {
+            return Err(Error::VersionMismatch { found: version });
+        }
+        let cpu = read(r.u8())?;
+        let cpu = CpuType::from_index(cpu).ok_or(Error::UnknownCpu { found: cpu })?;
+        let flags = read(r.u8())?;
+        if flags & !ROTARY != 0 {
+            return Err(Error::UnknownFlags { found: flags });
+        }
+
+        let mut container = Self::new(
+            &[],
+            Settings {
+                cpu,
+                charge_info: ChargeInfo::default(),
+                output_pla: OutputPla::default(),
+                micro_pla: None,
+                rotary_enabled: flags & ROTARY != 0,
+            },
+        );
+
+        // The known chunks which have been seen.
+        let mut seen = [false; TAGS.len()];
+
+        while r.pos() < data.len() {
+            let tag = read(r.array())?;
+            let len = usize::from(read(r.u16())?);
+            let contents = read(r.bytes(len))?;
+
+            let Some(idx) = TAGS.iter().position(|known| *known == tag) else {
+                continue;
+            };
+            if seen[idx] {
+                return Err(Error::DuplicateChunk { tag });
+            }
+            seen[idx] = true;
+
+            container.parse_chunk(tag, contents)?;
+        }
+
+        if !seen[0] {
+            return Err(Error::MissingRom);
+        }
+        if let Some(overlay) = &mut container.overlay {
+            overlay.title = container.title;
+        }
+        Ok(container)
+    }
+
+    /// Parse the contents of a known chunk.
+    fn parse_chunk(&mut self, tag: [u8; 4], contents: &'a [u8]) -> Result<(), Error> {
+        let mut r = Reader::new(contents);
+        let bad = |_| Error::BadChunk { tag };
+
+        match &tag {
+            b"ROM " => {
+                // The header, and therefore the micro-processor, precedes every chunk.
+                self.settings.cpu.check_rom_size(contents.len())?;
+                self.rom = contents;
+            }
+            b"OPLA" => self.settings.output_pla.load(&mut r).map_err(bad)?,
+            b"MPLA" => {
+                let mut pla = MicroPla::tms1100();
+                pla.load(&mut r).map_err(bad)?;
+                self.settings.micro_pla = Some(pla);
+            }
+            b"CHRG" => {
+                self.settings.charge_info = ChargeInfo {
+                    offset: r.usize().map_err(bad)?,
+                    scale: r.usize().map_err(bad)?,
+                };
+            }
+            b"NAME" => self.title = Some(str::from_utf8(contents).map_err(|_| Error::BadString)?),
+            b"YEAR" => self.year = Some(r.u16().map_err(bad)?),
+            b"KEYS" => {
+                let overlay = self.overlay.get_or_insert_with(Overlay::new);
+                for key in &mut overlay.keys {
+                    let len = usize::from(r.u8().map_err(bad)?);
+                    let label = r.bytes(len).map_err(bad)?;
+                    *key = str::from_utf8(label).map_err(|_| Error::BadString)?;
+                }
+            }
+            // The `RGNS` chunk.
+            _ => {
+                if !contents.len().is_multiple_of(REGION_SIZE)
+                    || contents.len() / REGION_SIZE > MAX_REGIONS
+                {
+                    return Err(Error::BadChunk { tag });
+                }
+
+                let overlay = self.overlay.get_or_insert_with(Overlay::new);
+                for slot in overlay
+                    .regions
+                    .iter_mut()
+                    .take(contents.len() / REGION_SIZE)
+                {
+                    let [x, y, width, height] = r.array().map_err(bad)?;
+                    let region = Region {
+                        x,
+                        y,
+                        width,
+                        height,
+                        color: r.array().map_err(bad)?,
+                    };
+                    if !region.is_valid() {
+                        return Err(Error::BadChunk { tag });
+                    }
+                    *slot = Some(region);
+                }
+            }
+        }
+
+        if r.pos() != contents.len() && &tag != b"ROM " && &tag != b"NAME" {
+            return Err(Error::BadChunk { tag });
+        }
+        Ok(())
+    }
+
+    /// Return the size of this container, once written, in bytes.
+    #[must_use]
+    pub fn size(&self) -> usize {
+        let mut size = HEADER_SIZE + CHUNK_SIZE + self.rom.len();
+        size += CHUNK_SIZE + <OutputPla as State>::SIZE;
+        size += CHUNK_SIZE + 16;
+        if self.settings.micro_pla.is_some() {
+            size += CHUNK_SIZE + <MicroPla as State>::SIZE;
+        }
+        if let Some(title) = self.title {
+            size += CHUNK_SIZE + title.len();
+        }
+        if self.year.is_some() {
+            size += CHUNK_SIZE + 2;
+        }
+        if let Some(overlay) = &self.overlay {
+            size += CHUNK_SIZE + overlay.keys.iter().map(|key| 1 + key.len()).sum::<usize>();
+            let regions = overlay.regions().count();
+            if regions != 0 {
+                size += CHUNK_SIZE + regions * REGION_SIZE;
+            }
+        }
+        size
+    }
+
+    /// Write this container into a buffer.
+    ///
+    /// This returns the amount of bytes written, which is always
+    /// [`size`](Self::size).
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the ROM is larger than the ROM of the
+    /// micro-processor, the title or a key label is too long, a region of the
+    /// overlay lies outside of the screen, or the given buffer is smaller than
+    /// [`size`](Self::size).
+    pub fn write(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.settings.cpu.check_rom_size(self.rom.len())?;
+        let title_len = self.title.map_or(Ok(0), |title| {
+            u16::try_from(title.len()).map_err(|_| Error::BadString)
+        })?;
+        if let Some(overlay) = &self.overlay {
+            if overlay
+                .keys
+                .iter()
+                .any(|key| key.len() > usize::from(u8::MAX))
+            {
+                return Err(Error::BadString);
+            }
+            if overlay.regions().any(|region| !region.is_valid()) {
+                return Err(Error::BadChunk { tag: *b"RGNS" });
+            }
+        }
+        if buf.len() < self.size() {
+            return Err(Error::BufferTooSmall);
+        }
+
+        let mut w = Writer::new(buf);
+        w.bytes(&MAGIC);
+        w.u8(VERSION);
+        w.u8(self.settings.cpu.index());
+        w.u8(if self.settings.rotary_enabled {
+            ROTARY
+        } else {
+            0
+        });
+
+        write_chunk(&mut w, *b"ROM ", self.rom.len());
+        w.bytes(self.rom);
+
+        write_chunk(&mut w, *b"OPLA", <OutputPla as State>::SIZE);
+        self.settings.output_pla.save(&mut w);
+
+        if let Some(pla) = &self.settings.micro_pla {
+            write_chunk(&mut w, *b"MPLA", <MicroPla as State>::SIZE);
+            pla.save(&mut w);
+        }
+
+        write_chunk(&mut w, *b"CHRG", 16);
+        w.usize(self.settings.charge_info.offset);
+        w.usize(self.settings.charge_info.scale);
+
+        if let Some(title) = self.title {
+            write_chunk(&mut w, *b"NAME", usize::from(title_len));
+            w.bytes(title.as_bytes());
+        }
+
+        if let Some(year) = self.year {
+            write_chunk(&mut w, *b"YEAR", 2);
+            w.u16(year);
+        }
+
+        if let Some(overlay) = &self.overlay {
+            write_chunk(
+                &mut w,
+                *b"KEYS",
+                overlay.keys.iter().map(|key| 1 + key.len()).sum::<usize>(),
+            );
+            for key in overlay.keys {
+                w.u8(u8::try_from(key.len()).unwrap_or(u8::MAX));
+                w.bytes(key.as_bytes());
+            }
+
+            let regions = overlay.regions().count();
+            if regions != 0 {
+                write_chunk(&mut w, *b"RGNS", regions * REGION_SIZE);
+                for region in overlay.regions() {
+                    w.bytes(&[region.x, region.y, region.width, region.height]);
+                    w.bytes(&region.color);
+                }
+            }
+        }
+
+        Ok(w.pos())
+    }
+}
+
+/// Write the tag and length of a chunk.
+fn write_chunk(w: &mut Writer, tag: [u8; 4], len: usize) {
+    w.bytes(&tag);
+    // Every chunk length is verified to fit beforehand.
+    w.u16(u16::try_from(len).unwrap_or(u16::MAX));
+}
+
+/// Map the result of reading the header or a chunk header.
+fn read<T>(result: Result<T, snapshot::Error>) -> Result<T, Error> {
+    result.map_err(|_| Error::Truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keypad::Key;
+
+    /// Write a container into a fixed buffer, returning the buffer and its length.
+    fn written(container: &Container) -> ([u8; 0x1000], usize) {
+        let mut buf = [0; 0x1000];
+        let len = container.write(&mut buf).unwrap();
+        assert_eq!(len, container.size());
+        (buf, len)
+    }
+
+    /// A container using every chunk.
+    fn full(rom: &[u8]) -> Container<'_> {
+        let mut container = Container::new(
+            rom,
+            Settings {
+                cpu: CpuType::I8021,
+                charge_info: ChargeInfo {
+                    offset: 500,
+                    scale: 70,
+                },
+                output_pla: OutputPla::NORMAL,
+                micro_pla: Some(MicroPla::tms1100()),
+                rotary_enabled: true,
+            },
+        );
+        container.title = Some("Test");
+        container.year = Some(1979);
+        container.overlay = Some(Overlay {
+            keys: ["1", "", "", "", "", "", "", "", "", "", "", "Start"],
+            ..Overlay::parse("region 0 0 16 4 ff4040\nregion 0 12 16 4 4040ff80").unwrap()
+        });
+        container
+    }
+
+    #[test]
+    fn round_trip() {
+        let rom = [0x8f, 0x3a, 0x00, 0x12];
+        let (buf, len) = written(&full(&rom));
+
+        let parsed = Container::parse(&buf[..len]).unwrap();
+        assert_eq!(parsed.rom, rom);
+        assert_eq!(parsed.settings.cpu, CpuType::I8021);
+        assert_eq!(parsed.settings.charge_info.scale, 70);
+        assert!(parsed.settings.rotary_enabled);
+        assert_eq!(parsed.title, Some("Test"));
+        assert_eq!(parsed.year, Some(1979));
+        let overlay = parsed.overlay.unwrap();
+        assert_eq!(overlay.title, Some("Test"));
+        assert_eq!(overlay.label(Key::At2x3), Some("Start"));
+        assert_eq!(overlay.label(Key::At0x1), None);
+        assert_eq!(overlay.regions, full(&rom).overlay.unwrap().regions);
+        assert_eq!(written(&parsed).0[..len], buf[..len]);
+
+        let minimal = Container::new(&rom, full(&rom).settings);
+        let (buf, len) = written(&minimal);
+        let parsed = Container::parse(&buf[..len]).unwrap();
+        assert_eq!(
+            (parsed.title, parsed.year, parsed.overlay),
+            (None, None, None)
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let rom = [0; 0x801];
+        let container = full(&rom[..4]);
+        let (buf, len) = written(&container);
+
+        assert_eq!(Container::parse(&buf[..3]).unwrap_err(), Error::Truncated);
+        assert_eq!(
+            Container::parse(&buf[..len - 1]).unwrap_err(),
+            Error::Truncated
+        );
+        assert_eq!(
+            Container::parse(b"MLTB\x01\x00\x00").unwrap_err(),
+            Error::BadMagic
+        );
+        assert_eq!(
+            Container::parse(b"MLTC\x02\x00\x00").unwrap_err(),
+            Error::VersionMismatch { found: 2 }
+        );
+        assert_eq!(
+            Container::parse(b"MLTC\x01\x09\x00").unwrap_err(),
+            Error::UnknownCpu { found: 9 }
+        );
+        assert_eq!(
+            Container::parse(b"MLTC\x01\x00\x00").unwrap_err(),
+            Error::MissingRom
+        );
+        assert_eq!(
+            Container::parse(b"MLTC\x01\x00\x00YEAR\x01\x00\x00").unwrap_err(),
+            Error::BadChunk { tag: *b"YEAR" }
+        );
+        assert_eq!(
+            Container::parse(b"MLTC\x01\x00\x00ROM \x00\x00ROM \x00\x00").unwrap_err(),
+            Error::DuplicateChunk { tag: *b"ROM " }
+        );
+
+        assert_eq!(
+            Container::parse(
+                b"MLTC\x01\x00\x00ROM \x00\x00RGNS\x07\x00\x00\x00\x01\x01\xff\xff\xff"
+            )
+            .unwrap_err(),
+            Error::BadChunk { tag: *b"RGNS" }
+        );
+        assert_eq!(
+            Container::parse(
+                b"MLTC\x01\x00\x00ROM \x00\x00RGNS\x08\x00\x08\x00\x09\x01\xff\xff\xff\xff"
+            )
+            .unwrap_err(),
+            Error::BadChunk { tag: *b"RGNS" }
+        );
+
+        // Unknown chunks are skipped.
+        assert!(Container::parse(b"MLTC\x01\x00\x00ROM \x00\x00XTRA\x01\x00\xff").is_ok());
+
+        assert_eq!(
+            Container::new(&rom, container.settings).write(&mut [0; 0x1000]),
+            Err(Error::Invalid(error::Error::RomTooLarge {
+                size: 0x801,
+                max: 0x400
+            }))
+        );
+
+        // The ROM size depends on the micro-processor.
+        let mut data = [0; 0x500];
+        let mut tms1100 = Container::new(&rom[..0x401], Settings::default());
+        let len = tms1100.write(&mut data).unwrap();
+        assert!(Container::parse(&data[..len]).unwrap().cartridge().is_ok());
+        tms1100.settings.cpu = CpuType::I8021;
+        assert_eq!(
+            tms1100.write(&mut data),
+            Err(Error::Invalid(error::Error::RomTooLarge {
+                size: 0x401,
+                max: 0x400
+            }))
+        );
+        data[5] = CpuType::I8021.index();
+        assert_eq!(
+            Container::parse(&data[..len]).unwrap_err(),
+            Error::Invalid(error::Error::RomTooLarge {
+                size: 0x401,
+                max: 0x400
+            })
+        );
+        assert_eq!(container.write(&mut [0; 16]), Err(Error::BufferTooSmall));
+    }
+
+    #[test]
+    fn fuzz() {
+        /// A xorshift pseudo-random number generator, so failures are reproducible.
+        struct Rng(u64);
+
+        impl Rng {
+            fn next(&mut self, max: usize) -> usize {
+                self.0 ^= self.0 << 13;
+                self.0 ^= self.0 >> 7;
+                self.0 ^= self.0 << 17;
+                usize::try_from(self.0 % max as u64).unwrap()
+            }
+        }
+
+        let rom = [0x8f, 0x3a, 0x00, 0x12];
+        let (valid, len) = written(&full(&rom));
+        let mut rng = Rng(0x4d49_4c54_4f4e);
+
+        for _ in 0..20_000 {
+            let mut data = valid;
+            let mut end = len;
+
+            // Corrupt a few bytes, then possibly truncate or extend the data.
+            for _ in 0..=rng.next(4) {
+                data[rng.next(end)] = u8::try_from(rng.next(256)).unwrap();
+            }
+            match rng.next(4) {
+                0 => end = rng.next(end + 1),
+                1 => end += rng.next(16),
+                _ => {}
+            }
+
+            // Parsing must never panic, and a parsed container must round-trip.
+            if let Ok(container) = Container::parse(&data[..end]) {
+                let (buf, len) = written(&container);
+                let reparsed = Container::parse(&buf[..len]).unwrap();
+                assert_eq!(written(&reparsed).0[..len], buf[..len]);
+            }
+        }
+    }
+}
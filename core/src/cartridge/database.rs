@@ -0,0 +1,208 @@
+//! A database of known Microvision cartridges.
+//!
+//! Cartridges are identified by the CRC-32 of their ROM dump, which replaces the
+//! need for frontends to know the [`Settings`] of every game by hand.
+//!
+//! # Status
+//!
+//! The built-in database is empty. Its entries are to come from the CRC-32 and
+//! settings of the Microvision software list of MAME, which have not been verified
+//! against actual dumps yet, and guessed entries would silently misconfigure games.
+//! Until then, [`lookup`] finds no game, every raw ROM dump runs with the default
+//! [`Settings`], and frontends should report this to the user, as the tools do.
+//! Frontends can supply their own list of games through [`lookup_in`].
+
+use super::{
+    settings::{ChargeInfo, CpuType, OutputPla, Settings},
+    Cartridge,
+};
+use crate::{error, overlay::Overlay};
+
+use core::fmt;
+
+/// The CRC-32 (IEEE 802.3) lookup table, for every byte value.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut idx = 0u32;
+    while idx < 256 {
+        let mut crc = idx;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[idx as usize] = crc;
+        idx += 1;
+    }
+    table
+};
+
+/// Return the CRC-32 (IEEE 802.3) of the given data, as used by e.g. MAME and
+/// No-Intro to identify ROM dumps.
+#[must_use]
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, byte| {
+        CRC32_TABLE[usize::from(crc.to_le_bytes()[0] ^ byte)] ^ (crc >> 8)
+    })
+}
+
+/// A known game cartridge.
+#[derive(Debug, Clone, Copy)]
+pub struct Game {
+    /// The CRC-32 of the ROM dump.
+    pub crc32: u32,
+    /// The title of the game.
+    pub title: &'static str,
+    /// The year the game was released.
+    pub year: u16,
+    /// The type of micro-processor on the cartridge.
+    pub cpu: CpuType,
+    /// The decode PLA for the O output of the TMS1100.
+    pub output_pla: OutputPla,
+    /// The calibration of the charge line to the rotary controller, if the game
+    /// uses the rotary controller.
+    pub rotary: Option<ChargeInfo>,
+    /// The overlay of the game, i.e. its colour regions and key labels.
+    pub overlay: Overlay<'static>,
+}
+
+impl Game {
+    /// Return the cartridge settings of this game.
+    #[must_use]
+    pub fn settings(&self) -> Settings {
+        Settings {
+            cpu: self.cpu,
+            charge_info: self.rotary.unwrap_or_default(),
+            output_pla: self.output_pla,
+            micro_pla: None,
+            rotary_enabled: self.rotary.is_some(),
+        }
+    }
+}
+
+/// The built-in database of known games.
+///
+/// This is empty for now, see the [status](self#status) of the database.
+pub const GAMES: &[Game] = &[];
+
+/// Return the known game with the given ROM dump, from the built-in database.
+#[must_use]
+pub fn lookup(data: &[u8]) -> Option<&'static Game> {
+    lookup_in(GAMES, data)
+}
+
+/// Return the known game with the given ROM dump, from the given list of games.
+#[must_use]
+pub fn lookup_in<'a>(games: &'a [Game], data: &[u8]) -> Option<&'a Game> {
+    let crc32 = crc32(data);
+    games.iter().find(|game| game.crc32 == crc32)
+}
+
+/// An error encountered while creating a cartridge from a known ROM dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The ROM dump is invalid for the game, i.e. larger than the ROM of its
+    /// micro-processor.
+    Invalid(error::Error),
+    /// The ROM dump is not listed in the database.
+    Unknown {
+        /// The CRC-32 of the ROM dump.
+        crc32: u32,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(err) => write!(f, "{err}"),
+            Self::Unknown { crc32 } => write!(f, "unknown rom dump with crc32 {crc32:08x}"),
+        }
+    }
+}
+
+impl Cartridge {
+    /// Create a cartridge from a ROM dump of a known game, using the settings of
+    /// the game listed in the built-in database.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the ROM dump is too large, or not a known game.
+    pub fn from_known_rom(data: &[u8]) -> Result<Self, Error> {
+        Self::from_rom_in(GAMES, data)
+    }
+
+    /// Create a cartridge from a ROM dump of a known game, using the settings of
+    /// the game listed in the given list of games.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the ROM dump is too large, or not a known game.
+    pub fn from_rom_in(games: &[Game], data: &[u8]) -> Result<Self, Error> {
+        let game = lookup_in(games, data).ok_or_else(|| Error::Unknown { crc32: crc32(data) })?;
+
+        Self::try_new(data, game.settings()).map_err(Error::Invalid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keypad::Key;
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn known_roms() {
+        let games = [Game {
+            crc32: crc32(&[0x8f, 0x3a]),
+            title: "Test",
+            year: 1979,
+            cpu: CpuType::I8021,
+            output_pla: OutputPla::NORMAL,
+            rotary: Some(ChargeInfo::default()),
+            overlay: Overlay {
+                keys: ["1", "", "", "", "", "", "", "", "", "", "", "Start"],
+                ..Overlay::new()
+            },
+        }];
+
+        let cart = Cartridge::from_rom_in(&games, &[0x8f, 0x3a]).unwrap();
+        assert_eq!(cart.settings.cpu, CpuType::I8021);
+        assert!(cart.settings.rotary_enabled);
+        assert_eq!(games[0].overlay.label(Key::At2x3), Some("Start"));
+
+        assert_eq!(
+            Cartridge::from_rom_in(&games, &[0x8f]).unwrap_err(),
+            Error::Unknown {
+                crc32: crc32(&[0x8f])
+            }
+        );
+        assert_eq!(
+            Cartridge::from_known_rom(&[0; 0x801]).unwrap_err(),
+            Error::Unknown {
+                crc32: crc32(&[0; 0x801])
+            }
+        );
+
+        // The ROM size depends on the micro-processor of the game.
+        let games = [Game {
+            crc32: crc32(&[0; 0x401]),
+            ..games[0]
+        }];
+        assert_eq!(
+            Cartridge::from_rom_in(&games, &[0; 0x401]).unwrap_err(),
+            Error::Invalid(error::Error::RomTooLarge {
+                size: 0x401,
+                max: 0x400
+            })
+        );
+    }
+}
@@ -15,6 +15,12 @@ macro_rules! line_type {
 }
 pub(crate) use line_type;
 
+/// The amount of times a console is clocked within a single frame.
+///
+/// This is based on the 100khz clock rate of the console, and a frame rate of
+/// 60 frames per second.
+pub const FRAME_CLOCKS: usize = 100_000 / 60;
+
 /// A 1-bit (boolean) input/output signal line.
 ///
 /// This is used for inter-chip communication and to transfer state from one
@@ -0,0 +1,480 @@
+//! An interactive debugger built around [`Console::clock`].
+//!
+//! # Logic
+//!
+//! The debugger clocks the console on behalf of a frontend, checking every
+//! breakpoint and watchpoint in between clocks, until either a requested [`Step`]
+//! has been completed or a reason to stop is encountered, which is reported as a
+//! [`Stop`].
+//!
+//! Breakpoints and watchpoints are checked on the boundary between two instructions,
+//! i.e. when the micro-processor is about to execute the [`Cycle::On0`] cycle of an
+//! instruction. A watchpoint is reported once the instruction accessing the watched
+//! RAM address has been completed.
+//!
+//! # Note
+//!
+//! Breakpoints, watchpoints and the R/O pins only exist on the TMS1000 family of
+//! micro-processors, therefore an Intel 8021 micro-controller can only be stepped
+//! by clock and by frame, stepping by instruction steps a single clock instead.
+
+use crate::{
+    buzzer,
+    cartridge::Cartridge,
+    common::{Interface, FRAME_CLOCKS},
+    display, keypad, rotary,
+    tms1100::{
+        mem::{RamAddr, RomAddr},
+        pla::{
+            instructions::{MTN, MTP},
+            Fixed,
+        },
+        Cycle, Tms1100,
+    },
+    Console, Cpu,
+};
+
+use core::fmt;
+
+use arbitrary_int::u5;
+
+/// The maximum amount of breakpoints.
+pub const MAX_BREAKPOINTS: usize = 16;
+
+/// The maximum amount of watchpoints.
+pub const MAX_WATCHPOINTS: usize = 16;
+
+/// An error encountered while configuring the debugger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The maximum amount of breakpoints or watchpoints has been reached.
+    Full,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full => write!(
+                f,
+                "the maximum amount of break/watchpoints has been reached"
+            ),
+        }
+    }
+}
+
+/// A kind of RAM access.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// The RAM is read from.
+    Read,
+    /// The RAM is written to.
+    Write,
+    /// The RAM is both read from and written to.
+    ReadWrite,
+}
+
+impl Access {
+    /// Create a kind of RAM access from the given read and write flags.
+    fn from_flags(read: bool, write: bool) -> Option<Self> {
+        match (read, write) {
+            (true, false) => Some(Self::Read),
+            (false, true) => Some(Self::Write),
+            (true, true) => Some(Self::ReadWrite),
+            (false, false) => None,
+        }
+    }
+
+    /// Check if this kind of access reads from RAM.
+    fn reads(self) -> bool {
+        matches!(self, Self::Read | Self::ReadWrite)
+    }
+
+    /// Check if this kind of access writes to RAM.
+    fn writes(self) -> bool {
+        matches!(self, Self::Write | Self::ReadWrite)
+    }
+
+    /// Check if this kind of access overlaps with another kind of access.
+    fn overlaps(self, other: Self) -> bool {
+        (self.reads() && other.reads()) || (self.writes() && other.writes())
+    }
+}
+
+/// An amount of emulation to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// Run a single clock, or sub-instruction [`Cycle`].
+    Cycle,
+    /// Run until the next instruction is reached.
+    Instruction,
+    /// Run until the next instruction is reached, if the current instruction is a
+    /// `CALL` then run until the called subroutine has returned.
+    Over,
+    /// Run a whole frame, see [`FRAME_CLOCKS`].
+    Frame,
+}
+
+/// The reason the debugger stopped running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// The requested step has been completed.
+    Done,
+    /// A breakpoint has been reached, the instruction at the given address has not
+    /// been executed yet.
+    Breakpoint(RomAddr),
+    /// A watched RAM address has been accessed.
+    Watchpoint {
+        /// The address of the instruction which accessed the RAM.
+        pc: RomAddr,
+        /// The accessed RAM address.
+        addr: RamAddr,
+        /// The kind of access performed.
+        access: Access,
+    },
+    /// The `R` output pins have changed.
+    R {
+        /// The previous state of the pins.
+        old: u16,
+        /// The current state of the pins.
+        new: u16,
+    },
+    /// The `O` output pins have changed.
+    O {
+        /// The previous state of the pins.
+        old: u5,
+        /// The current state of the pins.
+        new: u5,
+    },
+}
+
+/// An interactive debugger.
+#[derive(Debug, Clone)]
+pub struct Debugger {
+    /// The active breakpoints.
+    breakpoints: [Option<RomAddr>; MAX_BREAKPOINTS],
+    /// The active watchpoints.
+    watchpoints: [Option<(RamAddr, Access)>; MAX_WATCHPOINTS],
+    /// A watchpoint hit by the instruction currently being executed.
+    pending: Option<Stop>,
+    /// A flag determining if the debugger stops when the `R` pins change.
+    pub break_on_r: bool,
+    /// A flag determining if the debugger stops when the `O` pins change.
+    pub break_on_o: bool,
+}
+
+impl Debugger {
+    /// Create a new debugger, without any breakpoints or watchpoints.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            breakpoints: [None; MAX_BREAKPOINTS],
+            watchpoints: [None; MAX_WATCHPOINTS],
+            pending: None,
+            break_on_r: false,
+            break_on_o: false,
+        }
+    }
+
+    /// Add a breakpoint at the given ROM address.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if [`MAX_BREAKPOINTS`] breakpoints are
+    /// already active.
+    pub fn add_breakpoint(&mut self, addr: RomAddr) -> Result<(), Error> {
+        if self.breakpoints.contains(&Some(addr)) {
+            return Ok(());
+        }
+
+        let slot = self.breakpoints.iter_mut().find(|slot| slot.is_none());
+        *slot.ok_or(Error::Full)? = Some(addr);
+        Ok(())
+    }
+
+    /// Remove the breakpoint at the given ROM address.
+    ///
+    /// This returns a boolean indicating if the breakpoint existed.
+    pub fn remove_breakpoint(&mut self, addr: RomAddr) -> bool {
+        let slot = self
+            .breakpoints
+            .iter_mut()
+            .find(|slot| **slot == Some(addr));
+        slot.is_some_and(|slot| slot.take().is_some())
+    }
+
+    /// Return an iterator over the active breakpoints.
+    pub fn breakpoints(&self) -> impl Iterator<Item = RomAddr> + '_ {
+        self.breakpoints.iter().flatten().copied()
+    }
+
+    /// Add a watchpoint for the given kind of access to the given RAM address.
+    ///
+    /// If the address is already watched, only the kind of access is updated.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if [`MAX_WATCHPOINTS`] watchpoints are
+    /// already active.
+    pub fn add_watchpoint(&mut self, addr: RamAddr, access: Access) -> Result<(), Error> {
+        let idx = self
+            .watchpoints
+            .iter()
+            .position(|slot| matches!(slot, Some((other, _)) if *other == addr))
+            .or_else(|| self.watchpoints.iter().position(Option::is_none));
+
+        self.watchpoints[idx.ok_or(Error::Full)?] = Some((addr, access));
+        Ok(())
+    }
+
+    /// Remove the watchpoint at the given RAM address.
+    ///
+    /// This returns a boolean indicating if the watchpoint existed.
+    pub fn remove_watchpoint(&mut self, addr: RamAddr) -> bool {
+        let slot = self
+            .watchpoints
+            .iter_mut()
+            .find(|slot| matches!(slot, Some((other, _)) if *other == addr));
+        slot.is_some_and(|slot| slot.take().is_some())
+    }
+
+    /// Return an iterator over the active watchpoints.
+    pub fn watchpoints(&self) -> impl Iterator<Item = (RamAddr, Access)> + '_ {
+        self.watchpoints.iter().flatten().copied()
+    }
+
+    /// Run the given console until the given step has been completed, or until a
+    /// breakpoint, watchpoint or pin change is encountered.
+    ///
+    /// A breakpoint at the current instruction is ignored, so that running can be
+    /// resumed after a breakpoint has been reached.
+    ///
+    /// # Timing
+    ///
+    /// As with [`Console::clock`], every clock represents 10 **micro**-seconds, a
+    /// frontend should only call [`Console::sync`] after a whole frame has been run.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn run<L, B, K, R>(
+        &mut self,
+        console: &mut Console,
+        cart: &mut Cartridge,
+        hardware: Interface<L, B, K, R>,
+        step: Step,
+    ) -> Stop
+    where
+        L: display::Api,
+        B: buzzer::Api,
+        K: keypad::Api,
+        R: rotary::Api,
+    {
+        let mut clocks = 0;
+        let mut over = false;
+
+        loop {
+            let mut access = None;
+
+            if let Cpu::Tms1100(cpu) = &console.cpu {
+                if cpu.cycle == Cycle::On0 {
+                    if let Some(stop) = self.check_boundary(cpu, step, clocks, &mut over) {
+                        return stop;
+                    }
+                    access = self.check_access(cpu);
+                }
+            }
+
+            let done = match step {
+                Step::Cycle => clocks == 1,
+                Step::Instruction | Step::Over => {
+                    clocks == 1 && !matches!(console.cpu, Cpu::Tms1100(_))
+                }
+                Step::Frame => clocks == FRAME_CLOCKS,
+            };
+            if done {
+                return Stop::Done;
+            }
+
+            let pins = Self::pins(&console.cpu);
+            console.clock(
+                cart,
+                Interface {
+                    display: &mut *hardware.display,
+                    buzzer: &mut *hardware.buzzer,
+                    keypad: hardware.keypad,
+                    rotary: hardware.rotary,
+                },
+            );
+            clocks += 1;
+
+            if access.is_some() {
+                self.pending = access;
+            }
+            if let Some(stop) = self.check_pins(pins, Self::pins(&console.cpu)) {
+                return stop;
+            }
+        }
+    }
+
+    /// Check for reasons to stop on the boundary between two instructions.
+    fn check_boundary(
+        &mut self,
+        cpu: &Tms1100,
+        step: Step,
+        clocks: usize,
+        over: &mut bool,
+    ) -> Option<Stop> {
+        if let Some(stop) = self.pending.take() {
+            return Some(stop);
+        }
+
+        if clocks == 0 {
+            *over = step == Step::Over && matches!(cpu.fixed, Some(Fixed::Call)) && !cpu.flags.call;
+            return None;
+        }
+
+        let addr = cpu.addr();
+        if self.breakpoints().any(|other| other == addr) {
+            return Some(Stop::Breakpoint(addr));
+        }
+
+        match step {
+            Step::Instruction => Some(Stop::Done),
+            Step::Over if !(*over && cpu.flags.call) => Some(Stop::Done),
+            _ => None,
+        }
+    }
+
+    /// Check if the instruction about to be executed accesses a watched RAM address.
+    fn check_access(&self, cpu: &Tms1100) -> Option<Stop> {
+        let bit = matches!(cpu.fixed, Some(Fixed::Sbit | Fixed::Rbit));
+        let read = bit || cpu.micro.enables::<MTP>() || cpu.micro.enables::<MTN>();
+        let write = cpu.writes_ram();
+
+        let access = Access::from_flags(read, write)?;
+        let addr = RamAddr::new(cpu.regs.x, cpu.regs.y);
+
+        self.watchpoints()
+            .any(|(other, kind)| other == addr && kind.overlaps(access))
+            .then(|| Stop::Watchpoint {
+                pc: cpu.addr(),
+                addr,
+                access,
+            })
+    }
+
+    /// Check if the `R` or `O` pins have changed, if requested.
+    fn check_pins(&self, old: Option<(u16, u5)>, new: Option<(u16, u5)>) -> Option<Stop> {
+        let ((old_r, old_o), (new_r, new_o)) = (old?, new?);
+
+        if self.break_on_r && old_r != new_r {
+            Some(Stop::R {
+                old: old_r,
+                new: new_r,
+            })
+        } else if self.break_on_o && old_o != new_o {
+            Some(Stop::O {
+                old: old_o,
+                new: new_o,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Return the state of the `R` and `O` pins of the given micro-processor.
+    fn pins(cpu: &Cpu) -> Option<(u16, u5)> {
+        match cpu {
+            Cpu::Tms1100(cpu) => Some((cpu.r.value(), cpu.o.value())),
+            Cpu::I8021(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, Nothing};
+
+    use arbitrary_int::{u1, u3, u4, u6};
+
+    /// Run the debugger with a hardware interface which does nothing.
+    fn run(dbg: &mut Debugger, console: &mut Console, cart: &mut Cartridge, step: Step) -> Stop {
+        let (mut a, mut b) = (Nothing, Nothing);
+        let hardware = Interface {
+            display: &mut a,
+            buzzer: &mut b,
+            keypad: &Nothing,
+            rotary: &Nothing,
+        };
+        dbg.run(console, cart, hardware, step)
+    }
+
+    /// Return the current instruction address of the given console.
+    fn addr(console: &Console) -> RomAddr {
+        let Cpu::Tms1100(cpu) = &console.cpu else {
+            panic!("expected a TMS1100 micro-processor");
+        };
+        cpu.addr()
+    }
+
+    #[test]
+    fn stops() {
+        let (mut console, mut cart) = (Console::new(), testing::cartridge());
+        let mut dbg = Debugger::new();
+        let rom = |addr| RomAddr::new(u1::new(0), u4::new(0), u6::new(addr));
+
+        assert_eq!(
+            run(&mut dbg, &mut console, &mut cart, Step::Cycle),
+            Stop::Done
+        );
+        assert_eq!(
+            run(&mut dbg, &mut console, &mut cart, Step::Instruction),
+            Stop::Done
+        );
+        assert_eq!(addr(&console), rom(0x00));
+
+        // SETR, with Y = 4.
+        dbg.break_on_r = true;
+        assert_eq!(
+            run(&mut dbg, &mut console, &mut cart, Step::Frame),
+            Stop::R { old: 0, new: 0x10 }
+        );
+
+        // TAM.
+        dbg.add_breakpoint(rom(0x07)).unwrap();
+        assert_eq!(
+            run(&mut dbg, &mut console, &mut cart, Step::Frame),
+            Stop::Breakpoint(rom(0x07))
+        );
+
+        // TAM, writing M(0, 4), the breakpoint at its address is skipped.
+        let ram = RamAddr::new(u3::new(0), u4::new(4));
+        dbg.add_watchpoint(ram, Access::Write).unwrap();
+        assert_eq!(
+            run(&mut dbg, &mut console, &mut cart, Step::Frame),
+            Stop::Watchpoint {
+                pc: rom(0x07),
+                addr: ram,
+                access: Access::Write
+            }
+        );
+        assert_eq!(addr(&console), rom(0x0f));
+
+        // Without a CALL, stepping over is the same as stepping an instruction.
+        assert_eq!(
+            run(&mut dbg, &mut console, &mut cart, Step::Over),
+            Stop::Done
+        );
+        assert_eq!(addr(&console), rom(0x1f));
+
+        assert!(dbg.remove_breakpoint(rom(0x07)));
+        assert!(dbg.remove_watchpoint(ram));
+        assert_eq!(
+            run(&mut dbg, &mut console, &mut cart, Step::Frame),
+            Stop::Done
+        );
+
+        for addr in 0..16 {
+            dbg.add_breakpoint(rom(addr)).unwrap();
+        }
+        assert_eq!(dbg.add_breakpoint(rom(0x3f)), Err(Error::Full));
+    }
+}
@@ -5,7 +5,10 @@
 //! - Random Notes: <http://studio2.org.uk/studio2/mv/HughesNotes.pdf>
 //! - Driver Manual: <http://studio2.org.uk/studio2/mv/Hughes0488LCDDriver.pdf>
 
-use crate::common::line_type;
+use crate::{
+    common::{line_type, Ms},
+    snapshot::{self, Reader, State, Writer},
+};
 
 use arbitrary_int::{u3, u4};
 
@@ -147,6 +150,7 @@ impl Hughes0488 {
         data: DataLine,
         pulse: LatchPulse,
         not_clock: NotDataClock,
+        lcd: &mut Lcd,
         frontend: &mut A,
     ) where
         A: Api,
@@ -187,6 +191,7 @@ impl Hughes0488 {
                     // instead they are enabled and eventually decay to off
                     // over a brief period of time.
                     if self.col.0 >> x & 1 != 0 {
+                        lcd.drive(x, y);
                         frontend.enable_pixel(x, y);
                     }
                 }
@@ -199,6 +204,248 @@ impl Hughes0488 {
     }
 }
 
+impl State for Hughes0488 {
+    const SIZE: usize = 16;
+
+    fn save(&self, w: &mut Writer) {
+        w.u8(self.data.0.value());
+        w.bool(self.pulse.0);
+        w.bool(self.not_clock.0);
+        for latch in self.latches.data {
+            w.u8(latch.value());
+        }
+        w.u8(self.latches.counter.value());
+        w.u16(self.row.0);
+        w.u16(self.col.0);
+    }
+
+    fn load(&mut self, r: &mut Reader) -> Result<(), snapshot::Error> {
+        self.data.0 = r.u4()?;
+        self.pulse.0 = r.bool()?;
+        self.not_clock.0 = r.bool()?;
+        for latch in &mut self.latches.data {
+            *latch = r.u4()?;
+        }
+        self.latches.counter = r.u3()?;
+        self.row.0 = r.u16()?;
+        self.col.0 = r.u16()?;
+        Ok(())
+    }
+}
+
+/// The time an LCD pixel has to be driven for to reach its full contrast.
+const RISE_TIME: Ms = Ms(100);
+
+/// The time an LCD pixel holds its contrast for after it was last driven.
+///
+/// This is slightly longer than a frame, so pixels refreshed every frame are
+/// shown steadily.
+const HOLD_TIME: Ms = Ms(20_000);
+
+/// The time an LCD pixel takes to fade out after its hold time has passed.
+const DECAY_TIME: Ms = Ms(40_000);
+
+/// The state of a single LCD pixel.
+#[derive(Debug, Clone, Copy)]
+struct Pixel {
+    /// The time this pixel was last driven.
+    last: Ms,
+    /// The (effective) time this pixel was driven for, up to the [`RISE_TIME`].
+    charge: Ms,
+}
+
+impl Pixel {
+    /// Return the charge left at the given time, after the pixel decayed.
+    fn residual(self, now: Ms) -> usize {
+        let age = now.0.saturating_sub(self.last.0);
+        let fade = DECAY_TIME.0 - age.saturating_sub(HOLD_TIME.0).min(DECAY_TIME.0);
+
+        self.charge.0 * fade / DECAY_TIME.0
+    }
+}
+
+/// The 16x16 liquid crystal panel of the Microvision.
+///
+/// # Logic
+///
+/// The pixels of the panel are not set or cleared, instead they are driven by
+/// the [`Row`] and [`Column`] outputs of the Hughes 0488. A pixel gains contrast
+/// while it is driven, holds it for a brief period of time once it is no longer
+/// driven, and then slowly fades out. Games multiplexing rows or flickering sprites
+/// are therefore shown at a lower (average) contrast, like the real screen.
+#[derive(Debug, Clone)]
+pub struct Lcd {
+    /// The pixels, row by row.
+    pixels: [Pixel; 256],
+    /// The current time.
+    now: Ms,
+}
+
+impl Lcd {
+    /// Create a new, blank, LCD panel.
+    #[must_use]
+    pub(crate) fn new() -> Self {
+        Self {
+            pixels: [Pixel {
+                last: Ms(0),
+                charge: Ms(0),
+            }; 256],
+            now: Ms(0),
+        }
+    }
+
+    /// Reset this LCD panel.
+    pub(crate) fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Clock (update) this LCD panel.
+    ///
+    /// # Logic
+    ///
+    /// This advances the time of the panel to the given time, which determines the
+    /// contrast of every pixel.
+    pub(crate) fn clock(&mut self, now: Ms) {
+        self.now = now;
+    }
+
+    /// Drive the pixel at the given X and Y coordinates for a single clock.
+    fn drive(&mut self, x: usize, y: usize) {
+        let now = self.now;
+        let pixel = &mut self.pixels[y * 16 + x];
+
+        // The amount of microseconds every hz (clock) at 100khz takes.
+        pixel.charge = Ms((pixel.residual(now) + 10).min(RISE_TIME.0));
+        pixel.last = now;
+    }
+
+    /// Return the brightness (contrast) of the pixel at the given X and Y screen
+    /// coordinates, from `0` (off) to `255` (fully on).
+    ///
+    /// See [`Api::enable_pixel`] for the screen mapping.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if either coordinate is outside the 16x16 screen.
+    #[must_use]
+    pub fn brightness(&self, x: usize, y: usize) -> u8 {
+        assert!(x < 16 && y < 16, "the pixel ({x}, {y}) is off screen");
+
+        let residual = self.pixels[y * 16 + x].residual(self.now);
+        u8::try_from(residual * 255 / RISE_TIME.0).unwrap_or(u8::MAX)
+    }
+}
+
+impl State for Lcd {
+    const SIZE: usize = 8 + 256 * 3;
+
+    fn save(&self, w: &mut Writer) {
+        w.usize(self.now.0);
+        for pixel in &self.pixels {
+            // Pixels which are off are all saved alike, which keeps the snapshot
+            // from changing while they are not driven.
+            if pixel.residual(self.now) == 0 {
+                w.zeros(3);
+                continue;
+            }
+
+            let age = self.now.0.saturating_sub(pixel.last.0);
+            w.u16(u16::try_from(age).unwrap_or(u16::MAX));
+            w.u8(u8::try_from(pixel.charge.0).unwrap_or(u8::MAX));
+        }
+    }
+
+    fn load(&mut self, r: &mut Reader) -> Result<(), snapshot::Error> {
+        self.now.0 = r.usize()?;
+        for pixel in &mut self.pixels {
+            let age = usize::from(r.u16()?);
+            let charge = usize::from(r.u8()?);
+            if charge > RISE_TIME.0 {
+                return Err(snapshot::Error::Corrupt);
+            }
+
+            pixel.last = Ms(self.now.0.saturating_sub(age));
+            pixel.charge = Ms(charge);
+        }
+        Ok(())
+    }
+}
+
+/// A double-buffered 16x16 framebuffer, implementing the display [`Api`].
+///
+/// # Logic
+///
+/// Pixels are enabled within the back buffer while a frame is emulated, at the end
+/// of every frame, i.e. on [`Console::sync`](crate::Console::sync), the back buffer
+/// becomes the front buffer and is then cleared for the next frame. Every row is
+/// stored as a 16-bit bitplane, where bit N is the pixel in column N.
+#[derive(Debug, Clone, Default)]
+pub struct Framebuffer {
+    /// The rows of the last complete frame.
+    front: [u16; 16],
+    /// The rows of the frame being emulated.
+    back: [u16; 16],
+    /// The rows which changed since the dirty rows were last taken.
+    dirty: u16,
+}
+
+impl Framebuffer {
+    /// Create a new, blank, framebuffer.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the rows of the last complete frame.
+    #[must_use]
+    pub fn rows(&self) -> &[u16; 16] {
+        &self.front
+    }
+
+    /// Check if the pixel at the given X and Y screen coordinates is enabled in the
+    /// last complete frame.
+    ///
+    /// See [`Api::enable_pixel`] for the screen mapping.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if either coordinate is outside the 16x16 screen.
+    #[must_use]
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        assert!(x < 16 && y < 16, "the pixel ({x}, {y}) is off screen");
+
+        self.front[y] >> x & 1 != 0
+    }
+
+    /// Return the rows which changed since this function was last called, where
+    /// bit N is set if row N changed, and clear them.
+    pub fn take_dirty(&mut self) -> u16 {
+        core::mem::take(&mut self.dirty)
+    }
+
+    /// Begin a new frame, discarding any pixels enabled since the last frame ended.
+    pub fn begin_frame(&mut self) {
+        self.back = [0; 16];
+    }
+}
+
+impl Api for Framebuffer {
+    fn enable_pixel(&mut self, x: usize, y: usize) {
+        self.back[y] |= 1 << x;
+    }
+
+    fn end_frame(&mut self) {
+        for (y, (front, back)) in self.front.iter_mut().zip(&self.back).enumerate() {
+            if front != back {
+                self.dirty |= 1 << y;
+            }
+            *front = *back;
+        }
+
+        self.begin_frame();
+    }
+}
+
 /// An abstract (frontend agnostic) 16x16 LCD display.
 pub trait Api {
     /// Enable the pixel at the given X and Y screen coordinates.
@@ -209,4 +456,59 @@ pub trait Api {
     /// corner of the LCD display, so X = 2, Y = 3 would be the pixel on
     /// the 4th row and 3rd column.
     fn enable_pixel(&mut self, x: usize, y: usize);
+
+    /// End the current frame, the next frame begins immediately.
+    ///
+    /// This is called by [`Console::sync`](crate::Console::sync) at the end of
+    /// every frame, and does nothing by default.
+    fn end_frame(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn double_buffers() {
+        let mut fb = Framebuffer::new();
+        fb.enable_pixel(0, 1);
+        fb.enable_pixel(15, 3);
+        assert!(!fb.get(0, 1));
+
+        fb.end_frame();
+        assert!(fb.get(0, 1) && fb.get(15, 3) && !fb.get(1, 1));
+        assert_eq!(fb.rows()[3], 0x8000);
+        assert_eq!(fb.take_dirty(), 0b1010);
+        assert_eq!(fb.take_dirty(), 0);
+
+        // Only rows which changed are dirty, and the previous frame is cleared.
+        fb.enable_pixel(0, 1);
+        fb.end_frame();
+        assert!(fb.get(0, 1) && !fb.get(15, 3));
+        assert_eq!(fb.take_dirty(), 0b1000);
+    }
+
+    #[test]
+    fn decays() {
+        let mut lcd = Lcd::new();
+        lcd.clock(Ms(10));
+        lcd.drive(3, 2);
+        assert_eq!(lcd.brightness(3, 2), 25);
+        assert_eq!(lcd.brightness(2, 3), 0);
+
+        // Driving a pixel for longer raises its contrast, up to fully on.
+        for time in 2..=20 {
+            lcd.clock(Ms(time * 10));
+            lcd.drive(3, 2);
+        }
+        assert_eq!(lcd.brightness(3, 2), 255);
+
+        // The contrast holds for a while, then fades out.
+        lcd.clock(Ms(200 + HOLD_TIME.0));
+        assert_eq!(lcd.brightness(3, 2), 255);
+        lcd.clock(Ms(200 + HOLD_TIME.0 + DECAY_TIME.0 / 2));
+        assert_eq!(lcd.brightness(3, 2), 127);
+        lcd.clock(Ms(200 + HOLD_TIME.0 + DECAY_TIME.0));
+        assert_eq!(lcd.brightness(3, 2), 0);
+    }
 }
@@ -0,0 +1,48 @@
+//! The error type of the fallible (`try_`) variants of the core's functions.
+//!
+//! These functions exist for values which originate outside of the emulator,
+//! e.g. a ROM file chosen by a user, so a frontend can report the problem instead
+//! of panicking.
+
+use core::fmt;
+
+/// An error caused by an invalid value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The ROM data is larger than the ROM of the micro-processor.
+    RomTooLarge {
+        /// The size of the ROM data, in bytes.
+        size: usize,
+        /// The size of the ROM of the micro-processor, in bytes.
+        max: usize,
+    },
+    /// The percentage is larger than `100`.
+    PercentageOutOfRange {
+        /// The given percentage.
+        value: usize,
+    },
+    /// The pin does not exist.
+    PinOutOfRange {
+        /// The given pin.
+        pin: u8,
+        /// The amount of pins which exist.
+        pins: u8,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RomTooLarge { size, max } => write!(
+                f,
+                "the rom is {size} bytes, larger than the {max} bytes of the micro-processor"
+            ),
+            Self::PercentageOutOfRange { value } => {
+                write!(f, "the percentage {value} is larger than 100")
+            }
+            Self::PinOutOfRange { pin, pins } => {
+                write!(f, "pin {pin} does not exist, there are only {pins} pins")
+            }
+        }
+    }
+}
@@ -0,0 +1,755 @@
+//! An implementation of the Intel 8021 micro-controller.
+//!
+//! The Intel 8021 is a low-cost member of the MCS-48 family, featuring a 1kb
+//! ROM, 64 bytes of RAM, three quasi-bidirectional ports and an 8-bit
+//! timer/counter, but no interrupts, register banks or external memory. It is
+//! used instead of the TMS1100 by several Microvision cartridges, e.g. Block
+//! Buster, Bowling, Connect Four and Vegas Slots.
+//!
+//! # Timing
+//!
+//! Unlike the TMS1100 emulation, this implementation is only accurate at the
+//! instruction level. An instruction is fully executed on its first machine
+//! cycle and the micro-processor simply idles for any remaining machine cycles.
+//!
+//! # Links
+//!
+//! - MAME: <https://github.com/mamedev/mame/blob/master/src/devices/cpu/mcs48/mcs48.cpp>
+//! - User's Manual: <http://www.bitsavers.org/components/intel/MCS48/9800270D_MCS-48_Users_Manual_Nov80.pdf>
+
+pub mod pinio;
+
+use crate::{
+    common::Line,
+    snapshot::{self, Reader, State, Writer},
+    tms1100::mem::Rom,
+};
+
+use arbitrary_int::{u10, u3, u5};
+
+/// The mode of the timer/counter of the Intel 8021.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerMode {
+    /// The timer/counter is stopped.
+    Stopped,
+    /// The timer/counter is incremented every 32 machine cycles.
+    Timer,
+    /// The timer/counter is incremented on every falling edge of the `T1` input.
+    Counter,
+}
+
+/// An operation on an Intel 8243 I/O expander, see [`I8021::expander`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expander {
+    /// Read a port of the expander.
+    Read = 0,
+    /// Write a port of the expander.
+    Write = 1,
+    /// Bitwise OR a port of the expander.
+    Or = 2,
+    /// Bitwise AND a port of the expander.
+    And = 3,
+}
+
+/// The 8-bit timer/counter of the Intel 8021.
+#[derive(Debug, Clone, Copy)]
+pub struct Timer {
+    /// The current value of the timer/counter.
+    pub value: u8,
+    /// The 5-bit prescaler, which divides the machine cycles in timer mode.
+    pub prescaler: u5,
+    /// The current mode of the timer/counter.
+    pub mode: TimerMode,
+    /// The timer flag.
+    ///
+    /// # Logic
+    ///
+    /// This is enabled when the timer/counter overflows and is disabled when
+    /// tested by the `JTF` instruction.
+    pub overflow: bool,
+}
+
+/// The status flags of the Intel 8021.
+#[derive(Debug, Clone, Copy)]
+pub struct Flags {
+    /// The `CY` carry flag.
+    pub carry: bool,
+    /// The `AC` auxiliary carry flag.
+    ///
+    /// # Logic
+    ///
+    /// This stores the carry out of the lower nibble of an addition and is
+    /// used by the `DA` instruction.
+    pub aux_carry: bool,
+}
+
+/// A collection of registers on the Intel 8021.
+#[derive(Debug, Clone, Copy)]
+pub struct Registers {
+    /// The 8-bit `A` accumulator.
+    pub a: u8,
+    /// The 10-bit `PC` program counter.
+    pub pc: u10,
+    /// The 3-bit `SP` stack pointer.
+    ///
+    /// The 8-level stack is stored within RAM, starting at address `8`.
+    pub sp: u3,
+}
+
+/// An emulated Intel 8021 micro-controller.
+#[derive(Debug, Clone)]
+pub struct I8021 {
+    /// The 8-bit port P0.
+    pub p0: pinio::Port,
+    /// The 8-bit port P1.
+    pub p1: pinio::Port,
+    /// The 4-bit port P2.
+    pub p2: pinio::Port,
+    /// The `T1` test input line.
+    pub t1: Line,
+    /// The status flags.
+    pub flags: Flags,
+    /// The registers.
+    pub regs: Registers,
+    /// The timer/counter.
+    pub timer: Timer,
+    /// The internal 64 byte RAM.
+    ///
+    /// The first 8 bytes of RAM are the working registers `R0-R7`, followed
+    /// by the 16 byte stack.
+    pub ram: [u8; 0x40],
+    /// The amount of machine cycles remaining in the current instruction.
+    pub stall: u8,
+    /// The value of the `T1` input during the previous machine cycle.
+    ///
+    /// This is used by the counter to detect falling edges.
+    t1_prev: bool,
+}
+
+impl I8021 {
+    /// Create a new Intel 8021 micro-controller.
+    #[must_use]
+    pub(crate) fn new() -> Self {
+        Self {
+            p0: pinio::Port::new(8),
+            p1: pinio::Port::new(8),
+            p2: pinio::Port::new(4),
+            t1: false.into(),
+            flags: Flags {
+                carry: false,
+                aux_carry: false,
+            },
+            regs: Registers {
+                a: 0,
+                pc: u10::new(0),
+                sp: u3::new(0),
+            },
+            timer: Timer {
+                value: 0,
+                prescaler: u5::new(0),
+                mode: TimerMode::Stopped,
+                overflow: false,
+            },
+            ram: [0; 0x40],
+            stall: 0,
+            t1_prev: false,
+        }
+    }
+
+    /// Reset this micro-controller.
+    pub(crate) fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Read the next byte of the current instruction from ROM.
+    fn fetch(&mut self, rom: &Rom) -> u8 {
+        let byte = rom.data[usize::from(self.regs.pc.value())];
+        self.regs.pc = self.regs.pc.wrapping_add(u10::new(1));
+        byte
+    }
+
+    /// Read a byte from ROM within the current page.
+    fn read_page(&self, rom: &Rom, addr: u8) -> u8 {
+        rom.data[usize::from(self.regs.pc.value() & 0x300) | usize::from(addr)]
+    }
+
+    /// Return the RAM address of the working register `Rr`.
+    fn reg(opcode: u8) -> usize {
+        usize::from(opcode & 7)
+    }
+
+    /// Return the RAM address pointed to by the working register `R0` or `R1`.
+    fn indirect(&self, opcode: u8) -> usize {
+        usize::from(self.ram[usize::from(opcode & 1)] & 0x3f)
+    }
+
+    /// Add a value, and optionally the carry flag, to the accumulator.
+    fn add(&mut self, val: u8, with_carry: bool) {
+        let carry = u8::from(with_carry && self.flags.carry);
+
+        let sum = u16::from(self.regs.a) + u16::from(val) + u16::from(carry);
+        self.flags.aux_carry = (self.regs.a & 0xf) + (val & 0xf) + carry > 0xf;
+        self.flags.carry = sum > 0xff;
+        self.regs.a = sum.to_le_bytes()[0];
+    }
+
+    /// Jump to an address within the current page.
+    fn jump_near(&mut self, addr: u8) {
+        self.regs.pc = u10::new(self.regs.pc.value() & 0x300 | u16::from(addr));
+    }
+
+    /// Read the address operand of a conditional jump, jumping to it if the
+    /// given condition is met.
+    fn jump_if(&mut self, rom: &Rom, condition: bool) -> u8 {
+        let addr = self.fetch(rom);
+        if condition {
+            self.jump_near(addr);
+        }
+        2
+    }
+
+    /// Return the full 10-bit address of a `JMP` or `CALL` instruction.
+    fn far_addr(opcode: u8, addr: u8) -> u10 {
+        u10::new(u16::from(opcode >> 5 & 3) << 8 | u16::from(addr))
+    }
+
+    /// Perform an operation on the given port (`4-7`) of an Intel 8243 I/O
+    /// expander.
+    ///
+    /// # Logic
+    ///
+    /// The operation and the port are output on `P20-P23` and latched by the
+    /// expander on the falling edge of `PROG`, afterwards the lower nibble of
+    /// the accumulator is output on (or, when reading, input from) `P20-P23`
+    /// and latched on the rising edge of `PROG`. As an instruction is executed
+    /// at once, and no Microvision cartridge connects an 8243, only the final
+    /// state of the P2 port is emulated.
+    fn expander(&mut self, op: Expander, opcode: u8) {
+        self.p2.write((op as u8) << 2 | opcode & 3);
+
+        if op == Expander::Read {
+            // The lines are placed in input mode, the upper nibble of the
+            // accumulator is cleared as P2 only has 4 lines.
+            self.p2.write(0xf);
+            self.regs.a = self.p2.read();
+        } else {
+            self.p2.write(self.regs.a & 0xf);
+        }
+    }
+
+    /// Push the program counter onto the stack.
+    fn push(&mut self) {
+        let [lo, hi] = self.regs.pc.value().to_le_bytes();
+        let idx = 8 + usize::from(self.regs.sp.value()) * 2;
+
+        self.ram[idx] = lo;
+        self.ram[idx + 1] =
+            u8::from(self.flags.carry) << 7 | u8::from(self.flags.aux_carry) << 6 | 1 << 3 | hi;
+        self.regs.sp = self.regs.sp.wrapping_add(u3::new(1));
+    }
+
+    /// Pop the program counter from the stack.
+    fn pop(&mut self) {
+        self.regs.sp = self.regs.sp.wrapping_sub(u3::new(1));
+        let idx = 8 + usize::from(self.regs.sp.value()) * 2;
+
+        self.regs.pc = u10::new(u16::from_le_bytes([self.ram[idx], self.ram[idx + 1] & 3]));
+    }
+
+    /// Execute a single instruction.
+    ///
+    /// This returns the amount of machine cycles the instruction takes.
+    #[allow(clippy::too_many_lines)]
+    fn step(&mut self, rom: &Rom) -> u8 {
+        let opcode = self.fetch(rom);
+
+        match opcode {
+            // ADD A, #data
+            0x03 => {
+                let val = self.fetch(rom);
+                self.add(val, false);
+                return 2;
+            }
+            // JMP addr
+            0x04 | 0x24 | 0x44 | 0x64 | 0x84 | 0xa4 | 0xc4 | 0xe4 => {
+                let addr = self.fetch(rom);
+                self.regs.pc = Self::far_addr(opcode, addr);
+                return 2;
+            }
+            // DEC A
+            0x07 => self.regs.a = self.regs.a.wrapping_sub(1),
+            // IN A, P0
+            0x08 => {
+                self.regs.a = self.p0.read();
+                return 2;
+            }
+            // IN A, P1
+            0x09 => {
+                self.regs.a = self.p1.read();
+                return 2;
+            }
+            // IN A, P2
+            0x0a => {
+                self.regs.a = self.p2.read();
+                return 2;
+            }
+            // MOVD A, Pp
+            0x0c..=0x0f => {
+                self.expander(Expander::Read, opcode);
+                return 2;
+            }
+            // INC @Rr
+            0x10 | 0x11 => {
+                let idx = self.indirect(opcode);
+                self.ram[idx] = self.ram[idx].wrapping_add(1);
+            }
+            // ADDC A, #data
+            0x13 => {
+                let val = self.fetch(rom);
+                self.add(val, true);
+                return 2;
+            }
+            // CALL addr
+            0x14 | 0x34 | 0x54 | 0x74 | 0x94 | 0xb4 | 0xd4 | 0xf4 => {
+                let addr = self.fetch(rom);
+                self.push();
+                self.regs.pc = Self::far_addr(opcode, addr);
+                return 2;
+            }
+            // JTF addr
+            0x16 => {
+                let overflow = self.timer.overflow;
+                self.timer.overflow = false;
+                return self.jump_if(rom, overflow);
+            }
+            // INC A
+            0x17 => self.regs.a = self.regs.a.wrapping_add(1),
+            // INC Rr
+            0x18..=0x1f => {
+                let idx = Self::reg(opcode);
+                self.ram[idx] = self.ram[idx].wrapping_add(1);
+            }
+            // XCH A, @Rr
+            0x20 | 0x21 => {
+                let idx = self.indirect(opcode);
+                core::mem::swap(&mut self.regs.a, &mut self.ram[idx]);
+            }
+            // MOV A, #data
+            0x23 => {
+                self.regs.a = self.fetch(rom);
+                return 2;
+            }
+            // CLR A
+            0x27 => self.regs.a = 0,
+            // XCH A, Rr
+            0x28..=0x2f => {
+                let idx = Self::reg(opcode);
+                core::mem::swap(&mut self.regs.a, &mut self.ram[idx]);
+            }
+            // XCHD A, @Rr
+            0x30 | 0x31 => {
+                let idx = self.indirect(opcode);
+                let (a, m) = (self.regs.a, self.ram[idx]);
+                self.regs.a = a & 0xf0 | m & 0xf;
+                self.ram[idx] = m & 0xf0 | a & 0xf;
+            }
+            // CPL A
+            0x37 => self.regs.a = !self.regs.a,
+            // OUTL P1, A
+            0x39 => {
+                self.p1.write(self.regs.a);
+                return 2;
+            }
+            // OUTL P2, A
+            0x3a => {
+                self.p2.write(self.regs.a);
+                return 2;
+            }
+            // MOVD Pp, A
+            0x3c..=0x3f => {
+                self.expander(Expander::Write, opcode);
+                return 2;
+            }
+            // ORL A, @Rr
+            0x40 | 0x41 => self.regs.a |= self.ram[self.indirect(opcode)],
+            // MOV A, T
+            0x42 => self.regs.a = self.timer.value,
+            // ORL A, #data
+            0x43 => {
+                self.regs.a |= self.fetch(rom);
+                return 2;
+            }
+            // STRT CNT
+            0x45 => self.timer.mode = TimerMode::Counter,
+            // JNT1 addr
+            0x46 => return self.jump_if(rom, !self.t1.value()),
+            // SWAP A
+            0x47 => self.regs.a = self.regs.a.rotate_left(4),
+            // ORL A, Rr
+            0x48..=0x4f => self.regs.a |= self.ram[Self::reg(opcode)],
+            // ANL A, @Rr
+            0x50 | 0x51 => self.regs.a &= self.ram[self.indirect(opcode)],
+            // ANL A, #data
+            0x53 => {
+                self.regs.a &= self.fetch(rom);
+                return 2;
+            }
+            // STRT T
+            0x55 => {
+                self.timer.mode = TimerMode::Timer;
+                self.timer.prescaler = u5::new(0);
+            }
+            // JT1 addr
+            0x56 => return self.jump_if(rom, self.t1.value()),
+            // DA A
+            0x57 => {
+                if self.regs.a & 0xf > 9 || self.flags.aux_carry {
+                    self.flags.carry |= self.regs.a > 0xf9;
+                    self.regs.a = self.regs.a.wrapping_add(0x06);
+                }
+                if self.regs.a & 0xf0 > 0x90 || self.flags.carry {
+                    self.regs.a = self.regs.a.wrapping_add(0x60);
+                    self.flags.carry = true;
+                }
+            }
+            // ANL A, Rr
+            0x58..=0x5f => self.regs.a &= self.ram[Self::reg(opcode)],
+            // ADD A, @Rr
+            0x60 | 0x61 => self.add(self.ram[self.indirect(opcode)], false),
+            // MOV T, A
+            0x62 => self.timer.value = self.regs.a,
+            // STOP TCNT
+            0x65 => self.timer.mode = TimerMode::Stopped,
+            // RRC A
+            0x67 => {
+                let carry = self.regs.a & 1 != 0;
+                self.regs.a = self.regs.a >> 1 | u8::from(self.flags.carry) << 7;
+                self.flags.carry = carry;
+            }
+            // ADD A, Rr
+            0x68..=0x6f => self.add(self.ram[Self::reg(opcode)], false),
+            // ADDC A, @Rr
+            0x70 | 0x71 => self.add(self.ram[self.indirect(opcode)], true),
+            // RR A
+            0x77 => self.regs.a = self.regs.a.rotate_right(1),
+            // ADDC A, Rr
+            0x78..=0x7f => self.add(self.ram[Self::reg(opcode)], true),
+            // RET
+            0x83 => {
+                self.pop();
+                return 2;
+            }
+            // ORL P1, #data
+            0x89 => {
+                let val = self.fetch(rom);
+                self.p1.write(self.p1.latch | val);
+                return 2;
+            }
+            // ORL P2, #data
+            0x8a => {
+                let val = self.fetch(rom);
+                self.p2.write(self.p2.latch | val);
+                return 2;
+            }
+            // ORLD Pp, A
+            0x8c..=0x8f => {
+                self.expander(Expander::Or, opcode);
+                return 2;
+            }
+            // OUTL P0, A
+            0x90 => {
+                self.p0.write(self.regs.a);
+                return 2;
+            }
+            // JNZ addr
+            0x96 => return self.jump_if(rom, self.regs.a != 0),
+            // CLR C
+            0x97 => self.flags.carry = false,
+            // ANL P1, #data
+            0x99 => {
+                let val = self.fetch(rom);
+                self.p1.write(self.p1.latch & val);
+                return 2;
+            }
+            // ANL P2, #data
+            0x9a => {
+                let val = self.fetch(rom);
+                self.p2.write(self.p2.latch & val);
+                return 2;
+            }
+            // ANLD Pp, A
+            0x9c..=0x9f => {
+                self.expander(Expander::And, opcode);
+                return 2;
+            }
+            // MOV @Rr, A
+            0xa0 | 0xa1 => {
+                let idx = self.indirect(opcode);
+                self.ram[idx] = self.regs.a;
+            }
+            // MOVP A, @A
+            0xa3 => {
+                self.regs.a = self.read_page(rom, self.regs.a);
+                return 2;
+            }
+            // CPL C
+            0xa7 => self.flags.carry = !self.flags.carry,
+            // MOV Rr, A
+            0xa8..=0xaf => self.ram[Self::reg(opcode)] = self.regs.a,
+            // MOV @Rr, #data
+            0xb0 | 0xb1 => {
+                let idx = self.indirect(opcode);
+                self.ram[idx] = self.fetch(rom);
+                return 2;
+            }
+            // JMPP @A
+            0xb3 => {
+                let addr = self.read_page(rom, self.regs.a);
+                self.jump_near(addr);
+                return 2;
+            }
+            // MOV Rr, #data
+            0xb8..=0xbf => {
+                self.ram[Self::reg(opcode)] = self.fetch(rom);
+                return 2;
+            }
+            // JZ addr
+            0xc6 => return self.jump_if(rom, self.regs.a == 0),
+            // XRL A, @Rr
+            0xd0 | 0xd1 => self.regs.a ^= self.ram[self.indirect(opcode)],
+            // XRL A, #data
+            0xd3 => {
+                self.regs.a ^= self.fetch(rom);
+                return 2;
+            }
+            // XRL A, Rr
+            0xd8..=0xdf => self.regs.a ^= self.ram[Self::reg(opcode)],
+            // JNC addr
+            0xe6 => return self.jump_if(rom, !self.flags.carry),
+            // RL A
+            0xe7 => self.regs.a = self.regs.a.rotate_left(1),
+            // DJNZ Rr, addr
+            0xe8..=0xef => {
+                let idx = Self::reg(opcode);
+                self.ram[idx] = self.ram[idx].wrapping_sub(1);
+                return self.jump_if(rom, self.ram[idx] != 0);
+            }
+            // MOV A, @Rr
+            0xf0 | 0xf1 => self.regs.a = self.ram[self.indirect(opcode)],
+            // JC addr
+            0xf6 => return self.jump_if(rom, self.flags.carry),
+            // RLC A
+            0xf7 => {
+                let carry = self.regs.a & 0x80 != 0;
+                self.regs.a = self.regs.a << 1 | u8::from(self.flags.carry);
+                self.flags.carry = carry;
+            }
+            // MOV A, Rr
+            0xf8..=0xff => self.regs.a = self.ram[Self::reg(opcode)],
+            _ => {
+                // NOP, the remaining opcodes of the MCS-48 family, i.e. those
+                // for interrupts, register banks, memory banks, the F0 and F1
+                // flags, the bus port and T0, are not part of the Intel 8021
+                // instruction set (see the 8021 instruction set summary of the
+                // user's manual) and are executed as a NOP, as done by MAME.
+            }
+        }
+
+        1
+    }
+
+    /// Update the timer/counter.
+    fn clock_timer(&mut self) {
+        let increment = match self.timer.mode {
+            TimerMode::Stopped => false,
+            TimerMode::Timer => {
+                self.timer.prescaler = self.timer.prescaler.wrapping_add(u5::new(1));
+                self.timer.prescaler == u5::new(0)
+            }
+            TimerMode::Counter => self.t1_prev && !self.t1.value(),
+        };
+
+        if increment {
+            self.timer.value = self.timer.value.wrapping_add(1);
+            if self.timer.value == 0 {
+                self.timer.overflow = true;
+            }
+        }
+
+        self.t1_prev = self.t1.value();
+    }
+
+    /// Clock (update) this micro-controller.
+    ///
+    /// # Logic
+    ///
+    /// This executes a single machine cycle, executing a whole instruction if
+    /// the previous instruction has finished.
+    pub(crate) fn clock(&mut self, rom: &Rom) {
+        self.clock_timer();
+
+        if self.stall > 0 {
+            self.stall -= 1;
+        } else {
+            self.stall = self.step(rom) - 1;
+        }
+    }
+}
+
+impl State for I8021 {
+    const SIZE: usize = 83;
+
+    fn save(&self, w: &mut Writer) {
+        for port in [&self.p0, &self.p1, &self.p2] {
+            w.u8(port.latch);
+            w.u8(port.pins);
+        }
+        w.bool(self.t1.value());
+
+        w.bool(self.flags.carry);
+        w.bool(self.flags.aux_carry);
+
+        w.u8(self.regs.a);
+        w.u16(self.regs.pc.value());
+        w.u8(self.regs.sp.value());
+
+        w.u8(self.timer.value);
+        w.u8(self.timer.prescaler.value());
+        w.u8(match self.timer.mode {
+            TimerMode::Stopped => 0,
+            TimerMode::Timer => 1,
+            TimerMode::Counter => 2,
+        });
+        w.bool(self.timer.overflow);
+
+        w.bytes(&self.ram);
+        w.u8(self.stall);
+        w.bool(self.t1_prev);
+    }
+
+    fn load(&mut self, r: &mut Reader) -> Result<(), snapshot::Error> {
+        for port in [&mut self.p0, &mut self.p1, &mut self.p2] {
+            port.write(r.u8()?);
+            port.drive(r.u8()?);
+        }
+        self.t1 = r.bool()?.into();
+
+        self.flags.carry = r.bool()?;
+        self.flags.aux_carry = r.bool()?;
+
+        self.regs.a = r.u8()?;
+        self.regs.pc = u10::try_new(r.u16()?).map_err(|_| snapshot::Error::Corrupt)?;
+        self.regs.sp = r.u3()?;
+
+        self.timer.value = r.u8()?;
+        self.timer.prescaler = r.u5()?;
+        self.timer.mode = match r.u8()? {
+            0 => TimerMode::Stopped,
+            1 => TimerMode::Timer,
+            2 => TimerMode::Counter,
+            _ => return Err(snapshot::Error::Corrupt),
+        };
+        self.timer.overflow = r.bool()?;
+
+        self.ram = r.array()?;
+        self.stall = r.u8()?;
+        if self.stall > 1 {
+            return Err(snapshot::Error::Corrupt);
+        }
+        self.t1_prev = r.bool()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the given program until the given amount of machine cycles pass.
+    fn run(program: &[u8], cycles: usize) -> I8021 {
+        let mut rom = Rom::new();
+        rom.copy(program);
+
+        let mut cpu = I8021::new();
+        for _ in 0..cycles {
+            cpu.clock(&rom);
+        }
+        cpu
+    }
+
+    #[test]
+    fn arithmetic() {
+        // MOV A, #0x38; ADD A, #0x29; DA A; MOV R2, A; CLR C; CPL C;
+        // MOV A, #0xff; ADDC A, #0x00; JMP $
+        let cpu = run(
+            &[
+                0x23, 0x38, 0x03, 0x29, 0x57, 0xaa, 0x97, 0xa7, 0x23, 0xff, 0x13, 0x00, 0x04, 0x0c,
+            ],
+            32,
+        );
+
+        assert_eq!(cpu.ram[2], 0x67);
+        assert_eq!(cpu.regs.a, 0x00);
+        assert!(cpu.flags.carry);
+        assert!(cpu.flags.aux_carry);
+        assert_eq!(cpu.regs.pc.value(), 0x0c);
+    }
+
+    #[test]
+    fn loops_and_calls() {
+        // MOV R3, #5; CLR A; CALL 0x10; DJNZ R3, 0x03; OUTL P1, A; JMP $
+        // 0x10: ADD A, #3; RET
+        let mut program = [0; 0x14];
+        program[..0x0c].copy_from_slice(&[
+            0xbb, 0x05, 0x27, 0x14, 0x10, 0xeb, 0x03, 0x39, 0x04, 0x08, 0x00, 0x00,
+        ]);
+        program[0x10..].copy_from_slice(&[0x03, 0x03, 0x83, 0x00]);
+
+        let cpu = run(&program, 200);
+
+        assert_eq!(cpu.p1.value(), 15);
+        assert_eq!(cpu.regs.sp.value(), 0);
+        assert_eq!(cpu.regs.pc.value(), 0x08);
+    }
+
+    #[test]
+    fn expander() {
+        // MOV A, #0x35; MOVD P5, A; ANLD P6, A; ORLD P7, A; MOVD A, P4; JMP $
+        let mut rom = Rom::new();
+        rom.copy(&[0x23, 0x35, 0x3d, 0x9e, 0x8f, 0x0c, 0x04, 0x06]);
+
+        // Every instruction takes two machine cycles.
+        let mut cpu = I8021::new();
+        let mut latches = [0; 4];
+        for latch in &mut latches {
+            cpu.clock(&rom);
+            cpu.clock(&rom);
+            *latch = cpu.p2.value();
+        }
+        // The data is output once the operation and port have been latched.
+        assert_eq!(latches, [0xf, 0x5, 0x5, 0x5]);
+        assert_eq!(cpu.regs.a, 0x35);
+
+        // Reading clears the upper nibble of the accumulator.
+        cpu.p2.drive(0x9);
+        cpu.clock(&rom);
+        assert_eq!(cpu.regs.a, 0x09);
+        assert_eq!(cpu.p2.value(), 0xf);
+    }
+
+    #[test]
+    fn timer_overflow() {
+        // MOV A, #0xfe; MOV T, A; STRT T; NOP; JTF 0x09; JMP 0x05; CLR A; JMP $
+        let cpu = run(
+            &[
+                0x23, 0xfe, 0x62, 0x55, 0x00, 0x16, 0x09, 0x04, 0x05, 0x27, 0x04, 0x0a,
+            ],
+            100,
+        );
+
+        assert_eq!(cpu.regs.a, 0);
+        assert!(!cpu.timer.overflow);
+        assert_eq!(cpu.regs.pc.value(), 0x0a);
+    }
+}
@@ -0,0 +1,85 @@
+//! Emulation of the Intel 8021's input and output ports.
+
+use crate::error::Error;
+
+/// A quasi-bidirectional 8-bit port.
+///
+/// # Logic
+///
+/// Every line of the port has an output latch, written by the micro-processor,
+/// and a pin, driven by the external hardware. When a port is read, a line will
+/// only read as high if both its latch and its pin are high, therefore a line
+/// has to be written high before it can be used as an input.
+#[derive(Debug, Clone, Copy)]
+pub struct Port {
+    /// The output latch of every line.
+    pub(crate) latch: u8,
+    /// The external pin input of every line.
+    pub(crate) pins: u8,
+    /// The mask of the lines which exist on this port.
+    mask: u8,
+}
+
+impl Port {
+    /// Create a new port with the given amount of lines.
+    #[must_use]
+    pub(crate) fn new(lines: u8) -> Self {
+        let mask = u8::MAX >> (8 - lines);
+
+        Self {
+            latch: mask,
+            pins: mask,
+            mask,
+        }
+    }
+
+    /// Return the value of the output latch of this port.
+    #[must_use]
+    pub fn value(&self) -> u8 {
+        self.latch
+    }
+
+    /// Check if the output latch of the nth-line of this port is enabled.
+    ///
+    /// # Panics
+    ///
+    /// If the given line does not exist on this port, this function will panic.
+    #[must_use]
+    pub fn get(&self, nth: u8) -> bool {
+        assert!(nth < 8 && self.mask >> nth & 1 != 0);
+
+        self.latch >> nth & 1 != 0
+    }
+
+    /// Check if the output latch of the nth-line of this port is enabled.
+    ///
+    /// # Errors
+    ///
+    /// If the given line does not exist on this port, an error is returned.
+    pub fn try_get(&self, nth: u8) -> Result<bool, Error> {
+        if nth < 8 && self.mask >> nth & 1 != 0 {
+            Ok(self.get(nth))
+        } else {
+            Err(Error::PinOutOfRange {
+                pin: nth,
+                pins: self.mask.count_ones().try_into().unwrap_or(8),
+            })
+        }
+    }
+
+    /// Write a value to the output latch of this port.
+    pub(crate) fn write(&mut self, val: u8) {
+        self.latch = val & self.mask;
+    }
+
+    /// Read the value of this port.
+    #[must_use]
+    pub(crate) fn read(self) -> u8 {
+        self.latch & self.pins
+    }
+
+    /// Drive the external pins of this port.
+    pub(crate) fn drive(&mut self, val: u8) {
+        self.pins = val & self.mask;
+    }
+}
@@ -30,6 +30,22 @@ pub enum Key {
 }
 
 impl Key {
+    /// Every key location on the keypad, ordered by column then row.
+    pub const ALL: [Self; 12] = [
+        Self::At0x0,
+        Self::At0x1,
+        Self::At0x2,
+        Self::At0x3,
+        Self::At1x0,
+        Self::At1x1,
+        Self::At1x2,
+        Self::At1x3,
+        Self::At2x0,
+        Self::At2x1,
+        Self::At2x2,
+        Self::At2x3,
+    ];
+
     /// Return the row/column offsets of this key location.
     ///
     /// The return value of this function is structured as `(row, col)`.
@@ -50,6 +66,19 @@ impl Key {
             Self::At2x3 => (3, 2),
         }
     }
+
+    /// Return the index of this key location within [`Key::ALL`].
+    #[must_use]
+    pub fn index(self) -> usize {
+        let (row, col) = self.pos();
+        col * 4 + row
+    }
+
+    /// Return the key location in the given column and row, if it exists.
+    #[must_use]
+    pub fn at(col: usize, row: usize) -> Option<Self> {
+        (col < 3 && row < 4).then(|| Self::ALL[col * 4 + row])
+    }
 }
 
 /// An abstract (frontend agnostic) 3x4 keypad.
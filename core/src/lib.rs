@@ -6,28 +6,94 @@
 pub mod buzzer;
 pub mod cartridge;
 pub mod common;
+pub mod debug;
 pub mod display;
+pub mod error;
+pub mod i8021;
 pub mod keypad;
+pub mod movie;
+pub mod overlay;
+pub mod rewind;
 pub mod rotary;
+pub mod snapshot;
 pub mod tms1100;
 
-use buzzer::Buzzer;
-use cartridge::Cartridge;
+#[cfg(test)]
+mod testing;
+
+use buzzer::{Buzzer, BuzzerPulse};
+use cartridge::{settings::CpuType, Cartridge};
 use common::{Interface, Ms};
-use display::Hughes0488;
+use display::{DataLine, Hughes0488, LatchPulse, Lcd, NotDataClock};
+use i8021::I8021;
 use keypad::Key;
-use rotary::Rotary;
-use tms1100::{pinio, Tms1100};
+use rotary::{ChargePulse, Rotary};
+use tms1100::{pinio, trace::Tracer, Tms1100};
 
 use arbitrary_int::u4;
 
+/// An on-cartridge micro-processor.
+#[derive(Debug, Clone)]
+pub enum Cpu {
+    /// A TMS1100 micro-processor, or another member of the TMS1000 family.
+    Tms1100(Tms1100),
+    /// An Intel 8021 micro-controller.
+    I8021(I8021),
+}
+
+impl Cpu {
+    /// Create a new micro-processor of the given type.
+    #[must_use]
+    fn new(kind: CpuType) -> Self {
+        kind.model().map_or_else(
+            || Self::I8021(I8021::new()),
+            |model| Self::Tms1100(Tms1100::new(model)),
+        )
+    }
+
+    /// Return the type of this micro-processor.
+    #[must_use]
+    pub fn kind(&self) -> CpuType {
+        match self {
+            Self::Tms1100(cpu) => cpu.model.into(),
+            Self::I8021(_) => CpuType::I8021,
+        }
+    }
+
+    /// Reset this micro-processor.
+    fn reset(&mut self) {
+        match self {
+            Self::Tms1100(cpu) => cpu.reset(),
+            Self::I8021(cpu) => cpu.reset(),
+        }
+    }
+}
+
+/// The outputs of the micro-processor, as wired by the cartridge.
+struct Outputs {
+    /// The data input lines of the LCD driver.
+    data: DataLine,
+    /// The latch pulse input line of the LCD driver.
+    pulse: LatchPulse,
+    /// The not data clock input line of the LCD driver.
+    not_clock: NotDataClock,
+    /// The buzzer pulse line.
+    buzzer: BuzzerPulse,
+    /// The rotary charge line.
+    charge: ChargePulse,
+    /// The selection lines of the left, middle and right keyboard columns.
+    columns: [bool; 3],
+}
+
 /// An emulated (Milton Bradley) Microvision handheld.
 #[derive(Debug, Clone)]
 pub struct Console {
-    /// The on-cartridge TMS1100 micro-processor.
-    pub cpu: Tms1100,
+    /// The on-cartridge micro-processor.
+    pub cpu: Cpu,
     /// The Hughes 0488 LCD driver.
     pub driver: Hughes0488,
+    /// The LCD panel.
+    pub lcd: Lcd,
     /// The Piezo buzzer.
     pub buzzer: Buzzer,
     /// The rotary controller.
@@ -41,8 +107,9 @@ impl Console {
     #[must_use]
     pub fn new() -> Self {
         Self {
-            cpu: Tms1100::new(),
+            cpu: Cpu::new(CpuType::default()),
             driver: Hughes0488::new(),
+            lcd: Lcd::new(),
             buzzer: Buzzer::new(),
             rotary: Rotary::new(),
             elapsed: Ms(0),
@@ -57,6 +124,7 @@ impl Console {
     pub fn reset(&mut self) {
         self.cpu.reset();
         self.driver.reset();
+        self.lcd.reset();
         self.buzzer.reset();
         self.rotary.reset();
         self.elapsed = Ms(0);
@@ -68,96 +136,197 @@ impl Console {
     /// LCD display, etc. etc. To synchronize certain outputs like sound,
     /// use [sync](Self::sync).
     ///
+    /// If the type of micro-processor on the given cartridge differs from
+    /// the current micro-processor, e.g. a different cartridge has been
+    /// inserted, the micro-processor is replaced with a new one.
+    ///
     /// ## Timing
     ///
     /// This function should be called at a rate of 100khz, effectively every
     /// 10 **micro**-seconds.
-    #[allow(clippy::needless_pass_by_value)]
+    ///
+    /// ## Accuracy
+    ///
+    /// Unlike the TMS1100 wiring, the wiring of an Intel 8021's ports to the
+    /// console is unverified: it has neither been checked against a reference
+    /// such as MAME's `microvision.cpp`, nor been run with a dump of an Intel
+    /// 8021 cartridge. It simply connects the ports to the same console signals
+    /// the TMS1100 drives, therefore Intel 8021 cartridges may not be playable.
     pub fn clock<L, B, K, R>(&mut self, cart: &mut Cartridge, hardware: Interface<L, B, K, R>)
     where
         L: display::Api,
         B: buzzer::Api,
         K: keypad::Api,
         R: rotary::Api,
+    {
+        self.clock_traced(cart, hardware, &mut ());
+    }
+
+    /// Update this console, passing every instruction executed by a TMS1100 to the
+    /// given tracer.
+    ///
+    /// See [clock](Self::clock) for more details.
+    #[allow(clippy::needless_pass_by_value)]
+    pub fn clock_traced<L, B, K, R, T>(
+        &mut self,
+        cart: &mut Cartridge,
+        hardware: Interface<L, B, K, R>,
+        tracer: &mut T,
+    ) where
+        L: display::Api,
+        B: buzzer::Api,
+        K: keypad::Api,
+        R: rotary::Api,
+        T: Tracer,
     {
         /// Read a column of keys from the given keyboard.
         #[rustfmt::skip]
-        fn read_column<K>(kb: &K, k: &mut pinio::K, keys: [Key; 4])
+        fn read_column<K>(kb: &K, rows: &mut u4, keys: [Key; 4])
         where
             K: keypad::Api,
         {
-            if kb.get(keys[0]) { k.set(3, true) };
-            if kb.get(keys[1]) { k.set(2, true) };
-            if kb.get(keys[2]) { k.set(1, true) };
-            if kb.get(keys[3]) { k.set(0, true) };
+            if kb.get(keys[0]) { *rows |= u4::new(1 << 3); }
+            if kb.get(keys[1]) { *rows |= u4::new(1 << 2); }
+            if kb.get(keys[2]) { *rows |= u4::new(1 << 1); }
+            if kb.get(keys[3]) { *rows |= u4::new(1 << 0); }
         }
 
         // The amount of microseconds every hz (clock) at 100khz takes.
         self.elapsed.offset(Ms(10));
 
-        // Update the TMS1100 micro-processor.
-        self.cpu.clock(&cart.rom, &mut cart.ram);
+        if self.cpu.kind() != cart.settings.cpu {
+            self.cpu = Cpu::new(cart.settings.cpu);
+        }
 
-        // The R output of the TMS1100.
-        let control = self.cpu.r;
+        // Update the on-cartridge micro-processor.
+        let outputs = match &mut self.cpu {
+            Cpu::Tms1100(cpu) => {
+                cpu.clock(
+                    &cart.rom,
+                    &mut cart.ram,
+                    cart.settings.micro_pla.as_ref(),
+                    tracer,
+                );
+                Self::tms1100_outputs(cpu, cart)
+            }
+            Cpu::I8021(cpu) => {
+                cpu.clock(&cart.rom);
+                Self::i8021_outputs(cpu)
+            }
+        };
 
-        // Update the K input of the TMS1100.
-        let mut k = pinio::K::new();
+        // The rows of the currently selected keyboard columns.
+        let mut rows = u4::new(0);
 
-        // Pin 10 of the R output connects to the left column of the keyboard.
-        if control.get(10) {
+        if outputs.columns[0] {
             read_column(
                 hardware.keypad,
-                &mut k,
+                &mut rows,
                 [Key::At0x0, Key::At0x1, Key::At0x2, Key::At0x3],
             );
         }
-        // Pin 9 of the R output connects to the middle column of the keyboard.
-        if control.get(9) {
+        if outputs.columns[1] {
             read_column(
                 hardware.keypad,
-                &mut k,
+                &mut rows,
                 [Key::At1x0, Key::At1x1, Key::At1x2, Key::At1x3],
             );
         }
-        // Pin 8 of the R output connects to the right column of the keyboard.
-        if control.get(8) {
+        if outputs.columns[2] {
             read_column(
                 hardware.keypad,
-                &mut k,
+                &mut rows,
                 [Key::At2x0, Key::At2x1, Key::At2x2, Key::At2x3],
             );
         }
-        if cart.settings.rotary_enabled {
-            k.0 &= u4::new(7);
-            // If the charging circuit of the rotary controller has ended (timed out)
-            // set the K8 line.
-            if self.rotary.charge.value() && self.rotary.charge_end.is_before(self.elapsed) {
-                k.set(3, true);
+
+        // If the charging circuit of the rotary controller has ended (timed out).
+        let charge_ended = cart.settings.rotary_enabled
+            && self.rotary.charge.value()
+            && self.rotary.charge_end.is_before(self.elapsed);
+
+        // Update the inputs of the on-cartridge micro-processor.
+        match &mut self.cpu {
+            Cpu::Tms1100(cpu) => {
+                let mut k = pinio::K(rows);
+                if cart.settings.rotary_enabled {
+                    // The K8 line is connected to the rotary controller instead
+                    // of the keyboard.
+                    k.set(3, charge_ended);
+                }
+                cpu.k = k;
+            }
+            Cpu::I8021(cpu) => {
+                // This wiring is unverified, see `clock`.
+                cpu.p2.drive(rows.value());
+                cpu.t1 = charge_ended.into();
             }
         }
 
-        // Update the Hughes 0488 LCD driver.
+        // Update the Hughes 0488 LCD driver, and the LCD panel it drives.
+        self.lcd.clock(self.elapsed);
         self.driver.clock(
-            cart.settings.output_pla.modify(self.cpu.o),
-            control.get(6).into(),
-            control.get(7).into(),
+            outputs.data,
+            outputs.pulse,
+            outputs.not_clock,
+            &mut self.lcd,
             hardware.display,
         );
 
         // Update the Piezo buzzer.
-        self.buzzer.clock(control.get(0).into(), self.elapsed);
+        self.buzzer.clock(outputs.buzzer, self.elapsed);
 
         // Update the rotary controller.
         self.rotary
-            .clock(control.get(2).into(), self.elapsed, cart, hardware.rotary);
+            .clock(outputs.charge, self.elapsed, cart, hardware.rotary);
+    }
+
+    /// Return the outputs of a TMS1100, as wired by the cartridge.
+    fn tms1100_outputs(cpu: &Tms1100, cart: &Cartridge) -> Outputs {
+        // The R output of the TMS1100.
+        let control = cpu.r;
+
+        Outputs {
+            // The O output is decoded by the output PLA of the cartridge.
+            data: cart.settings.output_pla.modify(cpu.o),
+            // Pins 6 and 7 of the R output connect to the LCD driver.
+            pulse: control.get(6).into(),
+            not_clock: control.get(7).into(),
+            // Pin 0 of the R output connects to the Piezo buzzer.
+            buzzer: control.get(0).into(),
+            // Pin 2 of the R output connects to the rotary controller.
+            charge: control.get(2).into(),
+            // Pins 10, 9 and 8 of the R output connect to the left, middle
+            // and right columns of the keyboard.
+            columns: [control.get(10), control.get(9), control.get(8)],
+        }
+    }
+
+    /// Return the outputs of an Intel 8021, as wired by the cartridge.
+    ///
+    /// This wiring is unverified, see [clock](Self::clock).
+    fn i8021_outputs(cpu: &I8021) -> Outputs {
+        Outputs {
+            // Pins 0-3 of the P0 port connect to the data lines of the LCD driver.
+            data: DataLine(u4::new(cpu.p0.value() & 0xf)),
+            // Pins 5 and 4 of the P0 port connect to the LCD driver.
+            pulse: cpu.p0.get(5).into(),
+            not_clock: cpu.p0.get(4).into(),
+            // Pin 0 of the P1 port connects to the Piezo buzzer.
+            buzzer: cpu.p1.get(0).into(),
+            // Pin 1 of the P1 port connects to the rotary controller.
+            charge: cpu.p1.get(1).into(),
+            // Pins 4, 5 and 6 of the P1 port connect to the left, middle and
+            // right columns of the keyboard.
+            columns: [cpu.p1.get(4), cpu.p1.get(5), cpu.p1.get(6)],
+        }
     }
 
     /// Synchronize this console.
     ///
     /// This does not "run" anything in the console, it simply synchronizes
-    /// the values output to certain hardware. To actually "run" the console
-    /// use [clock](Self::clock).
+    /// the values output to certain hardware, and ends the frame of the display.
+    /// To actually "run" the console use [clock](Self::clock).
     ///
     /// # Timing
     ///
@@ -171,5 +340,88 @@ impl Console {
         R: rotary::Api,
     {
         self.buzzer.sync(hardware.buzzer);
+        hardware.display.end_frame();
+    }
+
+    /// Save a snapshot of this console, and the RAM of the given cartridge, into
+    /// a buffer.
+    ///
+    /// This returns the amount of bytes written, which is always
+    /// [`snapshot::SIZE`].
+    ///
+    /// # Errors
+    ///
+    /// If the given buffer is smaller than [`snapshot::SIZE`] bytes, an error
+    /// is returned.
+    pub fn save_state(&self, cart: &Cartridge, buf: &mut [u8]) -> Result<usize, snapshot::Error> {
+        snapshot::save(self, cart, buf)
+    }
+
+    /// Load a snapshot of this console, and the RAM of the given cartridge, from
+    /// a buffer.
+    ///
+    /// Neither this console nor the cartridge are modified if an error occurs.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the given buffer does not contain a valid snapshot,
+    /// or the snapshot was taken with a different format version or cartridge ROM.
+    pub fn load_state(&mut self, cart: &mut Cartridge, buf: &[u8]) -> Result<(), snapshot::Error> {
+        snapshot::load(self, cart, buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{cartridge, run_with, Pressed},
+        tms1100::mem::Rom,
+    };
+
+    /// Return the pixels lit by an Intel 8021 cartridge while the given key is
+    /// pressed, as a bitmask of the lit columns of every row.
+    fn i8021_pixels(key: Option<Key>) -> [u16; 16] {
+        let mut cart = cartridge();
+        cart.settings.cpu = CpuType::I8021;
+        // Select the left column of the keypad, and shift its rows into every
+        // latch of the LCD driver, which are then output as both the row and
+        // column data:
+        //
+        //     00: MOV A, #0x10; OUTL P1, A
+        //     03: IN A, P2; ANL A, #0x0f; MOV R0, A; MOV R2, #8
+        //     09: MOV A, R0; OUTL P0, A; ORL A, #0x10; OUTL P0, A; DJNZ R2, 0x09
+        //     10: ORL A, #0x30; OUTL P0, A; JMP 0x03
+        cart.rom = Rom::new();
+        cart.rom.copy(&[
+            0x23, 0x10, 0x39, 0x0a, 0x53, 0x0f, 0xa8, 0xba, 0x08, 0xf8, 0x90, 0x43, 0x10, 0x90,
+            0xea, 0x09, 0x43, 0x30, 0x90, 0x04, 0x03,
+        ]);
+
+        let mut console = Console::new();
+        run_with(&mut console, &mut cart, &Pressed(key), 1000);
+
+        core::array::from_fn(|y| {
+            (0..16)
+                .filter(|&x| console.lcd.brightness(x, y) != 0)
+                .fold(0, |acc, x| acc | 1 << x)
+        })
+    }
+
+    #[test]
+    fn i8021_drives_lcd_from_keypad() {
+        assert_eq!(i8021_pixels(None), [0; 16]);
+
+        // The rows of the left column are read from pins 3 to 0 of P2, and the
+        // data lines of the LCD driver are written from pins 0 to 3 of P0.
+        let lit = |bit: usize| -> [u16; 16] {
+            let mask = 0x1111 << bit;
+            core::array::from_fn(|y| if mask >> y & 1 != 0 { mask } else { 0 })
+        };
+        assert_eq!(i8021_pixels(Some(Key::At0x0)), lit(3));
+        assert_eq!(i8021_pixels(Some(Key::At0x3)), lit(0));
+
+        // Keys of the other columns are not selected.
+        assert_eq!(i8021_pixels(Some(Key::At1x0)), [0; 16]);
     }
 }
@@ -0,0 +1,518 @@
+//! Deterministic input movie recording and playback.
+//!
+//! A movie is a compact log of the input supplied to a console, frame by frame,
+//! which can be replayed to reproduce a play session with bit-identical output.
+//!
+//! # Format
+//!
+//! Movies use a little-endian binary format, which is written into (and read
+//! from) caller-provided buffers. Every movie begins with a header:
+//!
+//! | Size   | Contents                                                 |
+//! |--------|----------------------------------------------------------|
+//! | `4`    | The [`MAGIC`] bytes.                                     |
+//! | `1`    | The format [`VERSION`].                                  |
+//! | `2`    | The checksum of the cartridge's ROM data.                |
+//! | `307`  | The settings of the cartridge.                           |
+//! | `64`   | The (packed) initial contents of the cartridge's RAM.    |
+//! | `2`    | The amount of frames between each state hash.            |
+//!
+//! The header is followed by a record for every frame, consisting of the state
+//! of the keypad (`2` bytes) and the turn percentage of the rotary controller
+//! (`1` byte). Every `N` frames, the record is preceded by a hash of the state
+//! of the console (`4` bytes), taken at the beginning of the frame.
+
+use crate::{
+    cartridge::{
+        settings::{ChargeInfo, CpuType, OutputPla, Settings},
+        Cartridge,
+    },
+    keypad::{self, Key},
+    rotary::{self, Percentage},
+    snapshot::{self, Reader, State, Writer},
+    tms1100::mem::Ram,
+    Console,
+};
+
+use core::fmt;
+
+/// The magic bytes at the beginning of every movie.
+pub const MAGIC: [u8; 4] = *b"MLTM";
+
+/// The current version of the movie format.
+pub const VERSION: u8 = 5;
+
+/// The size of the movie header, in bytes.
+pub const HEADER_SIZE: usize = 9 + <Settings as State>::SIZE + <Ram as State>::SIZE;
+
+/// The size of a single frame record, in bytes.
+const FRAME_SIZE: usize = 3;
+
+/// The size of a single state hash, in bytes.
+const HASH_SIZE: usize = 4;
+
+/// An error encountered while recording or playing a movie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The given buffer is too small to hold the movie.
+    BufferTooSmall,
+    /// The movie does not begin with the expected [`MAGIC`] bytes.
+    BadMagic,
+    /// The movie was recorded with a different version of the format.
+    VersionMismatch {
+        /// The version stored in the movie.
+        found: u8,
+    },
+    /// The movie was recorded with a different cartridge ROM.
+    ChecksumMismatch {
+        /// The checksum of the currently inserted cartridge ROM.
+        expected: u16,
+        /// The checksum stored in the movie.
+        found: u16,
+    },
+    /// The movie contains a value which is out of range.
+    Corrupt,
+    /// The state of the console differs from the state during recording.
+    ///
+    /// States are only compared on frames with a state hash, therefore the
+    /// states may have begun to differ up to `N - 1` frames before the frame
+    /// at which this is detected.
+    Desync {
+        /// The frame at which the differing states were detected.
+        frame: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BufferTooSmall => write!(f, "the buffer is too small to hold the movie"),
+            Self::BadMagic => write!(f, "the data is not a movie"),
+            Self::VersionMismatch { found } => write!(
+                f,
+                "the movie has version {found}, expected version {VERSION}"
+            ),
+            Self::ChecksumMismatch { expected, found } => write!(
+                f,
+                "the movie was recorded with a different ROM ({found:#06x}, expected {expected:#06x})"
+            ),
+            Self::Corrupt => write!(f, "the movie contains corrupt data"),
+            Self::Desync { frame } => write!(f, "a replay desync was detected at frame {frame}"),
+        }
+    }
+}
+
+impl From<snapshot::Error> for Error {
+    fn from(err: snapshot::Error) -> Self {
+        match err {
+            snapshot::Error::BufferTooSmall => Self::BufferTooSmall,
+            snapshot::Error::BadMagic => Self::BadMagic,
+            snapshot::Error::VersionMismatch { found } => Self::VersionMismatch { found },
+            snapshot::Error::ChecksumMismatch { expected, found } => {
+                Self::ChecksumMismatch { expected, found }
+            }
+            snapshot::Error::Corrupt => Self::Corrupt,
+        }
+    }
+}
+
+/// The input supplied to a console during a single frame.
+///
+/// This implements both the [`keypad::Api`] and [`rotary::Api`], so it can be
+/// used directly as the input of an [`Interface`](crate::Interface).
+#[derive(Debug, Clone, Copy)]
+pub struct Input {
+    /// The state of every key, indexed by [`Key::index`].
+    keys: u16,
+    /// The turn percentage of the rotary controller.
+    turn: Percentage,
+}
+
+impl Input {
+    /// Sample the current input of a keypad and rotary controller.
+    #[must_use]
+    pub fn sample<K, R>(keypad: &K, rotary: &R) -> Self
+    where
+        K: keypad::Api,
+        R: rotary::Api,
+    {
+        let keys = Key::ALL
+            .iter()
+            .filter(|key| keypad.get(**key))
+            .fold(0, |acc, key| acc | 1 << key.index());
+
+        Self {
+            keys,
+            turn: rotary.turn(),
+        }
+    }
+}
+
+impl keypad::Api for Input {
+    fn get(&self, key: Key) -> bool {
+        self.keys >> key.index() & 1 != 0
+    }
+}
+
+impl rotary::Api for Input {
+    fn turn(&self) -> Percentage {
+        self.turn
+    }
+}
+
+/// A recorder of input movies.
+#[derive(Debug)]
+pub struct Recorder<'a> {
+    /// The buffer the movie is written into.
+    buf: &'a mut [u8],
+    /// The amount of bytes written.
+    len: usize,
+    /// The current frame.
+    frame: usize,
+    /// The amount of frames between each state hash.
+    hash_interval: u16,
+}
+
+impl<'a> Recorder<'a> {
+    /// Begin recording a movie of the given cartridge.
+    ///
+    /// This must be used right after the cartridge is inserted (and the console
+    /// reset), since the current contents of the cartridge's RAM are stored as
+    /// the initial contents. A hash of the console state is stored every
+    /// `hash_interval` frames, or never if it is zero.
+    ///
+    /// # Errors
+    ///
+    /// If the given buffer is too small to hold the movie header, an error is
+    /// returned.
+    pub fn new(buf: &'a mut [u8], cart: &Cartridge, hash_interval: u16) -> Result<Self, Error> {
+        if buf.len() < HEADER_SIZE {
+            return Err(Error::BufferTooSmall);
+        }
+
+        let mut w = Writer::new(buf);
+        w.bytes(&MAGIC);
+        w.u8(VERSION);
+        w.u16(cart.rom.checksum());
+        cart.settings.save(&mut w);
+        cart.ram.save(&mut w);
+        w.u16(hash_interval);
+
+        Ok(Self {
+            buf,
+            len: HEADER_SIZE,
+            frame: 0,
+            hash_interval,
+        })
+    }
+
+    /// Return the amount of bytes recorded so far.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if no frames have been recorded so far.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.frame == 0
+    }
+
+    /// Return the amount of frames recorded so far.
+    #[must_use]
+    pub fn frames(&self) -> usize {
+        self.frame
+    }
+
+    /// Record the input of a single frame.
+    ///
+    /// The input of the given keypad and rotary controller is sampled once,
+    /// the returned [`Input`] should then be used as the input of the console
+    /// for the entire frame.
+    ///
+    /// # Timing
+    ///
+    /// This function should be called at the beginning of every frame, before
+    /// the console is clocked.
+    ///
+    /// # Errors
+    ///
+    /// If the buffer is too small to hold the frame, an error is returned and
+    /// nothing is recorded.
+    pub fn record<K, R>(
+        &mut self,
+        console: &Console,
+        cart: &Cartridge,
+        keypad: &K,
+        rotary: &R,
+    ) -> Result<Input, Error>
+    where
+        K: keypad::Api,
+        R: rotary::Api,
+    {
+        let hashed = is_hash_frame(self.frame, self.hash_interval);
+        let size = FRAME_SIZE + if hashed { HASH_SIZE } else { 0 };
+        if self.buf.len() - self.len < size {
+            return Err(Error::BufferTooSmall);
+        }
+
+        let input = Input::sample(keypad, rotary);
+
+        let mut w = Writer::new(&mut self.buf[self.len..]);
+        if hashed {
+            w.u32(snapshot::hash(console, cart));
+        }
+        w.u16(input.keys);
+        #[allow(clippy::cast_possible_truncation)]
+        w.u8(input.turn.value() as u8);
+
+        self.len += w.pos();
+        self.frame += 1;
+
+        Ok(input)
+    }
+}
+
+/// A player of input movies.
+#[derive(Debug)]
+pub struct Player<'a> {
+    /// The buffer the movie is read from.
+    buf: &'a [u8],
+    /// The amount of bytes read.
+    pos: usize,
+    /// The current frame.
+    frame: usize,
+    /// The checksum of the cartridge's ROM data.
+    checksum: u16,
+    /// The settings of the cartridge.
+    settings: Settings,
+    /// The initial contents of the cartridge's RAM.
+    ram: Ram,
+    /// The amount of frames between each state hash.
+    hash_interval: u16,
+}
+
+impl<'a> Player<'a> {
+    /// Begin playing the given movie.
+    ///
+    /// # Errors
+    ///
+    /// If the given buffer does not contain a valid movie header, an error is
+    /// returned.
+    pub fn new(buf: &'a [u8]) -> Result<Self, Error> {
+        let mut r = Reader::new(buf);
+
+        if r.array()? != MAGIC {
+            return Err(Error::BadMagic);
+        }
+
+        let version = r.u8()?;
+        if version != VERSION {
+            return Err(Error::VersionMismatch { found: version });
+        }
+
+        let checksum = r.u16()?;
+
+        let mut settings = Settings {
+            cpu: CpuType::default(),
+            charge_info: ChargeInfo::default(),
+            output_pla: OutputPla::default(),
+            micro_pla: None,
+            rotary_enabled: false,
+        };
+        settings.load(&mut r)?;
+
+        let mut ram = Ram::new();
+        ram.load(&mut r)?;
+
+        let hash_interval = r.u16()?;
+
+        Ok(Self {
+            buf,
+            pos: r.pos(),
+            frame: 0,
+            checksum,
+            settings,
+            ram,
+            hash_interval,
+        })
+    }
+
+    /// Return the checksum of the ROM the movie was recorded with.
+    #[must_use]
+    pub fn checksum(&self) -> u16 {
+        self.checksum
+    }
+
+    /// Return the settings of the cartridge the movie was recorded with.
+    #[must_use]
+    pub fn settings(&self) -> Settings {
+        self.settings
+    }
+
+    /// Return the current frame.
+    #[must_use]
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Prepare a cartridge for playing this movie.
+    ///
+    /// This copies the recorded settings and initial RAM contents into the
+    /// given cartridge, the console should be reset afterwards.
+    ///
+    /// # Errors
+    ///
+    /// If the cartridge's ROM differs from the one the movie was recorded with,
+    /// an error is returned and nothing is modified.
+    pub fn prepare(&self, cart: &mut Cartridge) -> Result<(), Error> {
+        let expected = cart.rom.checksum();
+        if self.checksum != expected {
+            return Err(Error::ChecksumMismatch {
+                expected,
+                found: self.checksum,
+            });
+        }
+
+        cart.settings = self.settings;
+        cart.ram = self.ram.clone();
+
+        Ok(())
+    }
+
+    /// Replay the input of a single frame.
+    ///
+    /// This returns [None] once every frame of the movie has been replayed.
+    ///
+    /// # Timing
+    ///
+    /// This function should be called at the beginning of every frame, before
+    /// the console is clocked.
+    ///
+    /// # Errors
+    ///
+    /// If the state of the given console differs from the state during
+    /// recording, [`Error::Desync`] is returned with the frame at which this was
+    /// detected. As only the states of hashed frames are compared, this is the
+    /// first hashed frame after the states began to differ.
+    pub fn next(&mut self, console: &Console, cart: &Cartridge) -> Result<Option<Input>, Error> {
+        if self.pos == self.buf.len() {
+            return Ok(None);
+        }
+
+        let mut r = Reader::new(&self.buf[self.pos..]);
+
+        if is_hash_frame(self.frame, self.hash_interval)
+            && r.u32().map_err(|_| Error::Corrupt)? != snapshot::hash(console, cart)
+        {
+            return Err(Error::Desync { frame: self.frame });
+        }
+
+        let keys = r.u16().map_err(|_| Error::Corrupt)?;
+        let turn = r.u8().map_err(|_| Error::Corrupt)?;
+        if keys >> Key::ALL.len() != 0 || turn > 100 {
+            return Err(Error::Corrupt);
+        }
+
+        self.pos += r.pos();
+        self.frame += 1;
+
+        Ok(Some(Input {
+            keys,
+            turn: Percentage(usize::from(turn)),
+        }))
+    }
+}
+
+/// Check if a state hash is stored for the given frame.
+fn is_hash_frame(frame: usize, hash_interval: u16) -> bool {
+    hash_interval != 0 && frame.is_multiple_of(usize::from(hash_interval))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{cartridge, run_with, Pressed},
+        tms1100::mem::Rom,
+    };
+
+    /// Create a cartridge summing the left column of the keypad into RAM.
+    fn reader() -> Cartridge {
+        let mut cart = cartridge();
+        cart.rom = Rom::new();
+        // TCY 10, SETR, TKA, AMAAC, TAM, BR 3, placed in the execution order of
+        // the program counter.
+        for (addr, opcode) in [0x00, 0x01, 0x03, 0x07, 0x0f, 0x1f]
+            .into_iter()
+            .zip([0x45, 0x0d, 0x08, 0x06, 0x27, 0x83])
+        {
+            cart.rom.data[addr] = opcode;
+        }
+        cart
+    }
+
+    /// Play a movie to completion, returning the final state hash.
+    fn play(movie: &[u8], rom: &Rom) -> Result<u32, Error> {
+        let mut player = Player::new(movie)?;
+
+        let mut cart = reader();
+        cart.rom = rom.clone();
+        player.prepare(&mut cart)?;
+
+        let mut console = Console::new();
+        while let Some(input) = player.next(&console, &cart)? {
+            run_with(&mut console, &mut cart, &input, 1667);
+        }
+
+        Ok(snapshot::hash(&console, &cart))
+    }
+
+    #[test]
+    fn replays_and_detects_desync() {
+        let mut cart = reader();
+        cart.ram.fill_random();
+        let mut console = Console::new();
+
+        // The key is held down for frames 10 to 19.
+        let mut buf = [0; 512];
+        let mut recorder = Recorder::new(&mut buf, &cart, 4).unwrap();
+        for frame in 0..30 {
+            let pressed = Pressed((10..20).contains(&frame).then_some(Key::At0x1));
+            let input = recorder
+                .record(&console, &cart, &pressed, &pressed)
+                .unwrap();
+            run_with(&mut console, &mut cart, &input, 1667);
+        }
+        let len = recorder.len();
+
+        assert_eq!(
+            play(&buf[..len], &cart.rom),
+            Ok(snapshot::hash(&console, &cart))
+        );
+
+        // Modifying the initial RAM contents causes the replay to desync.
+        let mut bad = buf;
+        bad[HEADER_SIZE - 3] ^= 0x10;
+        assert!(matches!(
+            play(&bad[..len], &cart.rom),
+            Err(Error::Desync { frame }) if frame % 4 == 0
+        ));
+
+        // Releasing the key in frame 13, which follows the hash of frame 12, causes
+        // the replay to desync at the next hash.
+        let mut bad = buf;
+        bad[HEADER_SIZE + 13 * FRAME_SIZE + 4 * HASH_SIZE] ^= 1 << Key::At0x1.index();
+        assert_eq!(
+            play(&bad[..len], &cart.rom),
+            Err(Error::Desync { frame: 16 })
+        );
+
+        let mut rom = cart.rom.clone();
+        rom.data[0x7ff] = 1;
+        assert!(matches!(
+            play(&buf[..len], &rom),
+            Err(Error::ChecksumMismatch { .. })
+        ));
+    }
+}
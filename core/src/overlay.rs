@@ -0,0 +1,370 @@
+//! Cartridge overlays and a software compositor for the LCD display.
+//!
+//! Every Microvision cartridge came with a printed plastic overlay, which tinted
+//! regions of the LCD display and relabelled the keys of the keypad.
+//!
+//! # Format
+//!
+//! Overlays are loaded from a line-based text format, where blank lines and lines
+//! beginning with `#` are ignored:
+//!
+//! ```text
+//! title Block Buster
+//! # A region: the X and Y position, the width and height, and the colour as
+//! # RRGGBB or RRGGBBAA, where the alpha is the strength of the tint.
+//! region 0 0 16 4 ff4040
+//! region 0 12 16 4 4040ff80
+//! # A key: its column and row on the keypad, and its label.
+//! key 1 3 Serve
+//! ```
+//!
+//! Later regions are drawn over earlier regions.
+
+use crate::keypad::Key;
+
+use core::fmt;
+
+/// The maximum amount of colour regions of an overlay.
+pub const MAX_REGIONS: usize = 16;
+
+/// The colour of an LCD pixel which is off, without any overlay.
+const BACKGROUND: [u8; 3] = [0xc8, 0xcc, 0xb4];
+
+/// The colour of an LCD pixel which is fully on, without any overlay.
+const FOREGROUND: [u8; 3] = [0x20, 0x22, 0x1c];
+
+/// A rectangular, coloured region of an overlay, in LCD pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    /// The X position of the upper left corner.
+    pub x: u8,
+    /// The Y position of the upper left corner.
+    pub y: u8,
+    /// The width of the region.
+    pub width: u8,
+    /// The height of the region.
+    pub height: u8,
+    /// The colour of the region, where the alpha is the strength of the tint.
+    pub color: [u8; 4],
+}
+
+impl Region {
+    /// Check if this region is not empty, and lies within the screen.
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.width != 0
+            && self.height != 0
+            && usize::from(self.x) + usize::from(self.width) <= 16
+            && usize::from(self.y) + usize::from(self.height) <= 16
+    }
+
+    /// Check if this region contains the pixel at the given X and Y coordinates.
+    #[must_use]
+    pub fn contains(&self, x: usize, y: usize) -> bool {
+        let (left, top) = (usize::from(self.x), usize::from(self.y));
+        (left..left + usize::from(self.width)).contains(&x)
+            && (top..top + usize::from(self.height)).contains(&y)
+    }
+}
+
+/// An error encountered while parsing an overlay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// A line is malformed.
+    Syntax {
+        /// The (1-based) line the error is on.
+        line: usize,
+    },
+    /// A region or key lies outside of the screen or keypad.
+    OutOfBounds {
+        /// The (1-based) line the error is on.
+        line: usize,
+    },
+    /// The overlay has more than [`MAX_REGIONS`] regions.
+    TooManyRegions {
+        /// The (1-based) line the error is on.
+        line: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax { line } => write!(f, "line {line}: malformed overlay line"),
+            Self::OutOfBounds { line } => {
+                write!(f, "line {line}: outside of the screen or keypad")
+            }
+            Self::TooManyRegions { line } => {
+                write!(f, "line {line}: more than {MAX_REGIONS} regions")
+            }
+        }
+    }
+}
+
+/// The overlay of a cartridge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overlay<'a> {
+    /// The title of the game, if known.
+    pub title: Option<&'a str>,
+    /// The colour regions, where later regions are drawn over earlier regions and
+    /// empty slots are skipped.
+    pub regions: [Option<Region>; MAX_REGIONS],
+    /// The labels of every key, in the order of [`Key::ALL`], an empty label means
+    /// the key is unused.
+    pub keys: [&'a str; 12],
+}
+
+impl Default for Overlay<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Overlay<'a> {
+    /// Create a new, empty, overlay.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            title: None,
+            regions: [None; MAX_REGIONS],
+            keys: [""; 12],
+        }
+    }
+
+    /// Parse an overlay.
+    ///
+    /// The title and key labels of the returned overlay borrow from the given text.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if a line is malformed, a region or key lies outside
+    /// of the screen or keypad, or there are too many regions.
+    pub fn parse(text: &'a str) -> Result<Self, Error> {
+        let mut overlay = Self::new();
+
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (line, (cmd, args)) = (idx + 1, line.split_once(' ').unwrap_or((line, "")));
+            let syntax = Error::Syntax { line };
+            match cmd {
+                "title" if !args.is_empty() => overlay.title = Some(args.trim()),
+                "region" => {
+                    let mut fields = args.split_whitespace();
+                    let mut next = || fields.next().and_then(|field| field.parse::<u8>().ok());
+                    let (x, y, width, height) = (next(), next(), next(), next());
+                    let (Some(x), Some(y), Some(width), Some(height)) = (x, y, width, height)
+                    else {
+                        return Err(syntax);
+                    };
+                    let color = fields.next().and_then(parse_color).ok_or(syntax)?;
+                    if fields.next().is_some() {
+                        return Err(syntax);
+                    }
+
+                    let region = Region {
+                        x,
+                        y,
+                        width,
+                        height,
+                        color,
+                    };
+                    if !region.is_valid() {
+                        return Err(Error::OutOfBounds { line });
+                    }
+                    if !overlay.push(region) {
+                        return Err(Error::TooManyRegions { line });
+                    }
+                }
+                "key" => {
+                    let mut fields = args.splitn(3, ' ');
+                    let mut next = || fields.next().and_then(|field| field.parse::<usize>().ok());
+                    let (Some(col), Some(row)) = (next(), next()) else {
+                        return Err(syntax);
+                    };
+                    let label = fields.next().map_or("", str::trim);
+                    if label.is_empty() {
+                        return Err(syntax);
+                    }
+                    let key = Key::at(col, row).ok_or(Error::OutOfBounds { line })?;
+
+                    overlay.keys[key.index()] = label;
+                }
+                _ => return Err(syntax),
+            }
+        }
+
+        Ok(overlay)
+    }
+
+    /// Add a colour region to this overlay, drawn over the existing regions.
+    ///
+    /// This returns `false`, without adding the region, if this overlay already
+    /// has [`MAX_REGIONS`] regions.
+    #[must_use]
+    pub fn push(&mut self, region: Region) -> bool {
+        let Some(slot) = self.regions.iter_mut().find(|slot| slot.is_none()) else {
+            return false;
+        };
+
+        *slot = Some(region);
+        true
+    }
+
+    /// Return an iterator over the colour regions of this overlay.
+    pub fn regions(&self) -> impl Iterator<Item = &Region> {
+        self.regions.iter().flatten()
+    }
+
+    /// Return the label of the given key, if the key is used.
+    #[must_use]
+    pub fn label(&self, key: Key) -> Option<&'a str> {
+        let label = self.keys[key.index()];
+        (!label.is_empty()).then_some(label)
+    }
+
+    /// Return the tint of the pixel at the given X and Y coordinates.
+    fn tint(&self, x: usize, y: usize) -> [u8; 3] {
+        let region = self.regions().filter(|region| region.contains(x, y)).last();
+        let Some(&Region { color, .. }) = region else {
+            return [0xff; 3];
+        };
+
+        // Blend the colour with white (no tint), based upon its alpha.
+        let alpha = u16::from(color[3]);
+        let mut tint = [0; 3];
+        for (tint, channel) in tint.iter_mut().zip(color) {
+            *tint = mix(0xff, channel, alpha);
+        }
+        tint
+    }
+
+    /// Composite this overlay with the LCD display into an RGBA buffer.
+    ///
+    /// The brightness of every pixel, from `0` (off) to `255` (fully on), is given
+    /// by a function of its X and Y screen coordinates, e.g. using
+    /// [`Lcd::brightness`](crate::display::Lcd::brightness). Every pixel is drawn
+    /// as a square of `scale` by `scale` RGBA values, row by row.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the buffer is not exactly `16 * scale` squared
+    /// RGBA values in size.
+    pub fn composite<F>(&self, brightness: F, scale: usize, out: &mut [u8])
+    where
+        F: Fn(usize, usize) -> u8,
+    {
+        let width = 16 * scale;
+        assert_eq!(
+            out.len(),
+            width * width * 4,
+            "the buffer has the wrong size"
+        );
+
+        for (idx, rgba) in out.chunks_exact_mut(4).enumerate() {
+            let (x, y) = (idx % width / scale, idx / width / scale);
+            let base = shade(brightness(x, y));
+            let tint = self.tint(x, y);
+
+            for ((out, tint), base) in rgba.iter_mut().zip(tint).zip(base) {
+                // The overlay tints the light reflected by the LCD display.
+                *out = u8::try_from(u16::from(base) * u16::from(tint) / 255).unwrap_or(u8::MAX);
+            }
+            rgba[3] = 0xff;
+        }
+    }
+}
+
+/// Return the colour of an LCD pixel without any overlay, by its brightness from `0`
+/// (off) to `255` (fully on).
+#[must_use]
+pub fn shade(brightness: u8) -> [u8; 3] {
+    let mut color = [0; 3];
+    for (out, (off, on)) in color.iter_mut().zip(BACKGROUND.iter().zip(FOREGROUND)) {
+        *out = mix(*off, on, u16::from(brightness));
+    }
+    color
+}
+
+/// Mix two colour channels, by an amount from `0` (only `a`) to `255` (only `b`).
+fn mix(a: u8, b: u8, amount: u16) -> u8 {
+    let mixed = (u16::from(a) * (255 - amount) + u16::from(b) * amount) / 255;
+    u8::try_from(mixed).unwrap_or(u8::MAX)
+}
+
+/// Parse a colour, as `RRGGBB` or `RRGGBBAA`.
+fn parse_color(text: &str) -> Option<[u8; 4]> {
+    if !(text.len() == 6 || text.len() == 8) || !text.is_ascii() {
+        return None;
+    }
+
+    let mut color = [0xff; 4];
+    for (channel, idx) in color.iter_mut().zip((0..text.len()).step_by(2)) {
+        *channel = u8::from_str_radix(&text[idx..idx + 2], 16).ok()?;
+    }
+    Some(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses() {
+        let text = "\
+            title Block Buster
+            # The bricks.
+            region 0 0 16 4 ff0000
+            region 0 2 8 2 00ff0080
+
+            key 1 3 Serve
+        ";
+        let overlay = Overlay::parse(text).unwrap();
+        assert_eq!(overlay.title, Some("Block Buster"));
+        assert_eq!(overlay.regions().count(), 2);
+        assert_eq!(overlay.label(Key::At1x3), Some("Serve"));
+        assert_eq!(overlay.label(Key::At0x0), None);
+
+        assert!(overlay.regions().next().unwrap().contains(15, 3));
+        assert!(!overlay.regions().next().unwrap().contains(15, 4));
+
+        assert_eq!(
+            Overlay::parse("region 0 0 16").unwrap_err(),
+            Error::Syntax { line: 1 }
+        );
+        assert_eq!(
+            Overlay::parse("\nregion 8 0 9 1 ffffff").unwrap_err(),
+            Error::OutOfBounds { line: 2 }
+        );
+        assert_eq!(
+            Overlay::parse("key 3 0 Fire").unwrap_err(),
+            Error::OutOfBounds { line: 1 }
+        );
+        assert_eq!(
+            Overlay::parse("key 0 0").unwrap_err(),
+            Error::Syntax { line: 1 }
+        );
+        assert_eq!(
+            Overlay::parse("region 0 0 1 1 ff00zz").unwrap_err(),
+            Error::Syntax { line: 1 }
+        );
+    }
+
+    #[test]
+    fn composites() {
+        let overlay = Overlay::parse("region 0 0 16 1 ff0000\nregion 0 0 1 1 00ff0000").unwrap();
+        let mut out = [0; 32 * 32 * 4];
+        overlay.composite(|x, _| if x == 1 { 255 } else { 0 }, 2, &mut out);
+
+        // The top-left pixel has a fully transparent region on top.
+        assert_eq!(out[..4], [0xc8, 0xcc, 0xb4, 0xff]);
+        // The pixel to its right is on, and tinted red.
+        assert_eq!(out[8..12], [0x20, 0, 0, 0xff]);
+        // Pixels are scaled, and the second row is not tinted.
+        assert_eq!(out[12..16], out[8..12]);
+        assert_eq!(out[32 * 4 * 2..32 * 4 * 2 + 4], [0xc8, 0xcc, 0xb4, 0xff]);
+    }
+}
@@ -0,0 +1,330 @@
+//! A rewind buffer built on delta-compressed [snapshots](crate::snapshot).
+//!
+//! # Logic
+//!
+//! Every `N` frames, a snapshot of the console is recorded. Instead of storing
+//! every snapshot in full, only the most recent snapshot is kept as-is, each
+//! older snapshot is stored as the difference (`XOR`) between itself and its
+//! successor, which is then run-length encoded. Because most of the console
+//! state, e.g. the RAM, registers and latches, barely changes between frames,
+//! these deltas are usually only a handful of bytes.
+//!
+//! The snapshots and their deltas are stored within a caller-provided buffer,
+//! no snapshot is ever placed on the stack. When the buffer is full the oldest
+//! deltas are discarded, therefore the memory usage of the rewind buffer is
+//! bounded by the size of the given buffer.
+
+use crate::{
+    cartridge::Cartridge,
+    snapshot::{self, SIZE},
+    Console,
+};
+
+/// The amount of bytes of the buffer given to [`Rewind::new`] that are used to
+/// record snapshots, the remaining bytes store the deltas.
+///
+/// This holds the most recently recorded snapshot, and the scratch space a new
+/// snapshot is recorded into before it is compared to the most recent one.
+pub const OVERHEAD: usize = SIZE * 2;
+
+/// The size of the length header and trailer surrounding every delta.
+const LEN_SIZE: usize = 2;
+
+/// A fixed-capacity rewind buffer.
+#[derive(Debug)]
+pub struct Rewind<'a> {
+    /// The most recently recorded snapshot, if any has been recorded.
+    latest: &'a mut [u8],
+    /// The scratch space a new snapshot is recorded into.
+    scratch: &'a mut [u8],
+    /// The ring buffer storing the encoded deltas.
+    ///
+    /// Every delta is surrounded by its encoded length, stored as a 16-bit
+    /// value, so that the ring can be traversed from both ends.
+    ring: &'a mut [u8],
+    /// The offset of the oldest delta in the ring buffer.
+    head: usize,
+    /// The amount of bytes used within the ring buffer.
+    used: usize,
+    /// The amount of deltas stored within the ring buffer.
+    count: usize,
+    /// Whether a snapshot has been recorded.
+    recorded: bool,
+    /// The amount of frames between each recorded snapshot.
+    interval: usize,
+    /// The amount of frames since the last recorded snapshot.
+    frames: usize,
+}
+
+impl<'a> Rewind<'a> {
+    /// Create a new rewind buffer.
+    ///
+    /// A snapshot will be recorded every `interval` frames. The first
+    /// [`OVERHEAD`] bytes of the given buffer are used to record snapshots, the
+    /// deltas between them are stored within the remaining bytes.
+    ///
+    /// # Panics
+    ///
+    /// If the given interval is zero, or the given buffer is smaller than
+    /// [`OVERHEAD`] bytes, this function will panic.
+    #[must_use]
+    pub fn new(buf: &'a mut [u8], interval: usize) -> Self {
+        assert!(interval > 0, "The rewind interval must be non-zero");
+        assert!(
+            buf.len() >= OVERHEAD,
+            "The rewind buffer must be at least {OVERHEAD} bytes"
+        );
+
+        let (latest, rest) = buf.split_at_mut(SIZE);
+        let (scratch, ring) = rest.split_at_mut(SIZE);
+
+        Self {
+            latest,
+            scratch,
+            ring,
+            head: 0,
+            used: 0,
+            count: 0,
+            recorded: false,
+            interval,
+            frames: 0,
+        }
+    }
+
+    /// Return the amount of snapshots that can currently be rewound to.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.count + usize::from(self.recorded)
+    }
+
+    /// Check if there are no snapshots to rewind to.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        !self.recorded
+    }
+
+    /// Discard all recorded snapshots.
+    ///
+    /// This should be used whenever the console is reset, or a snapshot is
+    /// loaded from elsewhere.
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.used = 0;
+        self.count = 0;
+        self.recorded = false;
+        self.frames = 0;
+    }
+
+    /// Notify this rewind buffer that a frame has ended.
+    ///
+    /// If `interval` frames have passed since the last recorded snapshot, a new
+    /// snapshot of the given console is recorded.
+    ///
+    /// # Timing
+    ///
+    /// This function should be called at the end of every frame, after
+    /// [`Console::sync`].
+    pub fn frame(&mut self, console: &Console, cart: &Cartridge) {
+        self.frames += 1;
+        if self.frames >= self.interval {
+            self.frames = 0;
+            self.record(console, cart);
+        }
+    }
+
+    /// Record a snapshot of the given console.
+    pub fn record(&mut self, console: &Console, cart: &Cartridge) {
+        // The scratch space is always large enough to hold a snapshot.
+        let _ = snapshot::save(console, cart, self.scratch);
+
+        if self.recorded {
+            let mut len = 0;
+            encode(self.latest, self.scratch, |_| len += 1);
+
+            // Make space for the new delta by discarding the oldest deltas.
+            while self.count > 0 && self.ring.len() - self.used < len + LEN_SIZE * 2 {
+                self.pop_oldest();
+            }
+
+            if self.ring.len() - self.used >= len + LEN_SIZE * 2 {
+                self.push_newest(len);
+            }
+        }
+
+        self.latest.copy_from_slice(self.scratch);
+        self.recorded = true;
+    }
+
+    /// Step backwards by a single recorded snapshot.
+    ///
+    /// The first step restores the most recently recorded snapshot, every
+    /// following step restores the snapshot recorded before it. When recording
+    /// a snapshot every frame, this rewinds the console one frame at a time.
+    ///
+    /// This returns `false` if there were no snapshots left to restore.
+    ///
+    /// # Errors
+    ///
+    /// If the recorded snapshots were taken with a different cartridge, an
+    /// error is returned and nothing is modified.
+    pub fn step_back(
+        &mut self,
+        console: &mut Console,
+        cart: &mut Cartridge,
+    ) -> Result<bool, snapshot::Error> {
+        if !self.recorded {
+            return Ok(false);
+        }
+
+        snapshot::load(console, cart, self.latest)?;
+
+        if self.count > 0 {
+            self.pop_newest();
+        } else {
+            self.recorded = false;
+        }
+        self.frames = 0;
+
+        Ok(true)
+    }
+
+    /// Read a byte from the ring buffer, relative to the head.
+    fn get(&self, offset: usize) -> u8 {
+        self.ring[(self.head + offset) % self.ring.len()]
+    }
+
+    /// Read an encoded length from the ring buffer, relative to the head.
+    fn get_len(&self, offset: usize) -> usize {
+        usize::from(u16::from_le_bytes([self.get(offset), self.get(offset + 1)]))
+    }
+
+    /// Encode the delta between the most recent snapshot and the scratch space,
+    /// which is `len` bytes in size, straight into the end of the ring buffer.
+    fn push_newest(&mut self, len: usize) {
+        #[allow(clippy::cast_possible_truncation)]
+        let [lo, hi] = (len as u16).to_le_bytes();
+
+        let (ring, head) = (&mut *self.ring, self.head);
+        let mut offset = self.used;
+        let mut put = |byte| {
+            let size = ring.len();
+            ring[(head + offset) % size] = byte;
+            offset += 1;
+        };
+
+        put(lo);
+        put(hi);
+        encode(self.latest, self.scratch, &mut put);
+        put(lo);
+        put(hi);
+
+        self.used += len + LEN_SIZE * 2;
+        self.count += 1;
+    }
+
+    /// Discard the delta at the beginning of the ring buffer.
+    fn pop_oldest(&mut self) {
+        let size = self.get_len(0) + LEN_SIZE * 2;
+
+        self.head = (self.head + size) % self.ring.len();
+        self.used -= size;
+        self.count -= 1;
+    }
+
+    /// Remove the delta at the end of the ring buffer and apply it to the
+    /// most recent snapshot.
+    fn pop_newest(&mut self) {
+        let len = self.get_len(self.used - LEN_SIZE);
+        let start = self.used - LEN_SIZE - len;
+
+        let mut idx = 0;
+        let mut offset = start;
+        while offset < start + len {
+            match self.get(offset) {
+                0 => {
+                    idx += usize::from(self.get(offset + 1));
+                    offset += 2;
+                }
+                byte => {
+                    self.latest[idx] ^= byte;
+                    idx += 1;
+                    offset += 1;
+                }
+            }
+        }
+
+        self.used = start - LEN_SIZE;
+        self.count -= 1;
+    }
+}
+
+/// Encode the difference between two snapshots, passing every encoded byte to
+/// the given function.
+///
+/// The difference is run-length encoded, a zero byte followed by a count
+/// represents a run of unchanged bytes, every other byte is the `XOR` of the
+/// two snapshots. In the worst case every zero byte is encoded as a zero-run of
+/// length one, therefore a delta is at most twice the size of a snapshot.
+fn encode(old: &[u8], new: &[u8], mut out: impl FnMut(u8)) {
+    let mut run = 0u8;
+
+    for (a, b) in old.iter().zip(new) {
+        let byte = a ^ b;
+        if byte == 0 && run < u8::MAX {
+            run += 1;
+            continue;
+        }
+
+        if run > 0 {
+            out(0);
+            out(run);
+            run = 0;
+        }
+
+        if byte == 0 {
+            run = 1;
+        } else {
+            out(byte);
+        }
+    }
+
+    if run > 0 {
+        out(0);
+        out(run);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{cartridge, run};
+
+    #[test]
+    fn steps_back_in_order() {
+        let mut cart = cartridge();
+        let mut console = Console::new();
+
+        let mut buf = [0; OVERHEAD + 256];
+        let mut rewind = Rewind::new(&mut buf, 2);
+
+        let mut states = [0; 40];
+        for state in &mut states {
+            run(&mut console, &mut cart, 97);
+            rewind.frame(&console, &cart);
+            rewind.frame(&console, &cart);
+            *state = snapshot::hash(&console, &cart);
+        }
+
+        // Only a bounded amount of history fits within the ring buffer.
+        let len = rewind.len();
+        assert!(len > 1 && len < states.len());
+
+        for state in states.iter().rev().take(len) {
+            assert_eq!(rewind.step_back(&mut console, &mut cart), Ok(true));
+            assert_eq!(snapshot::hash(&console, &cart), *state);
+        }
+
+        assert!(rewind.is_empty());
+        assert_eq!(rewind.step_back(&mut console, &mut cart), Ok(false));
+    }
+}
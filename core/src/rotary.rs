@@ -3,6 +3,8 @@
 use crate::{
     cartridge::{settings::ChargeInfo, Cartridge},
     common::{line_type, Ms},
+    error::Error,
+    snapshot::{self, Reader, State, Writer},
 };
 
 line_type! {
@@ -62,6 +64,21 @@ impl Rotary {
     }
 }
 
+impl State for Rotary {
+    const SIZE: usize = 9;
+
+    fn save(&self, w: &mut Writer) {
+        w.usize(self.charge_end.0);
+        w.bool(self.charge.0);
+    }
+
+    fn load(&mut self, r: &mut Reader) -> Result<(), snapshot::Error> {
+        self.charge_end.0 = r.usize()?;
+        self.charge.0 = r.bool()?;
+        Ok(())
+    }
+}
+
 /// The turn percentage (`0-100`) of a rotary controller.
 #[derive(Debug, Clone, Copy)]
 pub struct Percentage(pub(crate) usize);
@@ -80,6 +97,20 @@ impl Percentage {
         Self(amount)
     }
 
+    /// Create a new percentage value.
+    ///
+    /// # Errors
+    ///
+    /// If the given value does not fall within the range of `0..=100`, an error
+    /// is returned.
+    pub fn try_new(amount: usize) -> Result<Self, Error> {
+        if amount <= 100 {
+            Ok(Self(amount))
+        } else {
+            Err(Error::PercentageOutOfRange { value: amount })
+        }
+    }
+
     /// Return the inner value of this percentage.
     #[must_use]
     pub fn value(&self) -> usize {
@@ -0,0 +1,489 @@
+//! Versioned save-state snapshots of an emulated Microvision.
+//!
+//! A snapshot captures every piece of emulated state, e.g. the micro-processor,
+//! LCD driver and panel, buzzer, rotary controller and the RAM of the inserted
+//! cartridge, down to the current sub-instruction cycle.
+//!
+//! # Format
+//!
+//! Snapshots use a stable, fixed-size, little-endian binary format, which is
+//! written into (and read from) caller-provided buffers of at least [`SIZE`]
+//! bytes. Every snapshot begins with a short header:
+//!
+//! | Offset | Size | Contents                                   |
+//! |--------|------|--------------------------------------------|
+//! | `0`    | `4`  | The [`MAGIC`] bytes.                       |
+//! | `4`    | `1`  | The format [`VERSION`].                    |
+//! | `5`    | `2`  | The checksum of the cartridge's ROM data.  |
+//!
+//! The header is then followed by the state of each component of the console
+//! and finally the (packed) nibbles of the cartridge's RAM.
+
+use crate::{
+    cartridge::{settings::CpuType, Cartridge},
+    i8021::I8021,
+    tms1100::{mem::Ram, Tms1100},
+    Console, Cpu,
+};
+
+use core::fmt;
+
+use arbitrary_int::{u1, u3, u4, u5, u6};
+
+/// The magic bytes at the beginning of every snapshot.
+pub const MAGIC: [u8; 4] = *b"MLTN";
+
+/// The current version of the snapshot format.
+///
+/// This is incremented every time the layout of the snapshot format changes,
+/// snapshots taken with a different version are rejected.
+pub const VERSION: u8 = 5;
+
+/// The size of the snapshot header, in bytes.
+const HEADER_SIZE: usize = 7;
+
+/// The size of every snapshot, in bytes.
+pub const SIZE: usize = HEADER_SIZE + <Console as State>::SIZE + <Ram as State>::SIZE;
+
+/// An error encountered while saving or loading a snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The given buffer is too small to hold a snapshot.
+    BufferTooSmall,
+    /// The snapshot does not begin with the expected [`MAGIC`] bytes.
+    BadMagic,
+    /// The snapshot was taken with a different version of the format.
+    VersionMismatch {
+        /// The version stored in the snapshot.
+        found: u8,
+    },
+    /// The snapshot was taken with a different cartridge ROM.
+    ChecksumMismatch {
+        /// The checksum of the currently inserted cartridge ROM.
+        expected: u16,
+        /// The checksum stored in the snapshot.
+        found: u16,
+    },
+    /// The snapshot contains a value which is out of range.
+    Corrupt,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BufferTooSmall => write!(f, "the buffer is too small to hold a snapshot"),
+            Self::BadMagic => write!(f, "the data is not a snapshot"),
+            Self::VersionMismatch { found } => write!(
+                f,
+                "the snapshot has version {found}, expected version {VERSION}"
+            ),
+            Self::ChecksumMismatch { expected, found } => write!(
+                f,
+                "the snapshot was taken with a different ROM ({found:#06x}, expected {expected:#06x})"
+            ),
+            Self::Corrupt => write!(f, "the snapshot contains corrupt data"),
+        }
+    }
+}
+
+/// A cursor writing snapshot data into a buffer.
+///
+/// The size of the buffer is verified before any writing begins, therefore
+/// none of the write operations are fallible.
+pub(crate) struct Writer<'a> {
+    /// The buffer being written to.
+    buf: &'a mut [u8],
+    /// The current offset into the buffer.
+    pos: usize,
+}
+
+impl<'a> Writer<'a> {
+    /// Create a new snapshot writer.
+    pub(crate) fn new(buf: &'a mut [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    /// Return the amount of bytes written.
+    pub(crate) fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Write a single byte.
+    pub(crate) fn u8(&mut self, val: u8) {
+        self.buf[self.pos] = val;
+        self.pos += 1;
+    }
+
+    /// Write a boolean, stored as a single byte.
+    pub(crate) fn bool(&mut self, val: bool) {
+        self.u8(val.into());
+    }
+
+    /// Write a 16-bit value.
+    pub(crate) fn u16(&mut self, val: u16) {
+        self.bytes(&val.to_le_bytes());
+    }
+
+    /// Write a 32-bit value.
+    pub(crate) fn u32(&mut self, val: u32) {
+        self.bytes(&val.to_le_bytes());
+    }
+
+    /// Write a pointer-sized value, stored as a 64-bit value.
+    pub(crate) fn usize(&mut self, val: usize) {
+        self.bytes(&(val as u64).to_le_bytes());
+    }
+
+    /// Write an amount of zero bytes.
+    pub(crate) fn zeros(&mut self, amount: usize) {
+        self.buf[self.pos..self.pos + amount].fill(0);
+        self.pos += amount;
+    }
+
+    /// Write a slice of bytes.
+    pub(crate) fn bytes(&mut self, val: &[u8]) {
+        self.buf[self.pos..self.pos + val.len()].copy_from_slice(val);
+        self.pos += val.len();
+    }
+}
+
+/// A cursor reading snapshot data from a buffer.
+pub(crate) struct Reader<'a> {
+    /// The buffer being read from.
+    buf: &'a [u8],
+    /// The current offset into the buffer.
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    /// Create a new snapshot reader.
+    pub(crate) fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    /// Return the amount of bytes read.
+    pub(crate) fn pos(&self) -> usize {
+        self.pos
+    }
+
+    /// Read a single byte.
+    pub(crate) fn u8(&mut self) -> Result<u8, Error> {
+        let val = *self.buf.get(self.pos).ok_or(Error::BufferTooSmall)?;
+        self.pos += 1;
+        Ok(val)
+    }
+
+    /// Read a boolean, stored as a single byte.
+    pub(crate) fn bool(&mut self) -> Result<bool, Error> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::Corrupt),
+        }
+    }
+
+    /// Read a 16-bit value.
+    pub(crate) fn u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    /// Read a 32-bit value.
+    pub(crate) fn u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    /// Read a pointer-sized value, stored as a 64-bit value.
+    pub(crate) fn usize(&mut self) -> Result<usize, Error> {
+        usize::try_from(u64::from_le_bytes(self.array()?)).map_err(|_| Error::Corrupt)
+    }
+
+    /// Read a fixed amount of bytes.
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos + N)
+            .ok_or(Error::BufferTooSmall)?;
+        self.pos += N;
+
+        let mut array = [0; N];
+        array.copy_from_slice(bytes);
+        Ok(array)
+    }
+
+    /// Read a slice of bytes.
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let bytes = self
+            .buf
+            .get(self.pos..self.pos + len)
+            .ok_or(Error::BufferTooSmall)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// Skip an amount of bytes.
+    pub(crate) fn skip(&mut self, amount: usize) -> Result<(), Error> {
+        if self.buf.len() < self.pos + amount {
+            return Err(Error::BufferTooSmall);
+        }
+        self.pos += amount;
+        Ok(())
+    }
+
+    /// Read a 1-bit value, stored as a single byte.
+    pub(crate) fn u1(&mut self) -> Result<u1, Error> {
+        u1::try_new(self.u8()?).map_err(|_| Error::Corrupt)
+    }
+
+    /// Read a 3-bit value, stored as a single byte.
+    pub(crate) fn u3(&mut self) -> Result<u3, Error> {
+        u3::try_new(self.u8()?).map_err(|_| Error::Corrupt)
+    }
+
+    /// Read a 4-bit value, stored as a single byte.
+    pub(crate) fn u4(&mut self) -> Result<u4, Error> {
+        u4::try_new(self.u8()?).map_err(|_| Error::Corrupt)
+    }
+
+    /// Read a 5-bit value, stored as a single byte.
+    pub(crate) fn u5(&mut self) -> Result<u5, Error> {
+        u5::try_new(self.u8()?).map_err(|_| Error::Corrupt)
+    }
+
+    /// Read a 6-bit value, stored as a single byte.
+    pub(crate) fn u6(&mut self) -> Result<u6, Error> {
+        u6::try_new(self.u8()?).map_err(|_| Error::Corrupt)
+    }
+}
+
+/// A component whose state can be stored within a snapshot.
+pub(crate) trait State {
+    /// The amount of bytes the state of this component occupies.
+    const SIZE: usize;
+
+    /// Write the state of this component.
+    fn save(&self, w: &mut Writer);
+
+    /// Read the state of this component.
+    ///
+    /// If an error is returned, the component may be partially modified.
+    fn load(&mut self, r: &mut Reader) -> Result<(), Error>;
+}
+
+impl State for Cpu {
+    const SIZE: usize = 1 + {
+        let (a, b) = (<Tms1100 as State>::SIZE, <I8021 as State>::SIZE);
+        if a > b {
+            a
+        } else {
+            b
+        }
+    };
+
+    fn save(&self, w: &mut Writer) {
+        // The state of every type of micro-processor is padded to the same size,
+        // so that snapshots remain fixed-size.
+        w.u8(self.kind().index());
+        match self {
+            Self::Tms1100(cpu) => {
+                cpu.save(w);
+                w.zeros(Self::SIZE - 1 - <Tms1100 as State>::SIZE);
+            }
+            Self::I8021(cpu) => {
+                cpu.save(w);
+                w.zeros(Self::SIZE - 1 - <I8021 as State>::SIZE);
+            }
+        }
+    }
+
+    fn load(&mut self, r: &mut Reader) -> Result<(), Error> {
+        let kind = CpuType::from_index(r.u8()?).ok_or(Error::Corrupt)?;
+        *self = if let Some(model) = kind.model() {
+            let mut cpu = Tms1100::new(model);
+            cpu.load(r)?;
+            r.skip(Self::SIZE - 1 - <Tms1100 as State>::SIZE)?;
+            Self::Tms1100(cpu)
+        } else {
+            let mut cpu = I8021::new();
+            cpu.load(r)?;
+            r.skip(Self::SIZE - 1 - <I8021 as State>::SIZE)?;
+            Self::I8021(cpu)
+        };
+        Ok(())
+    }
+}
+
+impl State for Console {
+    const SIZE: usize = <Cpu as State>::SIZE
+        + <crate::display::Hughes0488 as State>::SIZE
+        + <crate::display::Lcd as State>::SIZE
+        + <crate::buzzer::Buzzer as State>::SIZE
+        + <crate::rotary::Rotary as State>::SIZE
+        + 8;
+
+    fn save(&self, w: &mut Writer) {
+        self.cpu.save(w);
+        self.driver.save(w);
+        self.lcd.save(w);
+        self.buzzer.save(w);
+        self.rotary.save(w);
+        w.usize(self.elapsed.0);
+    }
+
+    fn load(&mut self, r: &mut Reader) -> Result<(), Error> {
+        self.cpu.load(r)?;
+        self.driver.load(r)?;
+        self.lcd.load(r)?;
+        self.buzzer.load(r)?;
+        self.rotary.load(r)?;
+        self.elapsed.0 = r.usize()?;
+        Ok(())
+    }
+}
+
+/// Save the state of a console, and the RAM of its cartridge, into a buffer.
+///
+/// This returns the amount of bytes written, which is always [`SIZE`].
+///
+/// # Errors
+///
+/// If the given buffer is smaller than [`SIZE`], [`Error::BufferTooSmall`]
+/// is returned.
+pub(crate) fn save(console: &Console, cart: &Cartridge, buf: &mut [u8]) -> Result<usize, Error> {
+    if buf.len() < SIZE {
+        return Err(Error::BufferTooSmall);
+    }
+
+    let mut w = Writer::new(buf);
+
+    w.bytes(&MAGIC);
+    w.u8(VERSION);
+    w.u16(cart.rom.checksum());
+
+    console.save(&mut w);
+    cart.ram.save(&mut w);
+
+    Ok(w.pos)
+}
+
+/// Return a hash of the state of a console, and the RAM of its cartridge.
+///
+/// This is the 32-bit FNV-1a hash of the snapshot of the console, which is
+/// useful for cheaply checking if two consoles are in an identical state.
+#[must_use]
+pub fn hash(console: &Console, cart: &Cartridge) -> u32 {
+    let mut buf = [0; SIZE];
+    let _ = save(console, cart, &mut buf);
+
+    buf.iter().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(*byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Load the state of a console, and the RAM of its cartridge, from a buffer.
+///
+/// Neither the console nor the cartridge are modified if an error occurs.
+///
+/// # Errors
+///
+/// An error is returned if the given buffer does not contain a valid snapshot
+/// or the snapshot was taken with a different format version or cartridge ROM.
+pub(crate) fn load(console: &mut Console, cart: &mut Cartridge, buf: &[u8]) -> Result<(), Error> {
+    let mut r = Reader::new(buf);
+
+    if r.array()? != MAGIC {
+        return Err(Error::BadMagic);
+    }
+
+    let version = r.u8()?;
+    if version != VERSION {
+        return Err(Error::VersionMismatch { found: version });
+    }
+
+    let expected = cart.rom.checksum();
+    let found = r.u16()?;
+    if found != expected {
+        return Err(Error::ChecksumMismatch { expected, found });
+    }
+
+    let mut new = console.clone();
+    new.load(&mut r)?;
+
+    let mut ram = cart.ram.clone();
+    ram.load(&mut r)?;
+
+    *console = new;
+    cart.ram = ram;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{cartridge, run};
+
+    #[test]
+    fn round_trip() {
+        let mut cart = cartridge();
+        let mut console = Console::new();
+        run(&mut console, &mut cart, 101);
+
+        let mut first = [0; SIZE];
+        assert_eq!(console.save_state(&cart, &mut first), Ok(SIZE));
+
+        let mut other = console.clone();
+        let mut other_cart = cart.clone();
+        run(&mut other, &mut other_cart, 53);
+        assert_eq!(other.load_state(&mut other_cart, &first), Ok(()));
+
+        let mut second = [0; SIZE];
+        other.save_state(&other_cart, &mut second).unwrap();
+        assert_eq!(first, second);
+
+        // Both consoles must continue identically after restoring.
+        run(&mut console, &mut cart, 77);
+        run(&mut other, &mut other_cart, 77);
+        console.save_state(&cart, &mut first).unwrap();
+        other.save_state(&other_cart, &mut second).unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn rejects_invalid() {
+        let mut cart = cartridge();
+        let mut console = Console::new();
+
+        let mut buf = [0; SIZE];
+        assert_eq!(
+            console.save_state(&cart, &mut buf[..SIZE - 1]),
+            Err(Error::BufferTooSmall)
+        );
+        console.save_state(&cart, &mut buf).unwrap();
+
+        let mut bad = buf;
+        bad[0] = b'X';
+        assert_eq!(console.load_state(&mut cart, &bad), Err(Error::BadMagic));
+
+        let mut bad = buf;
+        bad[4] = VERSION + 1;
+        assert_eq!(
+            console.load_state(&mut cart, &bad),
+            Err(Error::VersionMismatch { found: VERSION + 1 })
+        );
+
+        let mut other = cartridge();
+        other.rom.data[0x7ff] = 1;
+        assert_eq!(
+            console.load_state(&mut other, &buf),
+            Err(Error::ChecksumMismatch {
+                expected: cart.rom.checksum() + 1,
+                found: cart.rom.checksum(),
+            })
+        );
+
+        assert_eq!(
+            console.load_state(&mut cart, &buf[..SIZE - 1]),
+            Err(Error::BufferTooSmall)
+        );
+    }
+}
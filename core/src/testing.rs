@@ -0,0 +1,98 @@
+//! A collection of utilities shared between unit tests.
+
+use crate::{
+    buzzer,
+    cartridge::{settings, Cartridge},
+    display, keypad, rotary,
+    tms1100::mem::{Ram, Rom},
+    Console, Interface,
+};
+
+/// A hardware interface which ignores all output and supplies no input.
+pub struct Nothing;
+
+impl display::Api for Nothing {
+    fn enable_pixel(&mut self, _: usize, _: usize) {}
+}
+
+impl buzzer::Api for Nothing {
+    fn enable(&mut self, _: usize) {}
+    fn disable(&mut self) {}
+}
+
+impl keypad::Api for Nothing {
+    fn get(&self, _: keypad::Key) -> bool {
+        false
+    }
+}
+
+impl rotary::Api for Nothing {
+    fn turn(&self) -> rotary::Percentage {
+        rotary::Percentage::new(50)
+    }
+}
+
+/// The input of a single pressed key, or none.
+pub struct Pressed(pub Option<keypad::Key>);
+
+impl keypad::Api for Pressed {
+    fn get(&self, key: keypad::Key) -> bool {
+        self.0.is_some_and(|pressed| pressed.index() == key.index())
+    }
+}
+
+impl rotary::Api for Pressed {
+    fn turn(&self) -> rotary::Percentage {
+        Nothing.turn()
+    }
+}
+
+/// Create a cartridge running a small counting program.
+pub fn cartridge() -> Cartridge {
+    let mut rom = Rom::new();
+    // TCY 3, TCMIY 5, IMAC, TAM, TDO, SETR, BR 0, placed in the execution
+    // order of the program counter.
+    for (addr, opcode) in [0x00, 0x01, 0x03, 0x07, 0x0f, 0x1f, 0x3f]
+        .into_iter()
+        .zip([0x4c, 0x6a, 0x3e, 0x27, 0x0a, 0x0d, 0x80])
+    {
+        rom.data[addr] = opcode;
+    }
+
+    Cartridge {
+        rom,
+        ram: Ram::new(),
+        settings: settings::Settings {
+            cpu: settings::CpuType::Tms1100,
+            charge_info: settings::ChargeInfo::default(),
+            output_pla: settings::OutputPla::default(),
+            micro_pla: None,
+            rotary_enabled: false,
+        },
+    }
+}
+
+/// Clock the given console a number of times.
+pub fn run(console: &mut Console, cart: &mut Cartridge, times: usize) {
+    run_with(console, cart, &Nothing, times);
+}
+
+/// Clock the given console a number of times, with the given keypad and rotary
+/// controller input.
+pub fn run_with<I>(console: &mut Console, cart: &mut Cartridge, input: &I, times: usize)
+where
+    I: keypad::Api + rotary::Api,
+{
+    let (mut a, mut b) = (Nothing, Nothing);
+    for _ in 0..times {
+        console.clock(
+            cart,
+            Interface {
+                display: &mut a,
+                buzzer: &mut b,
+                keypad: input,
+                rotary: input,
+            },
+        );
+    }
+}
@@ -0,0 +1,324 @@
+//! A disassembler for the TMS1000 family of micro-processors.
+//!
+//! Rather than listing a [`Rom`] in linear byte order, the disassembler follows the
+//! order in which the micro-processor actually executes it: chapter by chapter, page
+//! by page, and within every page the 64-step sequence of the `PC` program counter,
+//! see [`next_pc`].
+//!
+//! # Targets
+//!
+//! The `BR` and `CALL` instructions only encode the 6-bit address of their target,
+//! the page and chapter come from the `PB` and `CB` registers respectively. Those
+//! registers can not be known without running the program, so the disassembler
+//! assumes that the target lies in the current page and chapter, unless an `LDP`
+//! or `COMC` instruction precedes the branch within the same page. As a branch may
+//! not be taken, these instructions also apply to every following branch.
+
+use super::{mem::Rom, mem::RomAddr, next_pc, pla::Fixed, Model};
+
+use core::fmt;
+
+use arbitrary_int::{u1, u2, u3, u4, u6, Number};
+
+/// The operand of a disassembled instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    /// The instruction does not have an operand.
+    None,
+    /// A 4-bit constant value, e.g. `TCY 3`.
+    Constant(u4),
+    /// The bit of a RAM nibble, e.g. `SBIT 2`.
+    Bit(u2),
+    /// A value for the `X` memory address register, e.g. `LDX 5`.
+    Register(u3),
+    /// A value for the `PB` page buffer register, e.g. `LDP 12`.
+    Page(u4),
+    /// The target of a branch or call, e.g. `BR 0:3:2a`.
+    Target(RomAddr),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => Ok(()),
+            Self::Constant(val) | Self::Page(val) => write!(f, "{}", val.value()),
+            Self::Bit(val) => write!(f, "{}", val.value()),
+            Self::Register(val) => write!(f, "{}", val.value()),
+            Self::Target(addr) => write!(f, "{addr}"),
+        }
+    }
+}
+
+/// A disassembled instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Instruction {
+    /// The address of this instruction.
+    pub addr: RomAddr,
+    /// The opcode of this instruction.
+    pub opcode: u8,
+    /// The mnemonic of this instruction, e.g. `TCMIY`.
+    pub mnemonic: &'static str,
+    /// The operand of this instruction.
+    pub operand: Operand,
+}
+
+impl Instruction {
+    /// Disassemble a single opcode, located at the given address.
+    ///
+    /// The target of a branch or call is assumed to be within the same page and
+    /// chapter as the given address.
+    #[must_use]
+    pub fn decode(model: Model, addr: RomAddr, opcode: u8) -> Self {
+        // The lower 4-bits of the opcode is a constant value,
+        // however most instructions expect this to be bit-swapped.
+        let constant = u4::new(opcode & 0xf).reverse_bits();
+
+        let mnemonic = if model.is_tms1100() {
+            mnemonic_tms1100(opcode)
+        } else {
+            mnemonic_tms1000(opcode)
+        };
+
+        let operand = match mnemonic {
+            "BR" | "CALL" => Operand::Target(RomAddr::new(
+                addr.chapter(),
+                addr.page(),
+                u6::new(opcode & 0x3f),
+            )),
+            "LDP" => Operand::Page(constant),
+            "LDX" => Operand::Register(u3::new(constant.value() >> (4 - model.x_bits()))),
+            "SBIT" | "RBIT" | "TBIT1" => Operand::Bit(u2::new(constant.value() >> 2)),
+            "TCY" | "YNEC" | "TCMIY" | "AC1AC" | "ALEC" => Operand::Constant(constant),
+            _ => Operand::None,
+        };
+
+        Self {
+            addr,
+            opcode,
+            mnemonic,
+            operand,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}  {:02x}  {}", self.addr, self.opcode, self.mnemonic)?;
+        if self.operand != Operand::None {
+            write!(f, " {}", self.operand)?;
+        }
+        Ok(())
+    }
+}
+
+/// Return the mnemonic of the given opcode in the TMS1100 instruction set.
+fn mnemonic_tms1100(opcode: u8) -> &'static str {
+    match opcode {
+        0x00 => "MNEA",
+        0x01 => "ALEM",
+        0x02 => "YNEA",
+        0x03 => "XMA",
+        0x04 => "DYN",
+        0x05 => "IYC",
+        0x06 => "AMAAC",
+        0x07 => "DMAN",
+        0x08 => "TKA",
+        0x09 => "COMX",
+        0x0a => "TDO",
+        0x0b => "COMC",
+        0x0c => "RSTR",
+        0x0d => "SETR",
+        0x0e => "KNEZ",
+        0x0f => "RETN",
+        0x10..=0x1f => "LDP",
+        0x20 => "TAY",
+        0x21 => "TMA",
+        0x22 => "TMY",
+        0x23 => "TYA",
+        0x24 => "TAMDYN",
+        0x25 => "TAMIYC",
+        0x26 => "TAMZA",
+        0x27 => "TAM",
+        0x28..=0x2f => "LDX",
+        0x30..=0x33 => "SBIT",
+        0x34..=0x37 => "RBIT",
+        0x38..=0x3b => "TBIT1",
+        0x3c => "SAMAN",
+        0x3d => "CPAIZ",
+        0x3e => "IMAC",
+        0x3f => "MNEZ",
+        0x40..=0x4f => "TCY",
+        0x50..=0x5f => "YNEC",
+        0x60..=0x6f => "TCMIY",
+        0x70..=0x7e => "AC1AC",
+        0x7f => "CLA",
+        0x80..=0xbf => "BR",
+        0xc0..=0xff => "CALL",
+    }
+}
+
+/// Return the mnemonic of the given opcode in the TMS1000 instruction set.
+fn mnemonic_tms1000(opcode: u8) -> &'static str {
+    match opcode {
+        0x00 => "COMX",
+        0x01 => "A8AAC",
+        0x02 => "YNEA",
+        0x03 => "TAM",
+        0x04 => "TAMZA",
+        0x05 => "A10AAC",
+        0x06 => "A6AAC",
+        0x07 => "DAN",
+        0x08 => "TKA",
+        0x09 => "KNEZ",
+        0x0a => "TDO",
+        0x0b => "CLO",
+        0x0c => "RSTR",
+        0x0d => "SETR",
+        0x0e => "IA",
+        0x0f => "RETN",
+        0x10..=0x1f => "LDP",
+        0x20 => "TAMIY",
+        0x21 => "TMA",
+        0x22 => "TMY",
+        0x23 => "TYA",
+        0x24 => "TAY",
+        0x25 => "AMAAC",
+        0x26 => "MNEZ",
+        0x27 => "SAMAN",
+        0x28 => "IMAC",
+        0x29 => "ALEM",
+        0x2a => "DMAN",
+        0x2b => "IYC",
+        0x2c => "DYN",
+        0x2d => "CPAIZ",
+        0x2e => "XMA",
+        0x2f => "CLA",
+        0x30..=0x33 => "SBIT",
+        0x34..=0x37 => "RBIT",
+        0x38..=0x3b => "TBIT1",
+        0x3c..=0x3f => "LDX",
+        0x40..=0x4f => "TCY",
+        0x50..=0x5f => "YNEC",
+        0x60..=0x6f => "TCMIY",
+        0x70..=0x7f => "ALEC",
+        0x80..=0xbf => "BR",
+        0xc0..=0xff => "CALL",
+    }
+}
+
+/// An iterator disassembling a ROM in execution order.
+#[derive(Debug, Clone)]
+pub struct Disassembler<'a> {
+    /// The ROM being disassembled.
+    rom: &'a Rom,
+    /// The model of the micro-processor the ROM belongs to.
+    model: Model,
+    /// The address of the next instruction.
+    addr: RomAddr,
+    /// The amount of instructions disassembled within the current page.
+    count: usize,
+    /// The page loaded by the last `LDP` instruction within the current page.
+    page: Option<u4>,
+    /// A flag determining if an odd amount of `COMC` instructions have been
+    /// disassembled within the current page.
+    comc: bool,
+    /// A flag determining if the whole ROM has been disassembled.
+    done: bool,
+}
+
+impl<'a> Disassembler<'a> {
+    /// Create a new disassembler for the given ROM.
+    #[must_use]
+    pub fn new(rom: &'a Rom, model: Model) -> Self {
+        Self {
+            rom,
+            model,
+            addr: RomAddr::new(u1::new(0), u4::new(0), u6::new(0)),
+            count: 0,
+            page: None,
+            comc: false,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for Disassembler<'_> {
+    type Item = Instruction;
+
+    fn next(&mut self) -> Option<Instruction> {
+        if self.done {
+            return None;
+        }
+
+        let addr = self.addr;
+        let opcode = self.rom.data[addr.full().value() as usize & (self.model.rom_size() - 1)];
+        let mut inst = Instruction::decode(self.model, addr, opcode);
+
+        match (self.model.fixed(opcode), &mut inst.operand) {
+            (Some(Fixed::Br | Fixed::Call), Operand::Target(target)) => {
+                *target = RomAddr::new(
+                    addr.chapter() ^ u1::new(self.comc.into()),
+                    self.page.unwrap_or_else(|| addr.page()),
+                    target.addr(),
+                );
+            }
+            (Some(Fixed::Ldp), Operand::Page(page)) => self.page = Some(*page),
+            (Some(Fixed::Comc), _) => self.comc = !self.comc,
+            _ => {}
+        }
+
+        // Move onto the next address in execution order.
+        self.count += 1;
+        if self.count < 64 {
+            self.addr = RomAddr::new(addr.chapter(), addr.page(), next_pc(addr.addr()));
+        } else {
+            self.count = 0;
+            self.page = None;
+            self.comc = false;
+
+            if addr.page() != u4::MAX {
+                self.addr = RomAddr::new(addr.chapter(), addr.page() + u4::new(1), u6::new(0));
+            } else if self.model.has_chapters() && addr.chapter() == u1::new(0) {
+                self.addr = RomAddr::new(u1::new(1), u4::new(0), u6::new(0));
+            } else {
+                self.done = true;
+            }
+        }
+
+        Some(inst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn execution_order() {
+        let mut rom = Rom::new();
+        // TCY 3, LDP 1, BR 0x3e, SBIT 2.
+        rom.data[0x00] = 0x4c;
+        rom.data[0x01] = 0x18;
+        rom.data[0x03] = 0xbe;
+        rom.data[0x07] = 0x31;
+
+        let mut dasm = Disassembler::new(&rom, Model::Tms1100);
+        let first: [_; 4] = core::array::from_fn(|_| dasm.next().unwrap());
+
+        assert_eq!(first[0].addr.full().value(), 0x00);
+        assert_eq!(first[0].mnemonic, "TCY");
+        assert_eq!(first[0].operand, Operand::Constant(u4::new(3)));
+        assert_eq!(first[1].operand, Operand::Page(u4::new(1)));
+        assert_eq!(first[2].addr.full().value(), 0x03);
+        assert_eq!(
+            first[2].operand,
+            Operand::Target(RomAddr::new(u1::new(0), u4::new(1), u6::new(0x3e)))
+        );
+        assert_eq!(first[3].mnemonic, "SBIT");
+        assert_eq!(first[3].operand, Operand::Bit(u2::new(2)));
+
+        // Every address of the ROM is visited exactly once.
+        assert_eq!(Disassembler::new(&rom, Model::Tms1100).count(), 0x800);
+        assert_eq!(Disassembler::new(&rom, Model::Tms1000).count(), 0x400);
+    }
+}
@@ -3,6 +3,13 @@
 //! These chips are embedded within the TMS1100 micro-processor and belong to
 //! the specific game cartridges rather than the Microvision handheld itself.
 
+use crate::{
+    error::Error,
+    snapshot::{self, Reader, State, Writer},
+};
+
+use core::fmt;
+
 use arbitrary_int::{u1, u11, u3, u4, u6, u7};
 use rand::{thread_rng, Rng};
 
@@ -11,7 +18,7 @@ use rand::{thread_rng, Rng};
 /// Rather than simply taking an 11-bit value as a ROM address, the TMS1100's
 /// ROM chip takes a chapter (`c`), page (`p`) and address (`a`). All of these
 /// inputs combine to form a full 11-bit address like so: `0b[c][pppp][aaaaaa]`.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RomAddr {
     /// The chapter (`c`).
     chapter: u1,
@@ -37,6 +44,37 @@ impl RomAddr {
     pub fn full(&self) -> u11 {
         u11::from(self.chapter) << 10 | u11::from(self.page) << 6 | u11::from(self.addr)
     }
+
+    /// Return the chapter (`c`).
+    #[must_use]
+    pub fn chapter(&self) -> u1 {
+        self.chapter
+    }
+
+    /// Return the page (`p`).
+    #[must_use]
+    pub fn page(&self) -> u4 {
+        self.page
+    }
+
+    /// Return the address (`a`).
+    #[must_use]
+    pub fn addr(&self) -> u6 {
+        self.addr
+    }
+}
+
+impl fmt::Display for RomAddr {
+    /// Format this address as a `chapter:page:address` label, e.g. `1:f:3a`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{:x}:{:02x}",
+            self.chapter.value(),
+            self.page.value(),
+            self.addr.value()
+        )
+    }
 }
 
 /// The TMS1100's 2kb (2048 x 8-bit) Read Only Memory (ROM) chip.
@@ -75,6 +113,26 @@ impl Rom {
         self.data[slice.len()..].fill(0);
     }
 
+    /// Copy the data from a slice into this ROM chip.
+    ///
+    /// See [copy](Self::copy) for more details.
+    ///
+    /// # Errors
+    ///
+    /// If the given slice is greater than 2kb an error is returned, and this ROM
+    /// chip is not modified.
+    pub fn try_copy(&mut self, slice: &[u8]) -> Result<(), Error> {
+        if slice.len() > 0x800 {
+            return Err(Error::RomTooLarge {
+                size: slice.len(),
+                max: 0x800,
+            });
+        }
+
+        self.copy(slice);
+        Ok(())
+    }
+
     /// Read from this ROM chip at the specified address.
     #[must_use]
     pub fn read(&self, addr: RomAddr) -> u8 {
@@ -98,7 +156,7 @@ impl Rom {
 /// RAM chip takes a memory address (`x`) and a memory address (`y`). These
 /// inputs combine to form a 7-bit (or more specifically a grid) index into
 /// RAM data like so: `0b[xxx][yyyy]`.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct RamAddr {
     /// The memory address (`x`)
     x: u3,
@@ -120,6 +178,13 @@ impl RamAddr {
     }
 }
 
+impl fmt::Display for RamAddr {
+    /// Format this address as a `x:y` label, e.g. `3:a`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{:x}", self.x.value(), self.y.value())
+    }
+}
+
 /// The TMS1100's 64b (128 x 4-bit) Random Access Memory (RAM) chip.
 #[derive(Debug, Clone)]
 pub struct Ram {
@@ -161,3 +226,22 @@ impl Ram {
         self.data[addr.full().value() as usize & 0x7f] = val;
     }
 }
+
+impl State for Ram {
+    const SIZE: usize = 0x40;
+
+    fn save(&self, w: &mut Writer) {
+        for pair in self.data.chunks(2) {
+            w.u8(pair[0].value() << 4 | pair[1].value());
+        }
+    }
+
+    fn load(&mut self, r: &mut Reader) -> Result<(), snapshot::Error> {
+        for pair in self.data.chunks_mut(2) {
+            let byte = r.u8()?;
+            pair[0] = u4::new(byte >> 4);
+            pair[1] = u4::new(byte & 0xf);
+        }
+        Ok(())
+    }
+}
@@ -3,25 +3,161 @@
 //! The usage of the term cycle-accuracy does not fully apply to internal pipelines
 //! or processes, it mostly applies to the external output/input signal(s).
 //!
+//! The same implementation also covers the rest of the TMS1000 family, e.g. the
+//! TMS1000 and TMS1300, which differ in their ROM/RAM sizes, pin counts and the
+//! presence of chapters, see [`Model`].
+//!
 //! # Links
 //!
 //! - MAME: <https://github.com/mamedev/mame/blob/master/src/devices/cpu/tms1000/tms1k_base.cpp>
 //! - Data Manual: <http://www.bitsavers.org/components/ti/TMS1000/TMS_1000_Series_Data_Manual_Dec76.pdf>
 //! - Programmers Reference: <https://en.wikichip.org/w/images/f/ff/TMS1000_Series_Programmer%27s_reference_manual.pdf>
 
+pub mod dasm;
 pub mod mem;
 pub mod pinio;
 pub mod pla;
+pub mod trace;
+
+use crate::snapshot::{self, Reader, State, Writer};
 
+use dasm::Instruction;
 use mem::{Ram, RamAddr, Rom, RomAddr};
 use pla::{
     instructions::{
         ATN, AUTA, AUTY, C8, CIN, CKM, CKN, CKP, FTN, MTN, MTP, NATN, NE, STO, STSL, YTP,
     },
-    Entry, Fixed,
+    Entry, Fixed, MicroPla,
 };
+use trace::{Record, Tracer};
+
+use arbitrary_int::{u1, u3, u4, u5, u6, Number};
+
+/// A member of the TMS1000 family of micro-processors.
+///
+/// The members of the family share the same core, but differ in the size of
+/// their ROM and RAM chips, the amount of R and O output pins and their default
+/// instruction set.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model {
+    /// The TMS1000, with a 1kb ROM, 64 nibbles of RAM and 11 R pins.
+    Tms1000,
+    /// The TMS1070, a high-voltage variant of the TMS1000.
+    Tms1070,
+    /// The TMS1100, with a 2kb ROM (2 chapters), 128 nibbles of RAM and 11 R pins.
+    #[default]
+    Tms1100,
+    /// The TMS1200, a TMS1000 with 13 R pins.
+    Tms1200,
+    /// The TMS1270, a high-voltage variant of the TMS1000 with 13 R pins and 10 O pins.
+    ///
+    /// Only the first 8 O pins are emulated, the O path from the `O` latch through
+    /// the output PLA is 8 bits wide, therefore `O8` and `O9` are never driven.
+    Tms1270,
+    /// The TMS1300, a TMS1100 with 16 R pins.
+    Tms1300,
+    /// The TMS1370, a high-voltage variant of the TMS1300.
+    Tms1370,
+}
+
+impl Model {
+    /// Check if this model is derived from the TMS1100.
+    ///
+    /// These models have a larger ROM and RAM, chapters and use the TMS1100
+    /// instruction set, the remaining models use the TMS1000 instruction set.
+    #[must_use]
+    pub fn is_tms1100(self) -> bool {
+        matches!(self, Self::Tms1100 | Self::Tms1300 | Self::Tms1370)
+    }
 
-use arbitrary_int::{u1, u11, u3, u4, u5, u6, Number};
+    /// Return the size of the ROM chip, in bytes.
+    #[must_use]
+    pub fn rom_size(self) -> usize {
+        if self.is_tms1100() {
+            0x800
+        } else {
+            0x400
+        }
+    }
+
+    /// Return the size of the RAM chip, in nibbles.
+    #[must_use]
+    pub fn ram_size(self) -> usize {
+        if self.is_tms1100() {
+            0x80
+        } else {
+            0x40
+        }
+    }
+
+    /// Return the width of the `X` memory address register, in bits.
+    #[must_use]
+    pub fn x_bits(self) -> u8 {
+        if self.is_tms1100() {
+            3
+        } else {
+            2
+        }
+    }
+
+    /// Check if the ROM chip is split into chapters.
+    #[must_use]
+    pub fn has_chapters(self) -> bool {
+        self.is_tms1100()
+    }
+
+    /// Return the amount of R output pins.
+    #[must_use]
+    pub fn r_pins(self) -> u8 {
+        match self {
+            Self::Tms1000 | Self::Tms1070 | Self::Tms1100 => 11,
+            Self::Tms1200 | Self::Tms1270 => 13,
+            Self::Tms1300 | Self::Tms1370 => 16,
+        }
+    }
+
+    /// Return the fixed-instruction for the given opcode.
+    #[must_use]
+    pub fn fixed(self, opcode: u8) -> Option<Fixed> {
+        if self.is_tms1100() {
+            Fixed::decode(opcode)
+        } else {
+            Fixed::decode_tms1000(opcode)
+        }
+    }
+
+    /// Return the (standard) micro-instruction PLA entry for the given opcode.
+    #[must_use]
+    pub fn micro(self, opcode: u8) -> Entry {
+        if self.is_tms1100() {
+            Entry::decode(opcode)
+        } else {
+            Entry::decode_tms1000(opcode)
+        }
+    }
+}
+
+/// Return the successor of the given `PC` program counter value.
+///
+/// # Logic
+///
+/// The program counter is Linear Feedback Shift Register (LFSR), this means that
+/// a feedback bit exists which is a XNOR of the highest two bits. However, this bit
+/// does make an exception when all the low bits of the program counter are set, so
+/// that the counter visits all 64 addresses of a page, starting with the sequence
+/// `00, 01, 03, 07, 0f, 1f, 3f, 3e, 3d, ...`.
+#[must_use]
+pub fn next_pc(pc: u6) -> u6 {
+    let mut feedback = u6::new(u8::from(pc.value() >> 5 == (pc.value() >> 4 & 1)));
+
+    if pc == u6::MAX >> 1 {
+        feedback = u6::new(1);
+    } else if pc == u6::MAX {
+        feedback = u6::new(0);
+    }
+
+    pc << 1 | feedback
+}
 
 /// The internal adder circuit of the TMS1100.
 ///
@@ -48,7 +184,8 @@ pub struct Adder {
     ///
     /// This acts as a flag output for the adder which can be modified through
     /// micro-instructions to represent the carry flag or a flag indicating
-    /// the inequality of the adder inputs P and N.
+    /// the inequality of the adder inputs P and N. It is set at the start of
+    /// every instruction, and branches or calls are only performed if it is set.
     pub status_out: bool,
 }
 
@@ -98,7 +235,7 @@ impl Adder {
 /// The TMS1100 operates on 6 oscillator cycles within a larger machine cycle,
 /// with the general process going: fetch data from memory, then execute an
 /// operation. Therefore, we need to represent each of 6 cycles as separate units.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Cycle {
     /// The first sub-instruction cycle.
     ///
@@ -164,6 +301,25 @@ impl Cycle {
             Self::On5 => Self::On0,
         }
     }
+
+    /// Return the sub-instruction cycle with the given index.
+    ///
+    /// This is the inverse of casting a cycle to an integer, e.g. `cycle as u8`,
+    /// and returns [None] if the index is not within the range of `0..=5`.
+    #[must_use]
+    pub fn from_index(idx: u8) -> Option<Self> {
+        let cycle = match idx {
+            0 => Self::On0,
+            1 => Self::On1,
+            2 => Self::On2,
+            3 => Self::On3,
+            4 => Self::On4,
+            5 => Self::On5,
+            _ => return None,
+        };
+
+        Some(cycle)
+    }
 }
 
 /// The branch/status flags of the TMS1100.
@@ -182,8 +338,8 @@ pub struct Flags {
     ///
     /// # Logic
     ///
-    /// This stores the results from adder operations and is used by instructions
-    /// to conditionally perform branches or calls.
+    /// This latches the status output of the adder, through the `STSL`
+    /// micro-instruction, and is output on the `O` pins by the `TDO` instruction.
     pub status: bool,
 }
 
@@ -224,10 +380,12 @@ pub struct Registers {
     pub cs: u1,
 }
 
-/// An emulated TMS1100 micro-processor.
+/// An emulated TMS1100 (or TMS1000 family) micro-processor.
 #[derive(Debug, Clone)]
 pub struct Tms1100 {
-    /// The 11-bit pin output R\[0-10\].
+    /// The model of this micro-processor.
+    pub model: Model,
+    /// The (up to) 16-bit pin output R\[0-15\].
     pub r: pinio::R,
     /// The 5-bit pin output O\[0-4\].
     pub o: pinio::O,
@@ -243,6 +401,8 @@ pub struct Tms1100 {
     pub cycle: Cycle,
     /// The currently decoded (and executing) opcode.
     pub opcode: u8,
+    /// The ROM address of the current opcode.
+    addr: RomAddr,
     /// The fixed instruction of the current opcode.
     pub fixed: Option<Fixed>,
     /// The micro-instruction PLA entry of the current opcode.
@@ -258,13 +418,16 @@ pub struct Tms1100 {
     ///
     /// The contents of this bus vary depending on the current instruction.
     cki_data: u4,
+    /// A flag determining if an opcode has been fetched since the last reset.
+    fetched: bool,
 }
 
 impl Tms1100 {
-    /// Create a new TMS1100 micro-processor.
+    /// Create a new TMS1100 (or TMS1000 family) micro-processor.
     #[must_use]
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(model: Model) -> Self {
         Self {
+            model,
             r: pinio::R::new(),
             o: pinio::O::new(),
             k: pinio::K::new(),
@@ -287,59 +450,73 @@ impl Tms1100 {
             },
             cycle: Cycle::On0,
             opcode: 0x00,
+            addr: RomAddr::new(u1::new(0), u4::new(0), u6::new(0)),
             fixed: None,
             micro: Entry::EMPTY,
             constant: u4::new(0),
             ram_data: u4::new(0),
             cki_data: u4::new(0),
+            fetched: false,
         }
     }
 
     /// Reset this micro-processor.
     pub(crate) fn reset(&mut self) {
-        *self = Self::new();
+        *self = Self::new(self.model);
+    }
+
+    /// Return the address of the current instruction.
+    ///
+    /// # Note
+    ///
+    /// Unlike the `PC` program counter, which is incremented directly after an opcode
+    /// is fetched, this is not modified while the instruction executes.
+    #[must_use]
+    pub fn addr(&self) -> RomAddr {
+        self.addr
     }
 
     /// Increment the `PC` program counter.
     fn next_pc(&mut self) {
-        // The program counter is Linear Feedback Shift Register (LFSR).
-        //
-        // This means that a feedback bit exists which is a XOR of the
-        // highest two bits. However, this bit does make an exception
-        // when all the low bits of the program counter are set.
-
-        let mut feedback = (self.regs.pc << 1) >> 5 & self.regs.pc >> 5;
-
-        if self.regs.pc == u6::MAX >> 1 {
-            feedback = u6::new(1);
-        } else if self.regs.pc == u6::MAX {
-            feedback = u6::new(0);
-        }
-
-        self.regs.pc = self.regs.pc << 1 | feedback;
+        self.regs.pc = next_pc(self.regs.pc);
     }
 
     /// Read the next opcode from ROM.
-    fn next_opcode(&mut self, rom: &Rom) {
-        self.opcode = rom.read(RomAddr::new(self.regs.cs, self.regs.pa, self.regs.pc));
+    ///
+    /// The opcode is decoded using the given micro-instruction PLA, or the standard
+    /// PLA of the model if none is given.
+    fn next_opcode(&mut self, rom: &Rom, pla: Option<&MicroPla>) {
+        self.addr = RomAddr::new(self.regs.ca, self.regs.pa, self.regs.pc);
+        self.opcode = rom.data[self.addr.full().value() as usize & (self.model.rom_size() - 1)];
 
         // The lower 4-bits of the opcode is a constant value,
         // however most instructions expect this to be bit-swapped.
         self.constant = u4::new(self.opcode & 0xf).reverse_bits();
 
-        self.fixed = Fixed::decode(self.opcode);
-        self.micro = Entry::decode(self.opcode);
+        self.fixed = self.model.fixed(self.opcode);
+        self.micro = match pla {
+            Some(pla) => pla.decode(self.opcode),
+            None => self.model.micro(self.opcode),
+        };
+        self.fetched = true;
 
         self.next_pc();
     }
 
+    /// Check if the current instruction writes to RAM.
+    pub(crate) fn writes_ram(&self) -> bool {
+        matches!(self.fixed, Some(Fixed::Sbit | Fixed::Rbit))
+            || self.micro.enables::<STO>()
+            || self.micro.enables::<CKM>()
+    }
+
     /// Read a value onto the `CKI` data bus.
     fn read_cki(&mut self) {
         self.cki_data = match self.opcode & 0xf8 {
             // Opcode: 00001XXX, reads the K inputs.
             0x08 => self.k.0,
             // Opcode: 0011XXXX, select the bit to modify.
-            0x30 | 0x38 => u4::new(1) << ((self.constant.value() >> 2) ^ 0xf),
+            0x30 | 0x38 => (u4::new(1) << (self.constant.value() >> 2)) ^ u4::new(0xf),
             // Opcode: 01XXXXXX, a constant value.
             0x00 | 0x40 | 0x48 | 0x50 | 0x58 | 0x60 | 0x68 | 0x70 | 0x78 => self.constant,
             _ => u4::new(0),
@@ -349,7 +526,7 @@ impl Tms1100 {
     /// Execute the first sub-instruction cycle.
     fn exec_0(&mut self, ram: &Ram) {
         match self.fixed {
-            Some(Fixed::Br) if self.flags.status => {
+            Some(Fixed::Br) if self.adder.status_out => {
                 if !self.flags.call {
                     self.regs.pa = self.regs.pb;
                 }
@@ -357,7 +534,7 @@ impl Tms1100 {
                 self.regs.ca = self.regs.cb;
                 self.regs.pc = u6::new(self.opcode & 0x3f);
             }
-            Some(Fixed::Call) if self.flags.status => {
+            Some(Fixed::Call) if self.adder.status_out => {
                 let prev_pa = self.regs.pa;
 
                 if !self.flags.call {
@@ -437,39 +614,58 @@ impl Tms1100 {
                 self.regs.cb ^= u1::MAX;
             }
             Some(Fixed::Comx) => {
-                self.regs.x ^= u3::MAX;
+                // The TMS1100 only complements the most significant bit.
+                self.regs.x ^= if self.model.is_tms1100() {
+                    u3::new(1 << (self.model.x_bits() - 1))
+                } else {
+                    u3::MAX >> (3 - self.model.x_bits())
+                };
             }
             Some(Fixed::Ldp) => {
                 self.regs.pb = self.constant;
             }
             Some(Fixed::Ldx) => {
-                self.regs.x = u3::new(self.constant.value() >> 1);
+                self.regs.x = u3::new(self.constant.value() >> (4 - self.model.x_bits()));
             }
             Some(Fixed::Rbit) => {
                 self.ram_data &= self.cki_data;
             }
             Some(Fixed::Rstr) => {
                 let idx = (self.regs.x.value() >> 2) << 4 | self.regs.y.value();
-                self.r.0 &= !(u11::new(1) << idx);
+                if idx < self.model.r_pins() {
+                    self.r.0 &= !(1 << idx);
+                }
             }
             Some(Fixed::Sbit) => {
                 self.ram_data |= self.cki_data ^ u4::new(0xf);
             }
             Some(Fixed::Setr) => {
                 let idx = (self.regs.x.value() >> 2) << 4 | self.regs.y.value();
-                self.r.0 |= u11::new(1) << idx;
+                if idx < self.model.r_pins() {
+                    self.r.0 |= 1 << idx;
+                }
             }
             Some(Fixed::Tdo) => {
-                self.o.0 = u5::new(u8::from(self.flags.status)) | u5::new(self.regs.a.value());
+                self.o.0 = u5::new(u8::from(self.flags.status) << 4 | self.regs.a.value());
+            }
+            Some(Fixed::Clo) => {
+                self.o.0 = u5::new(0);
             }
             _ => {}
         }
 
-        ram.write(RamAddr::new(self.regs.x, self.regs.y), self.ram_data);
+        // The address was already latched on the first sub-instruction cycle, none
+        // of the instructions writing to RAM modify it beforehand.
+        if self.writes_ram() {
+            ram.write(RamAddr::new(self.regs.x, self.regs.y), self.ram_data);
+        }
     }
 
     /// Execute the fifth sub-instruction cycle.
-    fn exec_4(&mut self, rom: &Rom) {
+    fn exec_4<T: Tracer>(&mut self, rom: &Rom, pla: Option<&MicroPla>, tracer: &mut T) {
+        // RAM is written on the third sub-instruction cycle, before `Y` is modified.
+        let written = RamAddr::new(self.regs.x, self.regs.y);
+
         if self.micro.enables::<AUTA>() {
             self.regs.a = self.adder.output;
         }
@@ -480,7 +676,30 @@ impl Tms1100 {
             self.flags.status = self.adder.status_out;
         }
 
-        self.next_opcode(rom);
+        if T::ENABLED {
+            self.trace(written, tracer);
+        }
+
+        self.next_opcode(rom, pla);
+    }
+
+    /// Pass a record of the current (completed) instruction to the given tracer.
+    ///
+    /// The idle instruction executed after a reset, before the first opcode has been
+    /// fetched, is not traced.
+    fn trace<T: Tracer>(&self, written: RamAddr, tracer: &mut T) {
+        if !self.fetched {
+            return;
+        }
+
+        tracer.trace(&Record {
+            inst: Instruction::decode(self.model, self.addr, self.opcode),
+            regs: self.regs,
+            status: self.flags.status,
+            r: self.r.0,
+            o: self.o.0,
+            ram: self.writes_ram().then_some((written, self.ram_data)),
+        });
     }
 
     /// Clock (update) this micro-processor.
@@ -488,13 +707,23 @@ impl Tms1100 {
     /// # Logic
     ///
     /// This executes a single sub-instruction cycle, 1/6 of a whole instruction.
+    ///
+    /// Opcodes are decoded using the given micro-instruction PLA, if any, otherwise the
+    /// standard PLA of the model is used. Every completed instruction is passed to the
+    /// given tracer.
     #[allow(clippy::similar_names)]
-    pub(crate) fn clock(&mut self, rom: &Rom, ram: &mut Ram) {
+    pub(crate) fn clock<T: Tracer>(
+        &mut self,
+        rom: &Rom,
+        ram: &mut Ram,
+        pla: Option<&MicroPla>,
+        tracer: &mut T,
+    ) {
         match self.cycle {
             Cycle::On0 => self.exec_0(ram),
             Cycle::On1 => self.exec_1(),
             Cycle::On2 => self.exec_2(ram),
-            Cycle::On4 => self.exec_4(rom),
+            Cycle::On4 => self.exec_4(rom, pla, tracer),
             Cycle::On3 | Cycle::On5 => {
                 // These sub-instruction cycles are idle in this emulation.
             }
@@ -503,3 +732,273 @@ impl Tms1100 {
         self.cycle.next();
     }
 }
+
+impl State for Tms1100 {
+    const SIZE: usize = 33;
+
+    fn save(&self, w: &mut Writer) {
+        w.u16(self.r.0);
+        w.u8(self.o.0.value());
+        w.u8(self.k.0.value());
+
+        w.u8(self.adder.p.value());
+        w.u8(self.adder.n.value());
+        w.u8(self.adder.output.value());
+        w.bool(self.adder.carry_in);
+        w.bool(self.adder.status_out);
+
+        w.bool(self.flags.call);
+        w.bool(self.flags.status);
+
+        w.u8(self.regs.a.value());
+        w.u8(self.regs.x.value());
+        w.u8(self.regs.y.value());
+        w.u8(self.regs.pc.value());
+        w.u8(self.regs.sr.value());
+        w.u8(self.regs.pa.value());
+        w.u8(self.regs.pb.value());
+        w.u8(self.regs.ca.value());
+        w.u8(self.regs.cb.value());
+        w.u8(self.regs.cs.value());
+
+        w.u8(self.cycle as u8);
+        w.u8(self.opcode);
+        w.u8(self.addr.chapter().value());
+        w.u8(self.addr.page().value());
+        w.u8(self.addr.addr().value());
+        w.u8(self.fixed.map_or(0, |fixed| fixed as u8 + 1));
+        w.u16(self.micro.0);
+        w.u8(self.constant.value());
+        w.u8(self.ram_data.value());
+        w.u8(self.cki_data.value());
+        w.bool(self.fetched);
+    }
+
+    fn load(&mut self, r: &mut Reader) -> Result<(), snapshot::Error> {
+        self.r.0 = r.u16()?;
+        if u32::from(self.r.0) >> self.model.r_pins() != 0 {
+            return Err(snapshot::Error::Corrupt);
+        }
+        self.o.0 = r.u5()?;
+        self.k.0 = r.u4()?;
+
+        self.adder.p = r.u4()?;
+        self.adder.n = r.u4()?;
+        self.adder.output = r.u4()?;
+        self.adder.carry_in = r.bool()?;
+        self.adder.status_out = r.bool()?;
+
+        self.flags.call = r.bool()?;
+        self.flags.status = r.bool()?;
+
+        self.regs.a = r.u4()?;
+        self.regs.x = r.u3()?;
+        if self.regs.x.value() >> self.model.x_bits() != 0 {
+            return Err(snapshot::Error::Corrupt);
+        }
+        self.regs.y = r.u4()?;
+        self.regs.pc = r.u6()?;
+        self.regs.sr = r.u6()?;
+        self.regs.pa = r.u4()?;
+        self.regs.pb = r.u4()?;
+        self.regs.ca = r.u1()?;
+        self.regs.cb = r.u1()?;
+        self.regs.cs = r.u1()?;
+
+        self.cycle = Cycle::from_index(r.u8()?).ok_or(snapshot::Error::Corrupt)?;
+        self.opcode = r.u8()?;
+        self.addr = RomAddr::new(r.u1()?, r.u4()?, r.u6()?);
+        self.fixed = match r.u8()? {
+            0 => None,
+            idx => Some(Fixed::from_index(idx - 1).ok_or(snapshot::Error::Corrupt)?),
+        };
+        self.micro = Entry(r.u16()?);
+        self.constant = r.u4()?;
+        self.ram_data = r.u4()?;
+        self.cki_data = r.u4()?;
+        self.fetched = r.bool()?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create a ROM containing a program, given in the execution order of the
+    /// program counter.
+    fn rom(program: &[u8]) -> Rom {
+        let mut rom = Rom::new();
+        let mut pc = u6::new(0);
+        for &opcode in program {
+            rom.data[usize::from(pc.value())] = opcode;
+            pc = next_pc(pc);
+        }
+        rom
+    }
+
+    /// Run a program until the given amount of instructions have been executed.
+    fn run(model: Model, program: &[u8], ram: &mut Ram, instructions: usize) -> Tms1100 {
+        let rom = rom(program);
+
+        // The first instruction after a reset is idle, as no opcode is fetched yet.
+        let mut cpu = Tms1100::new(model);
+        for _ in 0..(instructions + 1) * 6 {
+            cpu.clock(&rom, ram, None, &mut ());
+        }
+        cpu
+    }
+
+    #[test]
+    fn fetches_from_chapter_address() {
+        let mut rom = Rom::new();
+        // TCY 3 in chapter 0, and TCY 5 at the same page and address in chapter 1.
+        rom.data[0x000] = 0x4c;
+        rom.data[0x400] = 0x4a;
+
+        // A branch into another chapter only modifies CA, CS is set by calls.
+        let mut cpu = Tms1100::new(Model::Tms1100);
+        cpu.regs.ca = u1::new(1);
+        for _ in 0..6 {
+            cpu.clock(&rom, &mut Ram::new(), None, &mut ());
+        }
+        assert_eq!(cpu.regs.cs, u1::new(0));
+        assert_eq!(cpu.opcode, 0x4a);
+    }
+
+    #[test]
+    fn lfsr_program_counter() {
+        // The feedback bit was previously the AND of the highest two bits, which
+        // left the program counter stuck at address 0.
+        assert_eq!(next_pc(u6::new(0)), u6::new(0x01));
+
+        let mut pc = u6::new(0);
+        let mut visited = 0u64;
+        for _ in 0..64 {
+            visited |= 1 << pc.value();
+            pc = next_pc(pc);
+        }
+        assert_eq!(visited, u64::MAX);
+        assert_eq!(pc, u6::new(0));
+    }
+
+    #[test]
+    fn bit_select() {
+        let addr = RamAddr::new(u3::new(0), u4::new(0));
+
+        // SBIT 2, SBIT 0, RBIT 2. The bit-select value on the CKI bus previously
+        // shifted by the complemented bit, overflowing the 4-bit value, instead of
+        // complementing the selected bit.
+        let program = [0x31, 0x30, 0x35];
+        for (instructions, expected) in [(1, 0b0100), (2, 0b0101), (3, 0b0001)] {
+            let mut ram = Ram::new();
+            run(Model::Tms1100, &program, &mut ram, instructions);
+            assert_eq!(ram.read(addr), u4::new(expected));
+        }
+    }
+
+    #[test]
+    fn branch_on_status() {
+        // TCY 3, BR 0x20. The status is set at the start of every instruction, but
+        // SL is only latched by STSL, so it is still clear.
+        let cpu = run(Model::Tms1100, &[0x4c, 0xa0], &mut Ram::new(), 2);
+        assert!(!cpu.flags.status);
+        assert_eq!(cpu.addr().addr(), u6::new(0x20));
+
+        // TCY 1, YNEA (latching a set status into SL), YNEC 1, BR 0x20.
+        let cpu = run(
+            Model::Tms1100,
+            &[0x48, 0x02, 0x58, 0xa0],
+            &mut Ram::new(),
+            4,
+        );
+        assert!(cpu.flags.status);
+        assert_eq!(cpu.addr().addr(), u6::new(0x0f));
+    }
+
+    #[test]
+    fn call_on_status() {
+        // TCY 3, CALL 0x20.
+        let cpu = run(Model::Tms1100, &[0x4c, 0xe0], &mut Ram::new(), 2);
+        assert!(cpu.flags.call);
+        assert_eq!(cpu.addr().addr(), u6::new(0x20));
+
+        // TCY 1, YNEA, YNEC 1, CALL 0x20.
+        let cpu = run(
+            Model::Tms1100,
+            &[0x48, 0x02, 0x58, 0xe0],
+            &mut Ram::new(),
+            4,
+        );
+        assert!(!cpu.flags.call);
+        assert_eq!(cpu.addr().addr(), u6::new(0x0f));
+    }
+
+    #[test]
+    fn tdo_outputs_status_latch() {
+        // TCY 5, TYA, TCY 1, YNEA, TDO. SL is output on O4, above the accumulator,
+        // where it was previously ORed into the lowest bit.
+        let program = [0x4a, 0x23, 0x48, 0x02, 0x0a];
+        let cpu = run(Model::Tms1100, &program, &mut Ram::new(), 5);
+        assert_eq!(cpu.o.0, u5::new(0x15));
+    }
+
+    #[test]
+    fn comx() {
+        // LDX 5, COMX. The TMS1100 only complements the most significant bit of X,
+        // where all bits were previously complemented.
+        let cpu = run(Model::Tms1100, &[0x2d, 0x09], &mut Ram::new(), 2);
+        assert_eq!(cpu.regs.x, u3::new(1));
+
+        // COMX, the TMS1000 does complement all bits.
+        let cpu = run(Model::Tms1000, &[0x00], &mut Ram::new(), 1);
+        assert_eq!(cpu.regs.x, u3::new(3));
+    }
+
+    #[test]
+    fn ram_written_by_writers_only() {
+        let addr = RamAddr::new(u3::new(0), u4::new(0));
+        // TYA, TAM.
+        let rom = rom(&[0x23, 0x27]);
+
+        // RAM is modified after it is read on the first sub-instruction cycle, e.g.
+        // by a debugger. Every instruction previously wrote the value it read back
+        // on the third sub-instruction cycle, undoing the modification.
+        for (skip, expected) in [(1, 7), (2, 0)] {
+            let mut ram = Ram::new();
+            let mut cpu = Tms1100::new(Model::Tms1100);
+            for _ in 0..=skip * 6 {
+                cpu.clock(&rom, &mut ram, None, &mut ());
+            }
+
+            ram.write(addr, u4::new(7));
+            for _ in 1..6 {
+                cpu.clock(&rom, &mut ram, None, &mut ());
+            }
+            assert_eq!(ram.read(addr), u4::new(expected));
+        }
+    }
+
+    #[test]
+    fn traces_empty_opcodes() {
+        /// A tracer counting the traced instructions.
+        struct Counter(usize);
+
+        impl Tracer for Counter {
+            fn trace(&mut self, _: &Record) {
+                self.0 += 1;
+            }
+        }
+
+        // With a custom PLA, opcode 0x00 may decode to no micro-instructions at all,
+        // which was mistaken for the idle instruction after a reset.
+        let pla = MicroPla::new([Entry::EMPTY; 0x80]);
+        let (rom, mut ram) = (Rom::new(), Ram::new());
+        let (mut cpu, mut counter) = (Tms1100::new(Model::Tms1100), Counter(0));
+        for _ in 0..4 * 6 {
+            cpu.clock(&rom, &mut ram, Some(&pla), &mut counter);
+        }
+        assert_eq!(counter.0, 3);
+    }
+}
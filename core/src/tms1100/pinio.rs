@@ -1,26 +1,31 @@
 //! Emulation of the TMS1100's input and output pins.
 
-use arbitrary_int::{u11, u4, u5};
+use crate::error::Error;
 
-/// The 11-bit pin output R\[0-10\].
+use arbitrary_int::{u4, u5};
+
+/// The (up to) 16-bit pin output R\[0-15\].
 ///
 /// # Logic
 ///
 /// This is mapped, by the cartridge, to various components of the Microvision,
 /// such as the rotary controller, Piezo buzzer, LCD driver, etc. etc.
+///
+/// The amount of pins which actually exist depends upon the [`Model`](super::Model)
+/// of the micro-processor, e.g. the TMS1100 only has the 11 pins R\[0-10\].
 #[derive(Debug, Clone, Copy)]
-pub struct R(pub(crate) u11);
+pub struct R(pub(crate) u16);
 
 impl R {
-    /// Create a new 11-bit pin output.
+    /// Create a new 16-bit pin output.
     #[must_use]
     pub(crate) fn new() -> Self {
-        Self(u11::new(0))
+        Self(0)
     }
 
-    /// Return the inner 11-bit value of this pin output.
+    /// Return the inner 16-bit value of this pin output.
     #[must_use]
-    pub fn value(&self) -> u11 {
+    pub fn value(&self) -> u16 {
         self.0
     }
 
@@ -28,26 +33,49 @@ impl R {
     ///
     /// # Panics
     ///
-    /// If the given bit is not within the range of `0..=10`, this function will
+    /// If the given bit is not within the range of `0..=15`, this function will
     /// panic.
     pub fn set(&mut self, nth: u8, state: bool) {
-        assert!(nth < 4);
+        assert!(nth < 16);
 
-        self.0 &= !(u11::new(0) << nth);
-        self.0 |= u11::new(state.into()) << nth;
+        self.0 &= !(1 << nth);
+        self.0 |= u16::from(state) << nth;
     }
 
     /// Check if the nth-bit of this pin output is enabled.
     ///
     /// # Panics
     ///
-    /// If the given bit is not within the range of `0..=10`, this function will
+    /// If the given bit is not within the range of `0..=15`, this function will
     /// panic.
     #[must_use]
     pub fn get(&self, nth: u8) -> bool {
-        assert!(nth < 11);
+        assert!(nth < 16);
 
-        self.0.value() >> nth & 1 != 0
+        self.0 >> nth & 1 != 0
+    }
+
+    /// Set the nth-bit of this pin output.
+    ///
+    /// # Errors
+    ///
+    /// If the given bit is not within the range of `0..=15`, an error is
+    /// returned.
+    pub fn try_set(&mut self, nth: u8, state: bool) -> Result<(), Error> {
+        check(16, nth)?;
+        self.set(nth, state);
+        Ok(())
+    }
+
+    /// Check if the nth-bit of this pin output is enabled.
+    ///
+    /// # Errors
+    ///
+    /// If the given bit is not within the range of `0..=15`, an error is
+    /// returned.
+    pub fn try_get(&self, nth: u8) -> Result<bool, Error> {
+        check(16, nth)?;
+        Ok(self.get(nth))
     }
 }
 
@@ -83,7 +111,7 @@ impl O {
     pub fn set(&mut self, nth: u8, state: bool) {
         assert!(nth < 5);
 
-        self.0 &= !(u5::new(0) << nth);
+        self.0 &= !(u5::new(1) << nth);
         self.0 |= u5::new(state.into()) << nth;
     }
 
@@ -99,6 +127,29 @@ impl O {
 
         self.0.value() >> nth & 1 != 0
     }
+
+    /// Set the nth-bit of this pin output.
+    ///
+    /// # Errors
+    ///
+    /// If the given bit is not within the range of `0..=4`, an error is
+    /// returned.
+    pub fn try_set(&mut self, nth: u8, state: bool) -> Result<(), Error> {
+        check(5, nth)?;
+        self.set(nth, state);
+        Ok(())
+    }
+
+    /// Check if the nth-bit of this pin output is enabled.
+    ///
+    /// # Errors
+    ///
+    /// If the given bit is not within the range of `0..=4`, an error is
+    /// returned.
+    pub fn try_get(&self, nth: u8) -> Result<bool, Error> {
+        check(5, nth)?;
+        Ok(self.get(nth))
+    }
 }
 
 /// The 4-bit pin input K\[1,2,4,8\].
@@ -132,7 +183,7 @@ impl K {
     pub fn set(&mut self, nth: u8, state: bool) {
         assert!(nth < 4);
 
-        self.0 &= !(u4::new(0) << nth);
+        self.0 &= !(u4::new(1) << nth);
         self.0 |= u4::new(state.into()) << nth;
     }
 
@@ -148,4 +199,65 @@ impl K {
 
         self.0.value() >> nth & 1 != 0
     }
+
+    /// Set the nth-bit of this pin input.
+    ///
+    /// # Errors
+    ///
+    /// If the given bit is not within the range of `0..=3`, an error is
+    /// returned.
+    pub fn try_set(&mut self, nth: u8, state: bool) -> Result<(), Error> {
+        check(4, nth)?;
+        self.set(nth, state);
+        Ok(())
+    }
+
+    /// Check if the nth-bit of this pin input is enabled.
+    ///
+    /// # Errors
+    ///
+    /// If the given bit is not within the range of `0..=3`, an error is
+    /// returned.
+    pub fn try_get(&self, nth: u8) -> Result<bool, Error> {
+        check(4, nth)?;
+        Ok(self.get(nth))
+    }
+}
+
+/// Check if the nth-bit exists on pins with the given amount of bits.
+fn check(pins: u8, nth: u8) -> Result<(), Error> {
+    if nth < pins {
+        Ok(())
+    } else {
+        Err(Error::PinOutOfRange { pin: nth, pins })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_and_clear() {
+        let mut o = O::new();
+        o.set(4, true);
+        o.set(1, true);
+        o.set(4, false);
+        assert_eq!(o.value().value(), 0b00010);
+
+        let mut k = K::new();
+        assert_eq!(k.try_set(3, true), Ok(()));
+        assert_eq!(k.try_get(3), Ok(true));
+        assert_eq!(k.try_set(3, false), Ok(()));
+        assert_eq!(k.value().value(), 0);
+
+        assert_eq!(
+            k.try_set(4, true),
+            Err(Error::PinOutOfRange { pin: 4, pins: 4 })
+        );
+        assert_eq!(
+            R::new().try_get(16),
+            Err(Error::PinOutOfRange { pin: 16, pins: 16 })
+        );
+    }
 }
@@ -2,7 +2,15 @@
 //!
 //! The very nature of the term PLA suggests something that is modifiable per-chip, however
 //! this PLA implementation is fine-tuned to execute the standard configuration of the
-//! TMS1100's PLA, which every (official) Microvision cartridge uses.
+//! TMS1100's PLA, which every (official) Microvision cartridge uses, and the standard
+//! configuration of the TMS1000's PLA, which is shared by the rest of the TMS1000 family.
+//!
+//! Cartridges with a non-standard micro-instruction PLA can supply their own table,
+//! see [`MicroPla`].
+
+use crate::snapshot::{self, Reader, State, Writer};
+
+use core::fmt;
 
 /// The standard set of PLA micro-instructions.
 pub mod instructions {
@@ -179,7 +187,7 @@ pub mod instructions {
 }
 
 use instructions::{
-    InstructionRef, IsValid, ATN, AUTA, AUTY, C8, CIN, CKM, CKN, CKP, FTN, MTP, NATN, NE, STO,
+    InstructionRef, IsValid, ATN, AUTA, AUTY, C8, CIN, CKM, CKN, CKP, FTN, MTN, MTP, NATN, NE, STO,
     STSL, YTP,
 };
 
@@ -245,6 +253,46 @@ impl Entry {
             _ => Self::EMPTY,
         }
     }
+
+    /// Return the PLA entry for the given opcode on the TMS1000.
+    ///
+    /// The TMS1000 uses a different opcode layout than the TMS1100, along with a few
+    /// unique instructions, e.g. `A8AAC` and `IA`.
+    #[must_use]
+    pub fn decode_tms1000(opcode: u8) -> Self {
+        match opcode {
+            0x01 | 0x05 | 0x06 => Self(CKP | ATN | C8 | AUTA),
+            0x02 => Self(YTP | ATN | NE | STSL),
+            0x03 => Self(STO),
+            0x04 => Self(STO | AUTA),
+            0x07 => Self(CKP | ATN | CIN | C8 | AUTA),
+            0x08 => Self(CKP | AUTA),
+            0x09 => Self(CKP | NE),
+            0x0e => Self(ATN | CIN | AUTA),
+            0x20 => Self(STO | YTP | CIN | AUTY),
+            0x21 => Self(MTP | AUTA),
+            0x22 => Self(MTP | AUTY),
+            0x23 => Self(YTP | AUTA),
+            0x24 => Self(ATN | AUTY),
+            0x25 => Self(ATN | MTP | C8 | AUTA),
+            0x26 => Self(MTP | NE),
+            0x27 => Self(MTP | NATN | CIN | C8 | AUTA),
+            0x28 => Self(MTP | CIN | C8 | AUTA),
+            0x29 => Self(MTP | NATN | CIN | C8),
+            0x2a => Self(MTP | FTN | C8 | AUTA),
+            0x2b => Self(YTP | CIN | C8 | AUTY),
+            0x2c => Self(YTP | FTN | C8 | AUTY),
+            0x2d => Self(NATN | CIN | C8 | AUTA),
+            0x2e => Self(MTP | STO | AUTA),
+            0x2f => Self(AUTA),
+            0x38..=0x3b => Self(CKP | CKN | MTP | NE),
+            0x40..=0x4f => Self(CKP | AUTY),
+            0x50..=0x5f => Self(YTP | CKN | NE),
+            0x60..=0x6f => Self(CKM | YTP | CIN | AUTY),
+            0x70..=0x7f => Self(CKP | NATN | CIN | C8),
+            _ => Self::EMPTY,
+        }
+    }
 }
 
 impl From<u16> for Entry {
@@ -253,7 +301,232 @@ impl From<u16> for Entry {
     }
 }
 
-/// A set of 12 fixed-instructions.
+/// An error which occurred while parsing a PLA dump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The dump declares an unexpected amount of inputs or outputs.
+    Unsupported {
+        /// The (1-based) line the declaration is on.
+        line: usize,
+    },
+    /// The dump contains a malformed line.
+    Syntax {
+        /// The (1-based) line the error is on.
+        line: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported { line } => {
+                write!(f, "line {line}: unexpected amount of pla inputs or outputs")
+            }
+            Self::Syntax { line } => write!(f, "line {line}: malformed pla term"),
+        }
+    }
+}
+
+/// A (loadable) micro-instruction decode PLA.
+///
+/// The micro-instruction PLA is a mask option of the TMS1000 family, while every
+/// official Microvision cartridge uses the standard configuration, see [`Entry::decode`],
+/// homebrew or otherwise unusual chips may use a different one.
+///
+/// Only the opcodes `0x00..=0x7f` are decoded using this PLA, the remaining opcodes
+/// are always decoded as [Fixed] instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MicroPla {
+    /// The micro-instruction entry of every opcode.
+    entries: [Entry; 0x80],
+}
+
+impl MicroPla {
+    /// The outputs of the PLA, in the order they appear within a MAME dump.
+    const OUTPUTS: [u16; 16] = [
+        STSL, AUTY, AUTA, CIN, C8, NE, CKN, FTN, MTN, NATN, ATN, MTP, YTP, CKM, CKP, STO,
+    ];
+
+    /// The outputs of the PLA which are active-low within a MAME dump.
+    const INVERTED: u16 = 0x3fc8;
+
+    /// Create a new micro-instruction PLA from the entries of every opcode.
+    #[must_use]
+    pub fn new(entries: [Entry; 0x80]) -> Self {
+        Self { entries }
+    }
+
+    /// Create the standard micro-instruction PLA of the TMS1100.
+    #[must_use]
+    pub fn tms1100() -> Self {
+        let mut entries = [Entry::EMPTY; 0x80];
+        for (opcode, entry) in (0..).zip(&mut entries) {
+            *entry = Entry::decode(opcode);
+        }
+
+        Self { entries }
+    }
+
+    /// Create the standard micro-instruction PLA of the TMS1000.
+    #[must_use]
+    pub fn tms1000() -> Self {
+        let mut entries = [Entry::EMPTY; 0x80];
+        for (opcode, entry) in (0..).zip(&mut entries) {
+            *entry = Entry::decode_tms1000(opcode);
+        }
+
+        Self { entries }
+    }
+
+    /// Parse a micro-instruction PLA from a MAME `mpla` dump.
+    ///
+    /// These dumps use the Berkeley PLA text format, declaring 8 inputs and 16 outputs.
+    /// Every term consists of the input pattern, the opcode with its most significant bit
+    /// first (`-` matching either value), followed by the outputs which are enabled when
+    /// the term matches, in the following order:
+    ///
+    /// `STSL AUTY AUTA CIN C8 NE CKN 15TN MTN NATN ATN MTP YTP CKM CKP STO`
+    ///
+    /// Like on the actual chip, the outputs `CIN`, `CKN`, `15TN`, `MTN`, `NATN`, `ATN`,
+    /// `MTP`, `YTP` and `CKM` are active-low, e.g. an opcode that matches no terms at all
+    /// enables these micro-instructions.
+    ///
+    /// # Errors
+    ///
+    /// If the dump contains a malformed term or declares a different amount of inputs or
+    /// outputs, an error is returned.
+    pub fn parse_mame(text: &str) -> Result<Self, Error> {
+        let mut masks = [0u16; 0x80];
+        parse(text, 8, 16, &mut masks)?;
+
+        let mut entries = [Entry::EMPTY; 0x80];
+        for (entry, mask) in entries.iter_mut().zip(masks) {
+            let mask = mask ^ Self::INVERTED;
+            for (bit, micro) in Self::OUTPUTS.iter().enumerate() {
+                if mask & (1 << bit) != 0 {
+                    entry.0 |= micro;
+                }
+            }
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Return the PLA entry for the given opcode.
+    ///
+    /// The opcodes `0x80..=0xff` are not decoded by the PLA, and always return an
+    /// empty entry.
+    #[must_use]
+    pub fn decode(&self, opcode: u8) -> Entry {
+        self.entries
+            .get(usize::from(opcode))
+            .copied()
+            .unwrap_or(Entry::EMPTY)
+    }
+}
+
+/// Parse a PLA dump in the Berkeley PLA text format.
+///
+/// Every term consists of the input pattern, with its most significant bit first (`-`
+/// matching either value), followed by the outputs which are enabled when the term
+/// matches, with the least significant bit first. The enabled outputs of every input
+/// value in `0..table.len()` are combined into the given table.
+///
+/// # Errors
+///
+/// If the dump contains a malformed term or declares a different amount of inputs or
+/// outputs, an error is returned.
+pub(crate) fn parse(
+    text: &str,
+    inputs: usize,
+    outputs: usize,
+    table: &mut [u16],
+) -> Result<(), Error> {
+    for (idx, line) in text.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = line.split('#').next().unwrap_or_default().trim();
+        let mut fields = line.split_whitespace();
+
+        let Some(first) = fields.next() else {
+            continue;
+        };
+
+        if let Some(directive) = first.strip_prefix('.') {
+            let expected = match directive {
+                "i" => inputs,
+                "o" => outputs,
+                "e" | "end" => break,
+                // Other directives, e.g. term counts or labels, are irrelevant.
+                _ => continue,
+            };
+
+            if fields.next().and_then(|val| val.parse::<usize>().ok()) != Some(expected) {
+                return Err(Error::Unsupported { line: line_no });
+            }
+            continue;
+        }
+
+        let (Some(terms), None) = (fields.next(), fields.next()) else {
+            return Err(Error::Syntax { line: line_no });
+        };
+        if first.len() != inputs || terms.len() != outputs {
+            return Err(Error::Syntax { line: line_no });
+        }
+
+        // The bits which must match, and their expected values.
+        let (mut care, mut value) = (0usize, 0usize);
+        for (bit, input) in (0..inputs).rev().zip(first.bytes()) {
+            match input {
+                b'0' => care |= 1 << bit,
+                b'1' => {
+                    care |= 1 << bit;
+                    value |= 1 << bit;
+                }
+                b'-' => {}
+                _ => return Err(Error::Syntax { line: line_no }),
+            }
+        }
+
+        let mut enabled = 0;
+        for (bit, output) in terms.bytes().enumerate() {
+            match output {
+                b'1' => enabled |= 1 << bit,
+                b'0' | b'~' => {}
+                _ => return Err(Error::Syntax { line: line_no }),
+            }
+        }
+
+        for (input, mask) in table.iter_mut().enumerate() {
+            if input & care == value {
+                *mask |= enabled;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl State for MicroPla {
+    const SIZE: usize = 0x100;
+
+    fn save(&self, w: &mut Writer) {
+        for entry in &self.entries {
+            w.u16(entry.0);
+        }
+    }
+
+    fn load(&mut self, r: &mut Reader) -> Result<(), snapshot::Error> {
+        for entry in &mut self.entries {
+            entry.0 = r.u16()?;
+        }
+        Ok(())
+    }
+}
+
+/// A set of 13 fixed-instructions.
+///
+/// Both the TMS1100 and TMS1000 use 12 of these instructions, `COMC` only exists on
+/// the TMS1100 and `CLO` only exists on the TMS1000.
 ///
 /// Unlike most instructions with can be modified via the PLA, certain instructions
 /// like branch/call have fixed (non-programmable) logic decoders. However, this
@@ -339,6 +612,13 @@ pub enum Fixed {
     /// This fixed-instruction transfers the data from the accumulator and status latch
     /// into the `O` pin output.
     Tdo,
+    /// The fixed-instruction `CLO`.
+    ///
+    /// # Logic
+    ///
+    /// This fixed-instruction clears the `O` pin output, it only exists on the TMS1000
+    /// instruction set.
+    Clo,
 }
 
 impl Fixed {
@@ -366,12 +646,103 @@ impl Fixed {
 
         Some(fixed)
     }
+
+    /// Return the fixed-instruction for the given opcode on the TMS1000.
+    ///
+    /// In the case that the given opcode does not represent one of the 12 fixed-instructions
+    /// of the TMS1000, a [None] value will be returned.
+    #[must_use]
+    pub fn decode_tms1000(opcode: u8) -> Option<Self> {
+        let fixed = match opcode {
+            0x00 => Self::Comx,
+            0x0a => Self::Tdo,
+            0x0b => Self::Clo,
+            0x0c => Self::Rstr,
+            0x0d => Self::Setr,
+            0x0f => Self::Retn,
+            0x30..=0x33 => Self::Sbit,
+            0x34..=0x37 => Self::Rbit,
+            0x10..=0x1f => Self::Ldp,
+            0x3c..=0x3f => Self::Ldx,
+            0x80..=0xbf => Self::Br,
+            0xc0..=0xff => Self::Call,
+            _ => return None,
+        };
+
+        Some(fixed)
+    }
+
+    /// Return the fixed-instruction with the given index.
+    ///
+    /// This is the inverse of casting a fixed-instruction to an integer, e.g.
+    /// `fixed as u8`, and returns [None] if the index is not within the range
+    /// of `0..=12`.
+    #[must_use]
+    pub fn from_index(idx: u8) -> Option<Self> {
+        let fixed = match idx {
+            0 => Self::Br,
+            1 => Self::Call,
+            2 => Self::Retn,
+            3 => Self::Comc,
+            4 => Self::Comx,
+            5 => Self::Ldp,
+            6 => Self::Ldx,
+            7 => Self::Rbit,
+            8 => Self::Sbit,
+            9 => Self::Rstr,
+            10 => Self::Setr,
+            11 => Self::Tdo,
+            12 => Self::Clo,
+            _ => return None,
+        };
+
+        Some(fixed)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn builtin_micro_pla() {
+        let pla = MicroPla::tms1100();
+        for opcode in 0..=u8::MAX {
+            assert_eq!(pla.decode(opcode), Entry::decode(opcode));
+        }
+    }
+
+    #[test]
+    fn parse_mame_micro_pla() {
+        let dump = "\
+            # A PLA decoding TMA, TCY and nothing else.
+            .i 8
+            .o 16
+            .p 2
+            00100001 0011001111101100 # TMA
+            0100---- 0101001111111110 # TCY
+            .e
+        ";
+        let pla = MicroPla::parse_mame(dump).unwrap();
+        assert_eq!(pla.decode(0x21), Entry::decode(0x21));
+        for opcode in 0x40..=0x4f {
+            assert_eq!(pla.decode(opcode), Entry::decode(opcode));
+        }
+        assert_eq!(
+            pla.decode(0x00),
+            Entry::from(CIN | CKN | FTN | MTN | NATN | ATN | MTP | YTP | CKM)
+        );
+
+        assert_eq!(
+            MicroPla::parse_mame(".i 9"),
+            Err(Error::Unsupported { line: 1 })
+        );
+        assert_eq!(
+            MicroPla::parse_mame("0010000x 0000000000000000"),
+            Err(Error::Syntax { line: 1 })
+        );
+    }
+
     /// Define a test function for instruction decoding.
     ///
     /// This verifies if the correct micro/fixed-instructions are enabled for a given opcode.
@@ -440,4 +811,77 @@ mod tests {
     opcode!(cla, 0x7f, CKP | CIN | C8 | AUTA, None);
     opcode_range!(br, 0x80..=0xbf, Entry::EMPTY, Some(Fixed::Br));
     opcode_range!(call, 0xc0..=0xff, Entry::EMPTY, Some(Fixed::Call));
+
+    /// Instruction decoding tests for the TMS1000 instruction set.
+    mod tms1000 {
+        use super::super::*;
+
+        /// Define a test function for TMS1000 instruction decoding.
+        macro_rules! opcode {
+            ($name:ident, $op:literal, $entry:expr, $fixed:expr) => {
+                #[test]
+                fn $name() {
+                    assert_eq!(Entry::decode_tms1000($op), Entry::from($entry));
+                    assert_eq!(Fixed::decode_tms1000($op), $fixed);
+                }
+            };
+        }
+
+        /// Define a test function for TMS1000 instruction decoding over an opcode range.
+        macro_rules! opcode_range {
+            ($name:ident, $range:expr, $entry:expr, $fixed:expr) => {
+                #[test]
+                fn $name() {
+                    for op in $range {
+                        assert_eq!(Entry::decode_tms1000(op), Entry::from($entry));
+                        assert_eq!(Fixed::decode_tms1000(op), $fixed);
+                    }
+                }
+            };
+        }
+
+        opcode!(comx, 0x00, Entry::EMPTY, Some(Fixed::Comx));
+        opcode!(a8aac, 0x01, CKP | ATN | C8 | AUTA, None);
+        opcode!(ynea, 0x02, YTP | ATN | NE | STSL, None);
+        opcode!(tam, 0x03, STO, None);
+        opcode!(tamza, 0x04, STO | AUTA, None);
+        opcode!(a10aac, 0x05, CKP | ATN | C8 | AUTA, None);
+        opcode!(a6aac, 0x06, CKP | ATN | C8 | AUTA, None);
+        opcode!(dan, 0x07, CKP | ATN | CIN | C8 | AUTA, None);
+        opcode!(tka, 0x08, CKP | AUTA, None);
+        opcode!(knez, 0x09, CKP | NE, None);
+        opcode!(tdo, 0x0a, Entry::EMPTY, Some(Fixed::Tdo));
+        opcode!(clo, 0x0b, Entry::EMPTY, Some(Fixed::Clo));
+        opcode!(rstr, 0x0c, Entry::EMPTY, Some(Fixed::Rstr));
+        opcode!(setr, 0x0d, Entry::EMPTY, Some(Fixed::Setr));
+        opcode!(ia, 0x0e, ATN | CIN | AUTA, None);
+        opcode!(retn, 0x0f, Entry::EMPTY, Some(Fixed::Retn));
+        opcode_range!(ldp, 0x10..=0x1f, Entry::EMPTY, Some(Fixed::Ldp));
+        opcode!(tamiy, 0x20, STO | YTP | CIN | AUTY, None);
+        opcode!(tma, 0x21, MTP | AUTA, None);
+        opcode!(tmy, 0x22, MTP | AUTY, None);
+        opcode!(tya, 0x23, YTP | AUTA, None);
+        opcode!(tay, 0x24, ATN | AUTY, None);
+        opcode!(amaac, 0x25, ATN | MTP | C8 | AUTA, None);
+        opcode!(mnez, 0x26, MTP | NE, None);
+        opcode!(saman, 0x27, MTP | NATN | CIN | C8 | AUTA, None);
+        opcode!(imac, 0x28, MTP | CIN | C8 | AUTA, None);
+        opcode!(alem, 0x29, MTP | NATN | CIN | C8, None);
+        opcode!(dman, 0x2a, MTP | FTN | C8 | AUTA, None);
+        opcode!(iyc, 0x2b, YTP | CIN | C8 | AUTY, None);
+        opcode!(r#dyn, 0x2c, YTP | FTN | C8 | AUTY, None);
+        opcode!(cpaiz, 0x2d, NATN | CIN | C8 | AUTA, None);
+        opcode!(xma, 0x2e, MTP | STO | AUTA, None);
+        opcode!(cla, 0x2f, AUTA, None);
+        opcode_range!(sbit, 0x30..=0x33, Entry::EMPTY, Some(Fixed::Sbit));
+        opcode_range!(rbit, 0x34..=0x37, Entry::EMPTY, Some(Fixed::Rbit));
+        opcode_range!(tbit1, 0x38..=0x3b, CKP | CKN | MTP | NE, None);
+        opcode_range!(ldx, 0x3c..=0x3f, Entry::EMPTY, Some(Fixed::Ldx));
+        opcode_range!(tcy, 0x40..=0x4f, CKP | AUTY, None);
+        opcode_range!(ynec, 0x50..=0x5f, YTP | CKN | NE, None);
+        opcode_range!(tcmiy, 0x60..=0x6f, CKM | YTP | CIN | AUTY, None);
+        opcode_range!(alec, 0x70..=0x7f, CKP | NATN | CIN | C8, None);
+        opcode_range!(br, 0x80..=0xbf, Entry::EMPTY, Some(Fixed::Br));
+        opcode_range!(call, 0xc0..=0xff, Entry::EMPTY, Some(Fixed::Call));
+    }
 }
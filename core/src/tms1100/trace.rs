@@ -0,0 +1,187 @@
+//! An execution tracer for the TMS1000 family of micro-processors.
+//!
+//! # Format
+//!
+//! A [`Record`] is formatted as a single line, the first column (up to the tab) is
+//! formatted like the trace output of MAME's `tms1k` devices, e.g. `0C3: TCY      3`,
+//! so that both traces can be compared line by line, e.g. using `cut -f1`. The second
+//! column holds the state of the micro-processor after the instruction executed,
+//! e.g. (with the tab shown as spaces):
+//!
+//! ```text
+//! 007: TAM          A=1 X=0 Y=4 PC=0F SR=00 PA=0 PB=0 CA=0 CB=0 CS=0 SL=0 R=0000 O=00 M[0:4]=1
+//! ```
+
+use super::{
+    dasm::{Instruction, Operand},
+    mem::{RamAddr, RomAddr},
+    Registers,
+};
+
+use core::fmt;
+
+use arbitrary_int::{u4, u5};
+
+/// A hook receiving a record of every executed instruction.
+///
+/// # Note
+///
+/// Tracing is disabled by using the unit type `()` as tracer, which discards all
+/// records. As [`ENABLED`](Self::ENABLED) is a constant, records are then never
+/// even created, so tracing does not cost anything when disabled.
+pub trait Tracer {
+    /// A flag determining if this tracer receives records.
+    const ENABLED: bool = true;
+
+    /// Receive the record of an executed instruction.
+    fn trace(&mut self, record: &Record);
+}
+
+impl Tracer for () {
+    const ENABLED: bool = false;
+
+    fn trace(&mut self, _: &Record) {}
+}
+
+/// A record of a single executed instruction.
+#[derive(Debug, Clone, Copy)]
+pub struct Record {
+    /// The disassembled instruction.
+    pub inst: Instruction,
+    /// The data registers/latches after the instruction executed.
+    pub regs: Registers,
+    /// The `SL` status latch after the instruction executed.
+    pub status: bool,
+    /// The `R` output pins after the instruction executed.
+    pub r: u16,
+    /// The `O` output pins after the instruction executed.
+    pub o: u5,
+    /// The RAM address and value written by the instruction, if any.
+    pub ram: Option<(RamAddr, u4)>,
+}
+
+impl Record {
+    /// Return the ROM address of the instruction.
+    #[must_use]
+    pub fn addr(&self) -> RomAddr {
+        self.inst.addr
+    }
+}
+
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (inst, regs) = (&self.inst, &self.regs);
+
+        write!(f, "{:03X}: {:<8} ", inst.addr.full().value(), inst.mnemonic)?;
+        match inst.operand {
+            Operand::None => Ok(()),
+            // MAME only shows the 6-bit address of the target.
+            Operand::Target(addr) => write!(f, "${:02X}", addr.addr().value()),
+            operand => write!(f, "{operand}"),
+        }?;
+
+        write!(
+            f,
+            "\tA={:X} X={:X} Y={:X} PC={:02X} SR={:02X} PA={:X} PB={:X} CA={:X} CB={:X} CS={:X}",
+            regs.a.value(),
+            regs.x.value(),
+            regs.y.value(),
+            regs.pc.value(),
+            regs.sr.value(),
+            regs.pa.value(),
+            regs.pb.value(),
+            regs.ca.value(),
+            regs.cb.value(),
+            regs.cs.value(),
+        )?;
+        write!(
+            f,
+            " SL={} R={:04X} O={:02X}",
+            u8::from(self.status),
+            self.r,
+            self.o.value()
+        )?;
+
+        if let Some((addr, val)) = self.ram {
+            write!(f, " M[{addr}]={:X}", val.value())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{testing, Console, Interface};
+
+    use core::fmt::Write;
+
+    /// A tracer recording the first few instructions.
+    struct Recorder {
+        records: [Option<Record>; 8],
+        len: usize,
+    }
+
+    impl Tracer for Recorder {
+        fn trace(&mut self, record: &Record) {
+            if let Some(slot) = self.records.get_mut(self.len) {
+                *slot = Some(*record);
+            }
+            self.len += 1;
+        }
+    }
+
+    /// A fixed-size buffer for a single formatted record.
+    struct Line {
+        buf: [u8; 128],
+        len: usize,
+    }
+
+    impl fmt::Write for Line {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            let end = self.len + s.len();
+            self.buf
+                .get_mut(self.len..end)
+                .ok_or(fmt::Error)?
+                .copy_from_slice(s.as_bytes());
+            self.len = end;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn records() {
+        let (mut console, mut cart) = (Console::new(), testing::cartridge());
+        let mut recorder = Recorder {
+            records: [None; 8],
+            len: 0,
+        };
+
+        let (mut a, mut b) = (testing::Nothing, testing::Nothing);
+        for _ in 0..6 * 8 {
+            let hardware = Interface {
+                display: &mut a,
+                buzzer: &mut b,
+                keypad: &testing::Nothing,
+                rotary: &testing::Nothing,
+            };
+            console.clock_traced(&mut cart, hardware, &mut recorder);
+        }
+
+        // The idle instruction after the reset is not traced.
+        assert_eq!(recorder.len, 7);
+        let records = recorder.records;
+        assert_eq!(records[0].unwrap().inst.mnemonic, "TCY");
+        assert_eq!(records[6].unwrap().inst.mnemonic, "BR");
+
+        let mut line = Line {
+            buf: [0; 128],
+            len: 0,
+        };
+        write!(line, "{}", records[3].unwrap()).unwrap();
+        assert_eq!(
+            core::str::from_utf8(&line.buf[..line.len]).unwrap(),
+            "007: TAM      \tA=1 X=0 Y=4 PC=0F SR=00 PA=0 PB=0 CA=0 CB=0 CS=0 SL=0 R=0000 O=00 M[0:4]=1"
+        );
+    }
+}
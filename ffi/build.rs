@@ -0,0 +1,18 @@
+//! Generate the C header from the exported items into the output directory, where
+//! `tests/header.rs` checks that the checked-in `include/milton.h` is up to date.
+
+use std::env;
+
+fn main() {
+    let dir = env::var("CARGO_MANIFEST_DIR").expect("cargo sets the manifest directory");
+    let out = env::var("OUT_DIR").expect("cargo sets the output directory");
+
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let config = cbindgen::Config::from_file(format!("{dir}/cbindgen.toml"))
+        .expect("the cbindgen configuration is valid");
+    cbindgen::generate_with_config(&dir, config)
+        .expect("the header can be generated")
+        .write_to_file(format!("{out}/milton.h"));
+}
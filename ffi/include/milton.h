@@ -0,0 +1,223 @@
+/* The C API of the Milton emulator, see the documentation of the milton_ffi crate. */
+
+#ifndef MILTON_H
+#define MILTON_H
+
+/* This file is generated by cbindgen from ffi/src/lib.rs, do not edit it by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// A TMS1100 micro-processor.
+#define MILTON_CPU_TMS1100 0
+
+// An Intel 8021 micro-controller.
+#define MILTON_CPU_I8021 1
+
+// A TMS1000 micro-processor.
+#define MILTON_CPU_TMS1000 2
+
+// A TMS1070 micro-processor.
+#define MILTON_CPU_TMS1070 3
+
+// A TMS1200 micro-processor.
+#define MILTON_CPU_TMS1200 4
+
+// A TMS1270 micro-processor.
+#define MILTON_CPU_TMS1270 5
+
+// A TMS1300 micro-processor.
+#define MILTON_CPU_TMS1300 6
+
+// A TMS1370 micro-processor.
+#define MILTON_CPU_TMS1370 7
+
+// The O output is simply forwarded through to the LCD driver.
+#define MILTON_PLA_NORMAL 0
+
+// The O output is reversed then sent to the LCD driver.
+#define MILTON_PLA_REVERSED 1
+
+// An emulated console, with an inserted cartridge.
+//
+// This is opaque to C, and only ever used behind a pointer.
+typedef struct MiltonConsole MiltonConsole;
+
+// The settings of a cartridge.
+typedef struct MiltonSettings {
+  // The micro-processor, one of the `MILTON_CPU_*` constants.
+  uint8_t cpu;
+  // The output PLA, one of the `MILTON_PLA_*` constants.
+  uint8_t output_pla;
+  // Whether the rotary controller is enabled.
+  bool rotary;
+  // The value to offset the end time of a rotary charge by.
+  uint32_t charge_offset;
+  // The value to scale the end time of a rotary charge by.
+  uint32_t charge_scale;
+} MiltonSettings;
+
+// The hardware interface of a console, as callbacks.
+//
+// Every callback receives the `user` pointer, and may be null.
+//
+// Callbacks are called while the console is in use, so they must not call any
+// `milton_console_*` function with the same console, which is undefined
+// behaviour. Other consoles may be used.
+typedef struct MiltonCallbacks {
+  // The pointer passed to every callback.
+  void *user;
+  // Enable the pixel at the given X and Y screen coordinates, see
+  // [`display::Api::enable_pixel`].
+  void (*enable_pixel)(void *user, size_t x, size_t y);
+  // End the current frame, see [`display::Api::end_frame`].
+  void (*end_frame)(void *user);
+  // Play a sound with the given pitch (in Hz), see [`buzzer::Api::enable`].
+  void (*buzzer_enable)(void *user, size_t pitch);
+  // Stop playing sound, see [`buzzer::Api::disable`].
+  void (*buzzer_disable)(void *user);
+  // Return the status of the key in the given column (`0..=2`) and row
+  // (`0..=3`), see [`keypad::Api::get`].
+  bool (*key)(void *user, uint8_t col, uint8_t row);
+  // Return the turn of the rotary controller, from `0` to `100` percent, see
+  // [`rotary::Api::turn`].
+  uint8_t (*rotary)(void *user);
+} MiltonCallbacks;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Return the default settings of a cartridge, i.e. a TMS1100 with the normal
+// output PLA and no rotary controller.
+struct MiltonSettings milton_settings_default(void);
+
+// Create a console with an inserted cartridge, returning null if the cartridge
+// or settings are invalid.
+//
+// If the settings are null, the cartridge is either a container holding its own
+// settings, or a ROM dump of a known game, or a ROM dump using the default
+// settings.
+//
+// # Safety
+//
+// `data` must be valid for reads of `len` bytes, and `settings` must be null or
+// valid for reads. The console must be freed using `milton_console_free`.
+struct MiltonConsole *milton_console_new(const uint8_t *data,
+                                         size_t len,
+                                         const struct MiltonSettings *settings);
+
+// Free a console.
+//
+// # Safety
+//
+// The console must be null, or a console created by `milton_console_new` which
+// has not been freed.
+void milton_console_free(struct MiltonConsole *console);
+
+// Set the callbacks of a console, or remove them if the callbacks are null.
+//
+// # Safety
+//
+// The console must be null or valid, and the callbacks must be null or valid for
+// reads. Every callback must be safe to call with the `user` pointer until the
+// callbacks are replaced or the console is freed, and must not call any
+// `milton_console_*` function with this console, see [`MiltonCallbacks`].
+void milton_console_set_callbacks(struct MiltonConsole *console,
+                                  const struct MiltonCallbacks *callbacks);
+
+// Reset a console, as if the cartridge was reinserted.
+//
+// # Safety
+//
+// The console must be null or valid.
+void milton_console_reset(struct MiltonConsole *console);
+
+// Run a single clock of a console, which ends the frame every 1/60th of a second.
+//
+// # Safety
+//
+// The console must be null or valid.
+void milton_console_clock(struct MiltonConsole *console);
+
+// Run a console until the end of the current frame.
+//
+// # Safety
+//
+// The console must be null or valid.
+void milton_console_run_frame(struct MiltonConsole *console);
+
+// Press or release the key in the given column (`0..=2`) and row (`0..=3`),
+// returning `false` if the key does not exist.
+//
+// This is ignored while a key callback is set.
+//
+// # Safety
+//
+// The console must be null or valid.
+bool milton_console_set_key(struct MiltonConsole *console, uint8_t col, uint8_t row, bool pressed);
+
+// Turn the rotary controller, from `0` to `100` percent, returning `false` if the
+// turn is out of range.
+//
+// This is ignored while a rotary callback is set.
+//
+// # Safety
+//
+// The console must be null or valid.
+bool milton_console_set_rotary(struct MiltonConsole *console, uint8_t turn);
+
+// Write the 16 rows of the last complete frame, where bit N of a row is the pixel
+// in column N.
+//
+// # Safety
+//
+// The console must be null or valid, and `rows` must be valid for writes of 16
+// values.
+void milton_console_pixels(struct MiltonConsole *console, uint16_t *rows);
+
+// Write the brightness of the 256 pixels of the LCD panel, row by row, from `0`
+// (off) to `255` (fully on), which includes the slow decay of every pixel.
+//
+// # Safety
+//
+// The console must be null or valid, and `out` must be valid for writes of 256
+// values.
+void milton_console_brightness(struct MiltonConsole *console, uint8_t *out);
+
+// Return the pitch of the buzzer (in Hz) during the last frame, or zero if it was
+// silent.
+//
+// # Safety
+//
+// The console must be null or valid.
+uint32_t milton_console_pitch(struct MiltonConsole *console);
+
+// Return the amount of (mono) audio samples which can be pulled at the given
+// sample rate.
+//
+// # Safety
+//
+// The console must be null or valid.
+size_t milton_console_audio_available(struct MiltonConsole *console, uint32_t rate);
+
+// Pull (mono) audio samples at the given sample rate, returning the amount of
+// samples written.
+//
+// Samples should be pulled at least once every frame, see
+// [`Pcm`](milton_core::buzzer::Pcm).
+//
+// # Safety
+//
+// The console must be null or valid, and `out` must be valid for writes of `len`
+// samples.
+size_t milton_console_audio(struct MiltonConsole *console, uint32_t rate, int16_t *out, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* MILTON_H */
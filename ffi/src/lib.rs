@@ -0,0 +1,602 @@
+//! A stable C ABI for embedding the Milton emulator, declared by the generated
+//! `include/milton.h` header.
+//!
+//! # Usage
+//!
+//! A console is created with an inserted cartridge by `milton_console_new`, and
+//! destroyed by `milton_console_free`. It is then either clocked one clock at a
+//! time, or run a frame at a time, while input is pushed and the pixels and audio
+//! are pulled:
+//!
+//! ```c
+//! MiltonSettings settings = milton_settings_default();
+//! MiltonConsole *console = milton_console_new(rom, rom_len, &settings);
+//!
+//! milton_console_set_key(console, 1, 3, true);
+//! milton_console_run_frame(console);
+//!
+//! uint16_t rows[16];
+//! milton_console_pixels(console, rows);
+//!
+//! milton_console_free(console);
+//! ```
+//!
+//! Every function taking a console does nothing (or returns zero/false) if the
+//! console is null.
+//!
+//! # Callbacks
+//!
+//! The hardware interface of the console, i.e. the display, buzzer, keypad and
+//! rotary controller APIs, can also be supplied as [`MiltonCallbacks`]. Output
+//! callbacks are called in addition to updating the pulled state, input callbacks
+//! replace the pushed input.
+//!
+//! Callbacks are called while the console is borrowed, so they must not call any
+//! `milton_console_*` function with the same console.
+
+use std::{ffi::c_void, ptr, slice};
+
+use milton_core::{
+    buzzer,
+    cartridge::{
+        container::{self, Container},
+        settings::{ChargeInfo, CpuType, OutputPla, Settings},
+        Cartridge,
+    },
+    common::{Interface, FRAME_CLOCKS},
+    display::{self, Framebuffer},
+    keypad::{self, Key},
+    rotary::{self, Percentage},
+    Console,
+};
+
+/// A TMS1100 micro-processor.
+pub const MILTON_CPU_TMS1100: u8 = 0;
+/// An Intel 8021 micro-controller.
+pub const MILTON_CPU_I8021: u8 = 1;
+/// A TMS1000 micro-processor.
+pub const MILTON_CPU_TMS1000: u8 = 2;
+/// A TMS1070 micro-processor.
+pub const MILTON_CPU_TMS1070: u8 = 3;
+/// A TMS1200 micro-processor.
+pub const MILTON_CPU_TMS1200: u8 = 4;
+/// A TMS1270 micro-processor.
+pub const MILTON_CPU_TMS1270: u8 = 5;
+/// A TMS1300 micro-processor.
+pub const MILTON_CPU_TMS1300: u8 = 6;
+/// A TMS1370 micro-processor.
+pub const MILTON_CPU_TMS1370: u8 = 7;
+
+/// The O output is simply forwarded through to the LCD driver.
+pub const MILTON_PLA_NORMAL: u8 = 0;
+/// The O output is reversed then sent to the LCD driver.
+pub const MILTON_PLA_REVERSED: u8 = 1;
+
+/// The settings of a cartridge.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MiltonSettings {
+    /// The micro-processor, one of the `MILTON_CPU_*` constants.
+    pub cpu: u8,
+    /// The output PLA, one of the `MILTON_PLA_*` constants.
+    pub output_pla: u8,
+    /// Whether the rotary controller is enabled.
+    pub rotary: bool,
+    /// The value to offset the end time of a rotary charge by.
+    pub charge_offset: u32,
+    /// The value to scale the end time of a rotary charge by.
+    pub charge_scale: u32,
+}
+
+impl MiltonSettings {
+    /// Convert these settings, returning `None` if any setting is invalid.
+    fn settings(self) -> Option<Settings> {
+        let output_pla = match self.output_pla {
+            MILTON_PLA_NORMAL => OutputPla::NORMAL,
+            MILTON_PLA_REVERSED => OutputPla::REVERSED,
+            _ => return None,
+        };
+
+        Some(Settings {
+            cpu: CpuType::from_index(self.cpu)?,
+            charge_info: ChargeInfo {
+                offset: usize::try_from(self.charge_offset).ok()?,
+                scale: usize::try_from(self.charge_scale).ok()?,
+            },
+            output_pla,
+            micro_pla: None,
+            rotary_enabled: self.rotary,
+        })
+    }
+}
+
+/// The hardware interface of a console, as callbacks.
+///
+/// Every callback receives the `user` pointer, and may be null.
+///
+/// Callbacks are called while the console is in use, so they must not call any
+/// `milton_console_*` function with the same console, which is undefined
+/// behaviour. Other consoles may be used.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct MiltonCallbacks {
+    /// The pointer passed to every callback.
+    pub user: *mut c_void,
+    /// Enable the pixel at the given X and Y screen coordinates, see
+    /// [`display::Api::enable_pixel`].
+    pub enable_pixel: Option<unsafe extern "C" fn(user: *mut c_void, x: usize, y: usize)>,
+    /// End the current frame, see [`display::Api::end_frame`].
+    pub end_frame: Option<unsafe extern "C" fn(user: *mut c_void)>,
+    /// Play a sound with the given pitch (in Hz), see [`buzzer::Api::enable`].
+    pub buzzer_enable: Option<unsafe extern "C" fn(user: *mut c_void, pitch: usize)>,
+    /// Stop playing sound, see [`buzzer::Api::disable`].
+    pub buzzer_disable: Option<unsafe extern "C" fn(user: *mut c_void)>,
+    /// Return the status of the key in the given column (`0..=2`) and row
+    /// (`0..=3`), see [`keypad::Api::get`].
+    pub key: Option<unsafe extern "C" fn(user: *mut c_void, col: u8, row: u8) -> bool>,
+    /// Return the turn of the rotary controller, from `0` to `100` percent, see
+    /// [`rotary::Api::turn`].
+    pub rotary: Option<unsafe extern "C" fn(user: *mut c_void) -> u8>,
+}
+
+impl MiltonCallbacks {
+    /// No callbacks at all.
+    const NONE: Self = Self {
+        user: ptr::null_mut(),
+        enable_pixel: None,
+        end_frame: None,
+        buzzer_enable: None,
+        buzzer_disable: None,
+        key: None,
+        rotary: None,
+    };
+}
+
+/// The display, which keeps the pixels of the last complete frame.
+struct Screen {
+    framebuffer: Framebuffer,
+    callbacks: MiltonCallbacks,
+}
+
+impl display::Api for Screen {
+    fn enable_pixel(&mut self, x: usize, y: usize) {
+        self.framebuffer.enable_pixel(x, y);
+        if let Some(enable_pixel) = self.callbacks.enable_pixel {
+            // SAFETY: The callbacks are valid, see `milton_console_set_callbacks`.
+            unsafe { enable_pixel(self.callbacks.user, x, y) };
+        }
+    }
+
+    fn end_frame(&mut self) {
+        self.framebuffer.end_frame();
+        if let Some(end_frame) = self.callbacks.end_frame {
+            // SAFETY: The callbacks are valid, see `milton_console_set_callbacks`.
+            unsafe { end_frame(self.callbacks.user) };
+        }
+    }
+}
+
+/// The buzzer, which keeps the pitch of the last frame.
+struct Speaker {
+    pitch: Option<usize>,
+    callbacks: MiltonCallbacks,
+}
+
+impl buzzer::Api for Speaker {
+    fn enable(&mut self, pitch: usize) {
+        self.pitch = Some(pitch);
+        if let Some(enable) = self.callbacks.buzzer_enable {
+            // SAFETY: The callbacks are valid, see `milton_console_set_callbacks`.
+            unsafe { enable(self.callbacks.user, pitch) };
+        }
+    }
+
+    fn disable(&mut self) {
+        self.pitch = None;
+        if let Some(disable) = self.callbacks.buzzer_disable {
+            // SAFETY: The callbacks are valid, see `milton_console_set_callbacks`.
+            unsafe { disable(self.callbacks.user) };
+        }
+    }
+}
+
+/// The keypad and rotary controller, either pushed or supplied by callbacks.
+struct Input {
+    /// The state of every key, in the order of [`Key::ALL`].
+    keys: [bool; 12],
+    rotary: Percentage,
+    callbacks: MiltonCallbacks,
+}
+
+impl keypad::Api for Input {
+    fn get(&self, key: Key) -> bool {
+        let (row, col) = key.pos();
+        let Some(get) = self.callbacks.key else {
+            return self.keys[key.index()];
+        };
+
+        // Both offsets are at most 3.
+        let (col, row) = (
+            u8::try_from(col).unwrap_or(0),
+            u8::try_from(row).unwrap_or(0),
+        );
+        // SAFETY: The callbacks are valid, see `milton_console_set_callbacks`.
+        unsafe { get(self.callbacks.user, col, row) }
+    }
+}
+
+impl rotary::Api for Input {
+    fn turn(&self) -> Percentage {
+        let Some(turn) = self.callbacks.rotary else {
+            return self.rotary;
+        };
+
+        // SAFETY: The callbacks are valid, see `milton_console_set_callbacks`.
+        let turn = unsafe { turn(self.callbacks.user) };
+        Percentage::new(usize::from(turn.min(100)))
+    }
+}
+
+/// An emulated console, with an inserted cartridge.
+///
+/// This is opaque to C, and only ever used behind a pointer.
+pub struct MiltonConsole {
+    console: Console,
+    cart: Cartridge,
+    screen: Screen,
+    speaker: Speaker,
+    input: Input,
+    /// The amount of clocks run within the current frame.
+    clocks: usize,
+}
+
+impl MiltonConsole {
+    /// Run a single clock, synchronizing the console at the end of every frame.
+    fn clock(&mut self) {
+        let hardware = Interface {
+            display: &mut self.screen,
+            buzzer: &mut self.speaker,
+            keypad: &self.input,
+            rotary: &self.input,
+        };
+        self.console.clock(&mut self.cart, hardware);
+
+        self.clocks += 1;
+        if self.clocks == FRAME_CLOCKS {
+            let hardware = Interface {
+                display: &mut self.screen,
+                buzzer: &mut self.speaker,
+                keypad: &self.input,
+                rotary: &self.input,
+            };
+            self.console.sync(hardware);
+            self.clocks = 0;
+        }
+    }
+}
+
+/// Return the console behind a pointer, or `None` if it is null.
+///
+/// # Safety
+///
+/// The pointer must be null, or a console created by `milton_console_new` which
+/// has not been freed, and is not in use elsewhere.
+unsafe fn console<'a>(console: *mut MiltonConsole) -> Option<&'a mut MiltonConsole> {
+    // SAFETY: The caller guarantees that the pointer is either null or valid.
+    unsafe { console.as_mut() }
+}
+
+/// Return the default settings of a cartridge, i.e. a TMS1100 with the normal
+/// output PLA and no rotary controller.
+#[no_mangle]
+pub extern "C" fn milton_settings_default() -> MiltonSettings {
+    let settings = Settings::default();
+    MiltonSettings {
+        cpu: settings.cpu.index(),
+        output_pla: MILTON_PLA_NORMAL,
+        rotary: settings.rotary_enabled,
+        charge_offset: u32::try_from(settings.charge_info.offset).unwrap_or(u32::MAX),
+        charge_scale: u32::try_from(settings.charge_info.scale).unwrap_or(u32::MAX),
+    }
+}
+
+/// Create a console with an inserted cartridge, returning null if the cartridge
+/// or settings are invalid.
+///
+/// If the settings are null, the cartridge is either a container holding its own
+/// settings, or a ROM dump of a known game, or a ROM dump using the default
+/// settings.
+///
+/// # Safety
+///
+/// `data` must be valid for reads of `len` bytes, and `settings` must be null or
+/// valid for reads. The console must be freed using `milton_console_free`.
+#[no_mangle]
+pub unsafe extern "C" fn milton_console_new(
+    data: *const u8,
+    len: usize,
+    settings: *const MiltonSettings,
+) -> *mut MiltonConsole {
+    if data.is_null() {
+        return ptr::null_mut();
+    }
+
+    // SAFETY: The caller guarantees that the data is valid for reads of `len` bytes.
+    let data = unsafe { slice::from_raw_parts(data, len) };
+    // SAFETY: The caller guarantees that the settings are either null or valid.
+    let settings = unsafe { settings.as_ref() };
+
+    let cart = match settings {
+        Some(settings) => settings
+            .settings()
+            .and_then(|settings| Cartridge::try_new(data, settings).ok()),
+        None if data.starts_with(&container::MAGIC) => Container::parse(data)
+            .ok()
+            .and_then(|container| container.cartridge().ok()),
+        None => Cartridge::from_known_rom(data)
+            .or_else(|_| Cartridge::try_new(data, Settings::default()))
+            .ok(),
+    };
+    let Some(cart) = cart else {
+        return ptr::null_mut();
+    };
+
+    Box::into_raw(Box::new(MiltonConsole {
+        console: Console::new(),
+        cart,
+        screen: Screen {
+            framebuffer: Framebuffer::new(),
+            callbacks: MiltonCallbacks::NONE,
+        },
+        speaker: Speaker {
+            pitch: None,
+            callbacks: MiltonCallbacks::NONE,
+        },
+        input: Input {
+            keys: [false; 12],
+            rotary: Percentage::new(50),
+            callbacks: MiltonCallbacks::NONE,
+        },
+        clocks: 0,
+    }))
+}
+
+/// Free a console.
+///
+/// # Safety
+///
+/// The console must be null, or a console created by `milton_console_new` which
+/// has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn milton_console_free(console: *mut MiltonConsole) {
+    if !console.is_null() {
+        // SAFETY: The caller guarantees that the console is valid, and it was
+        // created by `Box::into_raw`.
+        drop(unsafe { Box::from_raw(console) });
+    }
+}
+
+/// Set the callbacks of a console, or remove them if the callbacks are null.
+///
+/// # Safety
+///
+/// The console must be null or valid, and the callbacks must be null or valid for
+/// reads. Every callback must be safe to call with the `user` pointer until the
+/// callbacks are replaced or the console is freed, and must not call any
+/// `milton_console_*` function with this console, see [`MiltonCallbacks`].
+#[no_mangle]
+pub unsafe extern "C" fn milton_console_set_callbacks(
+    console: *mut MiltonConsole,
+    callbacks: *const MiltonCallbacks,
+) {
+    // SAFETY: The caller guarantees that the console is either null or valid.
+    let Some(console) = (unsafe { self::console(console) }) else {
+        return;
+    };
+    // SAFETY: The caller guarantees that the callbacks are either null or valid.
+    let callbacks = unsafe { callbacks.as_ref() }.map_or(MiltonCallbacks::NONE, |cb| *cb);
+
+    console.screen.callbacks = callbacks;
+    console.speaker.callbacks = callbacks;
+    console.input.callbacks = callbacks;
+}
+
+/// Reset a console, as if the cartridge was reinserted.
+///
+/// # Safety
+///
+/// The console must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn milton_console_reset(console: *mut MiltonConsole) {
+    // SAFETY: The caller guarantees that the console is either null or valid.
+    if let Some(console) = unsafe { self::console(console) } {
+        console.console.reset();
+        console.clocks = 0;
+    }
+}
+
+/// Run a single clock of a console, which ends the frame every 1/60th of a second.
+///
+/// # Safety
+///
+/// The console must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn milton_console_clock(console: *mut MiltonConsole) {
+    // SAFETY: The caller guarantees that the console is either null or valid.
+    if let Some(console) = unsafe { self::console(console) } {
+        console.clock();
+    }
+}
+
+/// Run a console until the end of the current frame.
+///
+/// # Safety
+///
+/// The console must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn milton_console_run_frame(console: *mut MiltonConsole) {
+    // SAFETY: The caller guarantees that the console is either null or valid.
+    if let Some(console) = unsafe { self::console(console) } {
+        console.clock();
+        while console.clocks != 0 {
+            console.clock();
+        }
+    }
+}
+
+/// Press or release the key in the given column (`0..=2`) and row (`0..=3`),
+/// returning `false` if the key does not exist.
+///
+/// This is ignored while a key callback is set.
+///
+/// # Safety
+///
+/// The console must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn milton_console_set_key(
+    console: *mut MiltonConsole,
+    col: u8,
+    row: u8,
+    pressed: bool,
+) -> bool {
+    // SAFETY: The caller guarantees that the console is either null or valid.
+    let Some(console) = (unsafe { self::console(console) }) else {
+        return false;
+    };
+    let Some(key) = Key::at(usize::from(col), usize::from(row)) else {
+        return false;
+    };
+
+    console.input.keys[key.index()] = pressed;
+    true
+}
+
+/// Turn the rotary controller, from `0` to `100` percent, returning `false` if the
+/// turn is out of range.
+///
+/// This is ignored while a rotary callback is set.
+///
+/// # Safety
+///
+/// The console must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn milton_console_set_rotary(console: *mut MiltonConsole, turn: u8) -> bool {
+    // SAFETY: The caller guarantees that the console is either null or valid.
+    let Some(console) = (unsafe { self::console(console) }) else {
+        return false;
+    };
+
+    match Percentage::try_new(usize::from(turn)) {
+        Ok(turn) => {
+            console.input.rotary = turn;
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// Write the 16 rows of the last complete frame, where bit N of a row is the pixel
+/// in column N.
+///
+/// # Safety
+///
+/// The console must be null or valid, and `rows` must be valid for writes of 16
+/// values.
+#[no_mangle]
+pub unsafe extern "C" fn milton_console_pixels(console: *mut MiltonConsole, rows: *mut u16) {
+    // SAFETY: The caller guarantees that the console is either null or valid.
+    let Some(console) = (unsafe { self::console(console) }) else {
+        return;
+    };
+    if rows.is_null() {
+        return;
+    }
+
+    // SAFETY: The caller guarantees that the rows are valid for 16 writes.
+    let rows = unsafe { slice::from_raw_parts_mut(rows, 16) };
+    rows.copy_from_slice(console.screen.framebuffer.rows());
+}
+
+/// Write the brightness of the 256 pixels of the LCD panel, row by row, from `0`
+/// (off) to `255` (fully on), which includes the slow decay of every pixel.
+///
+/// # Safety
+///
+/// The console must be null or valid, and `out` must be valid for writes of 256
+/// values.
+#[no_mangle]
+pub unsafe extern "C" fn milton_console_brightness(console: *mut MiltonConsole, out: *mut u8) {
+    // SAFETY: The caller guarantees that the console is either null or valid.
+    let Some(console) = (unsafe { self::console(console) }) else {
+        return;
+    };
+    if out.is_null() {
+        return;
+    }
+
+    // SAFETY: The caller guarantees that the output is valid for 256 writes.
+    let out = unsafe { slice::from_raw_parts_mut(out, 256) };
+    for (idx, out) in out.iter_mut().enumerate() {
+        *out = console.console.lcd.brightness(idx % 16, idx / 16);
+    }
+}
+
+/// Return the pitch of the buzzer (in Hz) during the last frame, or zero if it was
+/// silent.
+///
+/// # Safety
+///
+/// The console must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn milton_console_pitch(console: *mut MiltonConsole) -> u32 {
+    // SAFETY: The caller guarantees that the console is either null or valid.
+    unsafe { self::console(console) }
+        .and_then(|console| console.speaker.pitch)
+        .map_or(0, |pitch| u32::try_from(pitch).unwrap_or(u32::MAX))
+}
+
+/// Return the amount of (mono) audio samples which can be pulled at the given
+/// sample rate.
+///
+/// # Safety
+///
+/// The console must be null or valid.
+#[no_mangle]
+pub unsafe extern "C" fn milton_console_audio_available(
+    console: *mut MiltonConsole,
+    rate: u32,
+) -> usize {
+    // SAFETY: The caller guarantees that the console is either null or valid.
+    unsafe { self::console(console) }
+        .map_or(0, |console| console.console.buzzer.pcm.available(rate))
+}
+
+/// Pull (mono) audio samples at the given sample rate, returning the amount of
+/// samples written.
+///
+/// Samples should be pulled at least once every frame, see
+/// [`Pcm`](milton_core::buzzer::Pcm).
+///
+/// # Safety
+///
+/// The console must be null or valid, and `out` must be valid for writes of `len`
+/// samples.
+#[no_mangle]
+pub unsafe extern "C" fn milton_console_audio(
+    console: *mut MiltonConsole,
+    rate: u32,
+    out: *mut i16,
+    len: usize,
+) -> usize {
+    // SAFETY: The caller guarantees that the console is either null or valid.
+    let Some(console) = (unsafe { self::console(console) }) else {
+        return 0;
+    };
+    if out.is_null() || rate == 0 {
+        return 0;
+    }
+
+    // SAFETY: The caller guarantees that the output is valid for `len` writes.
+    let out = unsafe { slice::from_raw_parts_mut(out, len) };
+    console.console.buzzer.pcm.render(rate, out)
+}
@@ -0,0 +1,38 @@
+//! Compile the C smoke test against the static library and the generated header,
+//! and run it.
+
+#![cfg(unix)]
+
+use std::{env, path::Path, process::Command};
+
+#[test]
+fn c_smoke_test() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let exe = env::current_exe().unwrap();
+    let lib = exe
+        .parent()
+        .and_then(|deps| deps.parent())
+        .unwrap()
+        .join("libmilton_ffi.a");
+    let out = Path::new(env!("CARGO_TARGET_TMPDIR")).join("smoke");
+
+    let cc = env::var("CC").unwrap_or_else(|_| "cc".into());
+    let status = Command::new(&cc)
+        .args(["-std=c99", "-Wall", "-Wextra", "-Werror", "-I"])
+        .arg(env!("OUT_DIR"))
+        .arg(manifest.join("tests/c/smoke.c"))
+        .arg(&lib)
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&out)
+        .status()
+        .unwrap_or_else(|err| panic!("unable to run {cc}: {err}"));
+    assert!(status.success(), "the smoke test does not compile");
+
+    let output = Command::new(&out).output().unwrap();
+    assert!(
+        output.status.success(),
+        "the smoke test failed:\n{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}
@@ -0,0 +1,145 @@
+/* A smoke test of the C API, compiled and run by `cargo test`. */
+
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+
+#include "milton.h"
+
+#define CHECK(cond)                                                             \
+    do {                                                                        \
+        if (!(cond)) {                                                          \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__,    \
+                    #cond);                                                     \
+            return 1;                                                           \
+        }                                                                       \
+    } while (0)
+
+/*
+ * The first 64 bytes of the ROM assembled from:
+ *
+ *     start:  TCY 10
+ *             SETR            ; select the left keyboard column
+ *     loop:   TCY 2
+ *             SETR            ; charge the rotary controller
+ *             RSTR
+ *             TCY 0
+ *             SETR            ; toggle the buzzer
+ *             RSTR
+ *             BR loop
+ */
+static const uint8_t ROM[64] = {
+    0x45, 0x0d, 0x00, 0x44, 0x00, 0x00, 0x00, 0x0d, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x83, 0x0c, 0x0d,
+};
+
+/* The amount of calls to every callback. */
+struct Calls {
+    unsigned frames;
+    unsigned keys;
+    unsigned rotary;
+};
+
+static void end_frame(void *user) {
+    ((struct Calls *)user)->frames++;
+}
+
+static bool key(void *user, uint8_t col, uint8_t row) {
+    ((struct Calls *)user)->keys++;
+    return col == 0 && row == 1;
+}
+
+static uint8_t rotary(void *user) {
+    ((struct Calls *)user)->rotary++;
+    return 50;
+}
+
+int main(void) {
+    static const uint8_t too_large[4096];
+    MiltonSettings settings = milton_settings_default();
+
+    CHECK(settings.cpu == MILTON_CPU_TMS1100);
+    CHECK(milton_console_new(too_large, sizeof(too_large), &settings) == NULL);
+    settings.cpu = 8;
+    CHECK(milton_console_new(ROM, sizeof(ROM), &settings) == NULL);
+    settings.cpu = MILTON_CPU_TMS1100;
+
+    MiltonConsole *console = milton_console_new(ROM, sizeof(ROM), &settings);
+    CHECK(console != NULL);
+
+    /* Pushed input. */
+    CHECK(milton_console_set_key(console, 2, 3, true));
+    CHECK(!milton_console_set_key(console, 3, 0, true));
+    CHECK(milton_console_set_rotary(console, 100));
+    CHECK(!milton_console_set_rotary(console, 101));
+
+    for (int frame = 0; frame < 3; frame++) {
+        milton_console_run_frame(console);
+    }
+
+    /* Nothing is drawn, but the buzzer is toggled. */
+    uint16_t rows[16];
+    uint8_t brightness[256];
+    milton_console_pixels(console, rows);
+    milton_console_brightness(console, brightness);
+    for (int idx = 0; idx < 16; idx++) {
+        CHECK(rows[idx] == 0);
+    }
+    for (int idx = 0; idx < 256; idx++) {
+        CHECK(brightness[idx] == 0);
+    }
+
+    int16_t samples[4096];
+    size_t available = milton_console_audio_available(console, 44100);
+    CHECK(available > 2000 && available <= 4096);
+    CHECK(milton_console_audio(console, 44100, samples, 4096) == available);
+    CHECK(milton_console_audio_available(console, 44100) == 0);
+    bool audible = false;
+    for (size_t idx = 0; idx < available; idx++) {
+        audible |= samples[idx] != 0;
+    }
+    CHECK(audible);
+
+    /* Callbacks. */
+    struct Calls calls = {0, 0, 0};
+    MiltonCallbacks callbacks = {
+        .user = &calls,
+        .end_frame = end_frame,
+        .key = key,
+        .rotary = rotary,
+    };
+    milton_console_set_callbacks(console, &callbacks);
+    milton_console_run_frame(console);
+    milton_console_run_frame(console);
+    /*
+     * The left column is selected on every clock, reading its 4 keys, and the
+     * loop of 7 instructions (of 6 clocks each) charges the rotary controller
+     * every 42 clocks: 2 frames of 1666 clocks read 13328 keys and charge it 79
+     * times.
+     */
+    CHECK(calls.frames == 2);
+    CHECK(calls.keys == 13328);
+    CHECK(calls.rotary == 79);
+
+    milton_console_set_callbacks(console, NULL);
+    for (int clock = 0; clock < 42; clock++) {
+        milton_console_clock(console);
+    }
+    CHECK(calls.frames == 2 && calls.keys == 13328 && calls.rotary == 79);
+
+    /* A null console is ignored. */
+    milton_console_run_frame(NULL);
+    CHECK(milton_console_pitch(NULL) == 0);
+    CHECK(!milton_console_set_key(NULL, 0, 0, true));
+
+    milton_console_reset(console);
+    milton_console_free(console);
+    milton_console_free(NULL);
+
+    puts("ok");
+    return 0;
+}
@@ -0,0 +1,25 @@
+//! Check that the micro-processor constants match the indices of the core.
+
+use milton_core::cartridge::settings::CpuType;
+use milton_ffi::{
+    MILTON_CPU_I8021, MILTON_CPU_TMS1000, MILTON_CPU_TMS1070, MILTON_CPU_TMS1100,
+    MILTON_CPU_TMS1200, MILTON_CPU_TMS1270, MILTON_CPU_TMS1300, MILTON_CPU_TMS1370,
+};
+
+#[test]
+fn cpu_constants_match_indices() {
+    let cpus = [
+        (MILTON_CPU_TMS1100, CpuType::Tms1100),
+        (MILTON_CPU_I8021, CpuType::I8021),
+        (MILTON_CPU_TMS1000, CpuType::Tms1000),
+        (MILTON_CPU_TMS1070, CpuType::Tms1070),
+        (MILTON_CPU_TMS1200, CpuType::Tms1200),
+        (MILTON_CPU_TMS1270, CpuType::Tms1270),
+        (MILTON_CPU_TMS1300, CpuType::Tms1300),
+        (MILTON_CPU_TMS1370, CpuType::Tms1370),
+    ];
+
+    for (constant, cpu) in cpus {
+        assert_eq!(CpuType::from_index(constant), Some(cpu));
+    }
+}
@@ -0,0 +1,19 @@
+//! Check that the checked-in header matches the one generated from the exported
+//! items.
+
+use std::{fs, path::Path};
+
+#[test]
+fn header_is_up_to_date() {
+    let generated = Path::new(env!("OUT_DIR")).join("milton.h");
+    let checked_in = Path::new(env!("CARGO_MANIFEST_DIR")).join("include/milton.h");
+
+    let expected = fs::read_to_string(&generated).unwrap();
+    let actual = fs::read_to_string(&checked_in).unwrap();
+    assert!(
+        actual == expected,
+        "{} is stale, replace it with {}",
+        checked_in.display(),
+        generated.display()
+    );
+}
@@ -0,0 +1,571 @@
+//! A libretro core for Microvision cartridges, either containers (`.mltc`) or raw
+//! ROM dumps (`.bin`).
+//!
+//! # Video
+//!
+//! Every frame is a 16x16 XRGB8888 image of the LCD panel, shaded by the decay of
+//! every pixel, at 60 frames per second.
+//!
+//! # Audio
+//!
+//! The PCM audio of the buzzer, filtered by the model of the Microvision's Piezo
+//! element, is played as 44.1 kHz stereo.
+//!
+//! # Input
+//!
+//! The 3x4 keypad is mapped to the `RetroPad` of port 0 as follows, where the
+//! D-pad forms the middle column:
+//!
+//! | Row | Column 0 | Column 1 | Column 2 |
+//! |-----|----------|----------|----------|
+//! | 0   | L        | Select   | R        |
+//! | 1   | Y        | Up       | X        |
+//! | 2   | Left     | Down     | Right    |
+//! | 3   | B        | Start    | A        |
+//!
+//! The horizontal axis of the left analog stick turns the rotary controller, at
+//! up to [`ROTARY_SPEED`] per frame, so the controller keeps its position when
+//! the stick is released.
+//!
+//! # Save states
+//!
+//! Save states are console snapshots, see [`Console::save_state`], which are always
+//! [`snapshot::SIZE`] bytes in size.
+
+pub mod sys;
+
+use std::{
+    ffi::{c_char, c_uint, c_void, CStr},
+    mem, slice,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+use milton_core::{
+    buzzer::{self, Piezo, PiezoParams},
+    cartridge::{
+        container::{self, Container},
+        settings::Settings,
+        Cartridge,
+    },
+    common::{Interface, FRAME_CLOCKS},
+    display,
+    keypad::{self, Key},
+    overlay,
+    rotary::{self, Percentage},
+    snapshot, Console,
+};
+
+/// The sample rate of the audio.
+const RATE: u32 = 44_100;
+
+/// The version of this core.
+const VERSION: &CStr =
+    match CStr::from_bytes_with_nul(concat!(env!("CARGO_PKG_VERSION"), "\0").as_bytes()) {
+        Ok(version) => version,
+        Err(_) => panic!("the version contains a nul byte"),
+    };
+
+/// The `RetroPad` button and description of every key, in the order of [`Key::ALL`].
+const BUTTONS: [(c_uint, &CStr); 12] = [
+    (sys::RETRO_DEVICE_ID_JOYPAD_L, c"Key 0x0"),
+    (sys::RETRO_DEVICE_ID_JOYPAD_Y, c"Key 0x1"),
+    (sys::RETRO_DEVICE_ID_JOYPAD_LEFT, c"Key 0x2"),
+    (sys::RETRO_DEVICE_ID_JOYPAD_B, c"Key 0x3"),
+    (sys::RETRO_DEVICE_ID_JOYPAD_SELECT, c"Key 1x0"),
+    (sys::RETRO_DEVICE_ID_JOYPAD_UP, c"Key 1x1"),
+    (sys::RETRO_DEVICE_ID_JOYPAD_DOWN, c"Key 1x2"),
+    (sys::RETRO_DEVICE_ID_JOYPAD_START, c"Key 1x3"),
+    (sys::RETRO_DEVICE_ID_JOYPAD_R, c"Key 2x0"),
+    (sys::RETRO_DEVICE_ID_JOYPAD_X, c"Key 2x1"),
+    (sys::RETRO_DEVICE_ID_JOYPAD_RIGHT, c"Key 2x2"),
+    (sys::RETRO_DEVICE_ID_JOYPAD_A, c"Key 2x3"),
+];
+
+/// The maximum amount the rotary controller is turned by per frame, in thousandths
+/// of a percent.
+pub const ROTARY_SPEED: i32 = 2_000;
+
+/// The callbacks supplied by the frontend.
+///
+/// The frontend guarantees that every callback is valid until it is replaced, and
+/// may call back into the core from any of them, so no lock is held while calling
+/// them.
+#[derive(Clone, Copy)]
+struct Callbacks {
+    environment: Option<sys::retro_environment_t>,
+    video: Option<sys::retro_video_refresh_t>,
+    audio: Option<sys::retro_audio_sample_batch_t>,
+    input_poll: Option<sys::retro_input_poll_t>,
+    input_state: Option<sys::retro_input_state_t>,
+}
+
+/// The callbacks supplied by the frontend.
+static CALLBACKS: Mutex<Callbacks> = Mutex::new(Callbacks {
+    environment: None,
+    video: None,
+    audio: None,
+    input_poll: None,
+    input_state: None,
+});
+
+/// The loaded game, if any.
+static CORE: Mutex<Option<Box<Core>>> = Mutex::new(None);
+
+/// Lock a mutex, ignoring any poisoning, as every state is valid.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Call a function with the loaded game, returning `None` if no game is loaded.
+fn with_core<R>(f: impl FnOnce(&mut Core) -> R) -> Option<R> {
+    lock(&CORE).as_deref_mut().map(f)
+}
+
+/// The display and buzzer, which are rendered from the LCD panel and the PCM audio
+/// generator instead.
+struct Null;
+
+impl display::Api for Null {
+    fn enable_pixel(&mut self, _: usize, _: usize) {}
+}
+
+impl buzzer::Api for Null {
+    fn enable(&mut self, _: usize) {}
+    fn disable(&mut self) {}
+}
+
+/// The keypad and rotary controller, as polled from the frontend.
+#[derive(Debug, Clone, Copy)]
+struct Input {
+    /// The state of every key, in the order of [`Key::ALL`].
+    keys: [bool; 12],
+    /// The turn of the rotary controller, in thousandths of a percent.
+    turn: i32,
+}
+
+impl Input {
+    /// Poll the state of the `RetroPad` of port 0.
+    fn poll(&mut self, state: sys::retro_input_state_t) {
+        for (pressed, (id, _)) in self.keys.iter_mut().zip(BUTTONS) {
+            // SAFETY: The callbacks are valid, see `Callbacks`.
+            *pressed = unsafe { state(0, sys::RETRO_DEVICE_JOYPAD, 0, id) } != 0;
+        }
+
+        // SAFETY: The callbacks are valid, see `Callbacks`.
+        let x = i32::from(unsafe {
+            state(
+                0,
+                sys::RETRO_DEVICE_ANALOG,
+                sys::RETRO_DEVICE_INDEX_ANALOG_LEFT,
+                sys::RETRO_DEVICE_ID_ANALOG_X,
+            )
+        });
+        self.turn = (self.turn + x * ROTARY_SPEED / 0x8000).clamp(0, 100_000);
+    }
+}
+
+impl keypad::Api for Input {
+    fn get(&self, key: Key) -> bool {
+        self.keys[key.index()]
+    }
+}
+
+impl rotary::Api for Input {
+    fn turn(&self) -> Percentage {
+        Percentage::new(usize::try_from(self.turn / 1000).unwrap_or(0))
+    }
+}
+
+/// A loaded game.
+struct Core {
+    console: Console,
+    cart: Cartridge,
+    input: Input,
+    piezo: Piezo,
+    /// The last frame, as XRGB8888 pixels.
+    video: [u32; 256],
+    /// The last frame of audio, as mono samples.
+    samples: Vec<i16>,
+    /// The last frame of audio, as interleaved stereo samples.
+    frames: Vec<i16>,
+}
+
+impl Core {
+    /// Load a game, returning `None` if the data is not a valid cartridge.
+    fn load(data: &[u8]) -> Option<Self> {
+        let cart = if data.starts_with(&container::MAGIC) {
+            Container::parse(data).ok()?.cartridge().ok()?
+        } else {
+            Cartridge::from_known_rom(data)
+                .or_else(|_| Cartridge::try_new(data, Settings::default()))
+                .ok()?
+        };
+
+        Some(Self {
+            console: Console::new(),
+            cart,
+            input: Input {
+                keys: [false; 12],
+                turn: 50_000,
+            },
+            piezo: Piezo::new(PiezoParams::MICROVISION, RATE),
+            video: [0; 256],
+            samples: Vec::new(),
+            frames: Vec::new(),
+        })
+    }
+
+    /// Run a single frame, rendering its video and audio.
+    fn run(&mut self) {
+        let (mut display, mut buzzer) = (Null, Null);
+        for _ in 0..FRAME_CLOCKS {
+            let hardware = Interface {
+                display: &mut display,
+                buzzer: &mut buzzer,
+                keypad: &self.input,
+                rotary: &self.input,
+            };
+            self.console.clock(&mut self.cart, hardware);
+        }
+
+        let hardware = Interface {
+            display: &mut display,
+            buzzer: &mut buzzer,
+            keypad: &self.input,
+            rotary: &self.input,
+        };
+        self.console.sync(hardware);
+
+        for (idx, pixel) in self.video.iter_mut().enumerate() {
+            let [r, g, b] = overlay::shade(self.console.lcd.brightness(idx % 16, idx / 16));
+            *pixel = u32::from_be_bytes([0, r, g, b]);
+        }
+
+        let pcm = &mut self.console.buzzer.pcm;
+        self.samples.resize(pcm.available(RATE), 0);
+        pcm.render(RATE, &mut self.samples);
+        self.piezo.process(&mut self.samples);
+        self.frames.clear();
+        self.frames
+            .extend(self.samples.iter().flat_map(|&sample| [sample, sample]));
+    }
+}
+
+/// Return the version of the libretro API implemented by this core.
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    sys::RETRO_API_VERSION
+}
+
+/// Describe this core.
+///
+/// # Safety
+///
+/// `info` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut sys::retro_system_info) {
+    let system = sys::retro_system_info {
+        library_name: c"Milton".as_ptr(),
+        library_version: VERSION.as_ptr(),
+        valid_extensions: c"bin|mltc".as_ptr(),
+        need_fullpath: false,
+        block_extract: false,
+    };
+
+    // SAFETY: The caller guarantees that `info` is valid for writes.
+    unsafe { info.write(system) };
+}
+
+/// Describe the video and audio of this core.
+///
+/// # Safety
+///
+/// `info` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut sys::retro_system_av_info) {
+    let av = sys::retro_system_av_info {
+        geometry: sys::retro_game_geometry {
+            base_width: 16,
+            base_height: 16,
+            max_width: 16,
+            max_height: 16,
+            aspect_ratio: 1.0,
+        },
+        timing: sys::retro_system_timing {
+            fps: 60.0,
+            sample_rate: f64::from(RATE),
+        },
+    };
+
+    // SAFETY: The caller guarantees that `info` is valid for writes.
+    unsafe { info.write(av) };
+}
+
+/// Set the environment callback.
+#[no_mangle]
+pub extern "C" fn retro_set_environment(cb: Option<sys::retro_environment_t>) {
+    lock(&CALLBACKS).environment = cb;
+}
+
+/// Set the video callback.
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(cb: Option<sys::retro_video_refresh_t>) {
+    lock(&CALLBACKS).video = cb;
+}
+
+/// Set the single frame audio callback, which is not used by this core.
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_: Option<sys::retro_audio_sample_t>) {}
+
+/// Set the batched audio callback.
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(cb: Option<sys::retro_audio_sample_batch_t>) {
+    lock(&CALLBACKS).audio = cb;
+}
+
+/// Set the input polling callback.
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(cb: Option<sys::retro_input_poll_t>) {
+    lock(&CALLBACKS).input_poll = cb;
+}
+
+/// Set the input state callback.
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(cb: Option<sys::retro_input_state_t>) {
+    lock(&CALLBACKS).input_state = cb;
+}
+
+/// Set the device of a controller port, only the `RetroPad` is supported.
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_: c_uint, _: c_uint) {}
+
+/// Initialize this core.
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+/// Deinitialize this core, unloading any game.
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    *lock(&CORE) = None;
+}
+
+/// Load a game, returning `false` if it is not a valid cartridge.
+///
+/// # Safety
+///
+/// `game` must be null, or point to a valid game whose data is valid for reads of
+/// its size.
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const sys::retro_game_info) -> bool {
+    // SAFETY: The caller guarantees that `game` is either null or valid.
+    let Some(game) = (unsafe { game.as_ref() }) else {
+        return false;
+    };
+    if game.data.is_null() {
+        return false;
+    }
+
+    // SAFETY: The caller guarantees that the data is valid for reads of its size.
+    let data = unsafe { slice::from_raw_parts(game.data.cast::<u8>(), game.size) };
+    let Some(core) = Core::load(data) else {
+        return false;
+    };
+
+    let Some(environment) = lock(&CALLBACKS).environment else {
+        return false;
+    };
+
+    let mut format = sys::RETRO_PIXEL_FORMAT_XRGB8888;
+    // SAFETY: The callbacks are valid, see `Callbacks`, and the data of this command
+    // is a pixel format.
+    if !unsafe {
+        environment(
+            sys::RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+            (&raw mut format).cast(),
+        )
+    } {
+        return false;
+    }
+
+    let buttons = BUTTONS.map(|(id, description)| sys::retro_input_descriptor {
+        port: 0,
+        device: sys::RETRO_DEVICE_JOYPAD,
+        index: 0,
+        id,
+        description: description.as_ptr(),
+    });
+    let rotary = sys::retro_input_descriptor {
+        port: 0,
+        device: sys::RETRO_DEVICE_ANALOG,
+        index: sys::RETRO_DEVICE_INDEX_ANALOG_LEFT,
+        id: sys::RETRO_DEVICE_ID_ANALOG_X,
+        description: c"Rotary".as_ptr(),
+    };
+    let end = sys::retro_input_descriptor {
+        port: 0,
+        device: 0,
+        index: 0,
+        id: 0,
+        description: std::ptr::null(),
+    };
+    let mut descriptors: Vec<_> = buttons.into_iter().chain([rotary, end]).collect();
+    // SAFETY: The callbacks are valid, see `Callbacks`, and the data of this command
+    // is a list of descriptors ending with one without description.
+    unsafe {
+        environment(
+            sys::RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS,
+            descriptors.as_mut_ptr().cast(),
+        );
+    }
+
+    *lock(&CORE) = Some(Box::new(core));
+    true
+}
+
+/// Load a special game, which is not supported by this core.
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _: c_uint,
+    _: *const sys::retro_game_info,
+    _: usize,
+) -> bool {
+    false
+}
+
+/// Unload the loaded game.
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    *lock(&CORE) = None;
+}
+
+/// Return the region of the loaded game.
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    sys::RETRO_REGION_NTSC
+}
+
+/// Reset the console.
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    with_core(|core| {
+        core.console.reset();
+        core.piezo.reset();
+    });
+}
+
+/// Run a single frame, passing its video and audio to the frontend.
+///
+/// The loaded game is only locked while it runs, as the frontend may call back
+/// into the core, e.g. to save a state, from any of its callbacks.
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    let callbacks = *lock(&CALLBACKS);
+    let Some(mut input) = with_core(|core| core.input) else {
+        return;
+    };
+
+    if let Some(poll) = callbacks.input_poll {
+        // SAFETY: The callbacks are valid, see `Callbacks`.
+        unsafe { poll() };
+    }
+    if let Some(state) = callbacks.input_state {
+        input.poll(state);
+    }
+
+    let Some((video, frames)) = with_core(|core| {
+        core.input = input;
+        core.run();
+        (core.video, mem::take(&mut core.frames))
+    }) else {
+        return;
+    };
+
+    if let Some(refresh) = callbacks.video {
+        // SAFETY: The callbacks are valid, see `Callbacks`, and the frame is 16 rows
+        // of 16 pixels, without padding.
+        unsafe { refresh(video.as_ptr().cast(), 16, 16, 16 * 4) };
+    }
+    if let Some(audio) = callbacks.audio {
+        let mut remaining = &frames[..];
+        while !remaining.is_empty() {
+            // SAFETY: The callbacks are valid, see `Callbacks`, and the samples are
+            // valid for reads of twice the amount of frames.
+            let written = unsafe { audio(remaining.as_ptr(), remaining.len() / 2) };
+            let written = written.min(remaining.len() / 2);
+            if written == 0 {
+                break;
+            }
+            remaining = &remaining[written * 2..];
+        }
+    }
+
+    // Hand the buffer back, to reuse its allocation on the next frame.
+    with_core(|core| core.frames = frames);
+}
+
+/// Return the size of a save state.
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    snapshot::SIZE
+}
+
+/// Save a state into a buffer, returning `false` if no game is loaded or the
+/// buffer is too small.
+///
+/// # Safety
+///
+/// `data` must be null, or valid for writes of `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    if data.is_null() {
+        return false;
+    }
+
+    // SAFETY: The caller guarantees that the data is valid for writes of its size.
+    let buf = unsafe { slice::from_raw_parts_mut(data.cast::<u8>(), size) };
+    with_core(|core| core.console.save_state(&core.cart, buf).is_ok()).unwrap_or(false)
+}
+
+/// Load a state from a buffer, returning `false` if no game is loaded or the
+/// buffer does not contain a valid save state of the loaded game.
+///
+/// # Safety
+///
+/// `data` must be null, or valid for reads of `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    if data.is_null() {
+        return false;
+    }
+
+    // SAFETY: The caller guarantees that the data is valid for reads of its size.
+    let buf = unsafe { slice::from_raw_parts(data.cast::<u8>(), size) };
+    with_core(|core| {
+        let loaded = core.console.load_state(&mut core.cart, buf).is_ok();
+        if loaded {
+            core.piezo.reset();
+        }
+        loaded
+    })
+    .unwrap_or(false)
+}
+
+/// Reset all cheats, which are not supported by this core.
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+/// Set a cheat, which is not supported by this core.
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_: c_uint, _: bool, _: *const c_char) {}
+
+/// Return a memory region, none of which are exposed by this core.
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(_: c_uint) -> *mut c_void {
+    std::ptr::null_mut()
+}
+
+/// Return the size of a memory region, none of which are exposed by this core.
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(_: c_uint) -> usize {
+    0
+}
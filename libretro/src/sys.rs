@@ -0,0 +1,140 @@
+//! The subset of the libretro API (`libretro.h`) used by this core.
+
+#![allow(non_camel_case_types)]
+
+use std::ffi::{c_char, c_uint, c_void};
+
+/// The version of the libretro API implemented by this core.
+pub const RETRO_API_VERSION: c_uint = 1;
+
+/// The environment command setting the pixel format, see [`retro_pixel_format`].
+pub const RETRO_ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+/// The environment command describing the input, see [`retro_input_descriptor`].
+pub const RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS: c_uint = 11;
+
+/// The 32-bit XRGB pixel format, where the upper 8 bits are ignored.
+pub const RETRO_PIXEL_FORMAT_XRGB8888: retro_pixel_format = 1;
+
+/// The `RetroPad`, a gamepad with a D-pad and 12 buttons.
+pub const RETRO_DEVICE_JOYPAD: c_uint = 1;
+/// The analog sticks of a `RetroPad`.
+pub const RETRO_DEVICE_ANALOG: c_uint = 5;
+
+// The IDs of the RetroPad buttons.
+pub const RETRO_DEVICE_ID_JOYPAD_B: c_uint = 0;
+pub const RETRO_DEVICE_ID_JOYPAD_Y: c_uint = 1;
+pub const RETRO_DEVICE_ID_JOYPAD_SELECT: c_uint = 2;
+pub const RETRO_DEVICE_ID_JOYPAD_START: c_uint = 3;
+pub const RETRO_DEVICE_ID_JOYPAD_UP: c_uint = 4;
+pub const RETRO_DEVICE_ID_JOYPAD_DOWN: c_uint = 5;
+pub const RETRO_DEVICE_ID_JOYPAD_LEFT: c_uint = 6;
+pub const RETRO_DEVICE_ID_JOYPAD_RIGHT: c_uint = 7;
+pub const RETRO_DEVICE_ID_JOYPAD_A: c_uint = 8;
+pub const RETRO_DEVICE_ID_JOYPAD_X: c_uint = 9;
+pub const RETRO_DEVICE_ID_JOYPAD_L: c_uint = 10;
+pub const RETRO_DEVICE_ID_JOYPAD_R: c_uint = 11;
+
+/// The left analog stick.
+pub const RETRO_DEVICE_INDEX_ANALOG_LEFT: c_uint = 0;
+/// The horizontal axis of an analog stick.
+pub const RETRO_DEVICE_ID_ANALOG_X: c_uint = 0;
+
+/// The NTSC region, i.e. 60 frames per second.
+pub const RETRO_REGION_NTSC: c_uint = 0;
+
+/// A pixel format.
+pub type retro_pixel_format = c_uint;
+
+/// The environment callback, handling a command with command-specific data.
+pub type retro_environment_t = unsafe extern "C" fn(cmd: c_uint, data: *mut c_void) -> bool;
+/// The video callback, receiving a frame of `width` by `height` pixels, where rows
+/// are `pitch` bytes apart.
+pub type retro_video_refresh_t =
+    unsafe extern "C" fn(data: *const c_void, width: c_uint, height: c_uint, pitch: usize);
+/// The audio callback, receiving a single stereo frame.
+pub type retro_audio_sample_t = unsafe extern "C" fn(left: i16, right: i16);
+/// The audio callback, receiving interleaved stereo frames.
+pub type retro_audio_sample_batch_t =
+    unsafe extern "C" fn(data: *const i16, frames: usize) -> usize;
+/// The input callback, polling the state of the input.
+pub type retro_input_poll_t = unsafe extern "C" fn();
+/// The input callback, returning the state of a button or axis.
+pub type retro_input_state_t =
+    unsafe extern "C" fn(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16;
+
+/// The description of a core.
+#[repr(C)]
+pub struct retro_system_info {
+    /// The name of the core.
+    pub library_name: *const c_char,
+    /// The version of the core.
+    pub library_version: *const c_char,
+    /// The file extensions of the content, separated by `|`.
+    pub valid_extensions: *const c_char,
+    /// Whether the content must be loaded by the core from its path.
+    pub need_fullpath: bool,
+    /// Whether archives must not be extracted by the frontend.
+    pub block_extract: bool,
+}
+
+/// The dimensions of the video.
+#[repr(C)]
+pub struct retro_game_geometry {
+    /// The nominal width of the video.
+    pub base_width: c_uint,
+    /// The nominal height of the video.
+    pub base_height: c_uint,
+    /// The maximum width of the video.
+    pub max_width: c_uint,
+    /// The maximum height of the video.
+    pub max_height: c_uint,
+    /// The display aspect ratio, or `0.0` for square pixels.
+    pub aspect_ratio: f32,
+}
+
+/// The timing of the video and audio.
+#[repr(C)]
+pub struct retro_system_timing {
+    /// The frames per second of the video.
+    pub fps: f64,
+    /// The sample rate of the audio.
+    pub sample_rate: f64,
+}
+
+/// The description of the video and audio.
+#[repr(C)]
+pub struct retro_system_av_info {
+    /// The dimensions of the video.
+    pub geometry: retro_game_geometry,
+    /// The timing of the video and audio.
+    pub timing: retro_system_timing,
+}
+
+/// The content to load.
+#[repr(C)]
+pub struct retro_game_info {
+    /// The path of the content, if any.
+    pub path: *const c_char,
+    /// The content itself, unless the core needs the full path.
+    pub data: *const c_void,
+    /// The size of the content, in bytes.
+    pub size: usize,
+    /// Frontend-specific metadata.
+    pub meta: *const c_char,
+}
+
+/// The description of a single input, the list of descriptors ends with a
+/// descriptor whose `description` is null.
+#[repr(C)]
+pub struct retro_input_descriptor {
+    /// The port of the input.
+    pub port: c_uint,
+    /// The device of the input.
+    pub device: c_uint,
+    /// The index of the input, e.g. the analog stick.
+    pub index: c_uint,
+    /// The ID of the input, e.g. the button.
+    pub id: c_uint,
+    /// The human readable description of the input.
+    pub description: *const c_char,
+}
@@ -0,0 +1,267 @@
+//! A small libretro frontend, loading the core as a shared library.
+
+use std::{
+    env,
+    ffi::{c_uint, c_void, CStr},
+    mem::MaybeUninit,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicI16, AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use libloading::Library;
+use milton_libretro::sys;
+
+/// The pixel format requested by the core.
+static FORMAT: AtomicUsize = AtomicUsize::new(usize::MAX);
+/// The amount of input descriptors supplied by the core.
+static DESCRIPTORS: AtomicUsize = AtomicUsize::new(0);
+/// The frames passed to the video callback.
+static VIDEO: Mutex<Vec<Vec<u32>>> = Mutex::new(Vec::new());
+/// The amount of audio frames passed to the audio callback.
+static AUDIO: AtomicUsize = AtomicUsize::new(0);
+/// The amount of inputs queried by the core.
+static QUERIES: AtomicUsize = AtomicUsize::new(0);
+/// Whether the button of the key in column 0 and row 0 is pressed.
+static PRESSED: AtomicBool = AtomicBool::new(true);
+/// The X axis of the left analog stick.
+static ANALOG_X: AtomicI16 = AtomicI16::new(i16::MAX);
+/// The core, called back from the video callback.
+static CORE: Mutex<Option<Reentry>> = Mutex::new(None);
+
+/// The functions of the core called from within a callback.
+#[derive(Clone, Copy)]
+struct Reentry {
+    serialize_size: extern "C" fn() -> usize,
+    serialize: unsafe extern "C" fn(*mut c_void, usize) -> bool,
+    reset: extern "C" fn(),
+}
+
+unsafe extern "C" fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    match cmd {
+        sys::RETRO_ENVIRONMENT_SET_PIXEL_FORMAT => {
+            // SAFETY: The data of this command is a pixel format.
+            let format = unsafe { *data.cast::<sys::retro_pixel_format>() };
+            FORMAT.store(format as usize, Ordering::SeqCst);
+            true
+        }
+        sys::RETRO_ENVIRONMENT_SET_INPUT_DESCRIPTORS => {
+            let mut descriptor = data.cast::<sys::retro_input_descriptor>().cast_const();
+            // SAFETY: The data of this command is a list of descriptors, which ends
+            // with a descriptor without description.
+            while !unsafe { (*descriptor).description }.is_null() {
+                DESCRIPTORS.fetch_add(1, Ordering::SeqCst);
+                // SAFETY: See above.
+                descriptor = unsafe { descriptor.add(1) };
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+unsafe extern "C" fn video(data: *const c_void, width: c_uint, height: c_uint, pitch: usize) {
+    assert_eq!((width, height, pitch), (16, 16, 64));
+    // SAFETY: The frame is 16 rows of 16 pixels, without padding.
+    let frame = unsafe { std::slice::from_raw_parts(data.cast::<u32>(), 256) };
+    VIDEO.lock().unwrap().push(frame.to_vec());
+
+    // The core must not hold any lock while calling back the frontend.
+    let core = *CORE.lock().unwrap();
+    if let Some(core) = core {
+        let mut state = vec![0u8; (core.serialize_size)()];
+        // SAFETY: The state is valid for writes of its size.
+        assert!(unsafe { (core.serialize)(state.as_mut_ptr().cast(), state.len()) });
+        (core.reset)();
+    }
+}
+
+unsafe extern "C" fn audio(_: *const i16, frames: usize) -> usize {
+    AUDIO.fetch_add(frames, Ordering::SeqCst);
+    frames
+}
+
+unsafe extern "C" fn input_poll() {}
+
+unsafe extern "C" fn input_state(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16 {
+    assert_eq!(port, 0);
+    QUERIES.fetch_add(1, Ordering::SeqCst);
+    match (device, index, id) {
+        (sys::RETRO_DEVICE_JOYPAD, 0, sys::RETRO_DEVICE_ID_JOYPAD_L) => {
+            PRESSED.load(Ordering::SeqCst).into()
+        }
+        (
+            sys::RETRO_DEVICE_ANALOG,
+            sys::RETRO_DEVICE_INDEX_ANALOG_LEFT,
+            sys::RETRO_DEVICE_ID_ANALOG_X,
+        ) => ANALOG_X.load(Ordering::SeqCst),
+        _ => 0,
+    }
+}
+
+/// Return the path of the core, next to the directory of this test.
+fn core_path() -> PathBuf {
+    let exe = env::current_exe().unwrap();
+    let dir = exe.parent().and_then(|deps| deps.parent()).unwrap();
+    dir.join(libloading::library_filename("milton_libretro"))
+}
+
+#[test]
+#[allow(clippy::too_many_lines)]
+fn runs_as_shared_library() {
+    // SAFETY: The core does not run any code when loaded.
+    let lib = unsafe { Library::new(core_path()) }.expect("the core is built");
+
+    macro_rules! sym {
+        ($name:ident: $ty:ty) => {
+            // SAFETY: The symbol is declared with the signature of libretro.h.
+            let symbol = unsafe { lib.get::<$ty>(concat!(stringify!($name), "\0").as_bytes()) };
+            let $name = *symbol.unwrap();
+        };
+    }
+
+    sym!(retro_api_version: extern "C" fn() -> c_uint);
+    sym!(retro_get_system_info: unsafe extern "C" fn(*mut sys::retro_system_info));
+    sym!(retro_get_system_av_info: unsafe extern "C" fn(*mut sys::retro_system_av_info));
+    sym!(retro_set_environment: extern "C" fn(Option<sys::retro_environment_t>));
+    sym!(retro_set_video_refresh: extern "C" fn(Option<sys::retro_video_refresh_t>));
+    sym!(retro_set_audio_sample_batch: extern "C" fn(Option<sys::retro_audio_sample_batch_t>));
+    sym!(retro_set_input_poll: extern "C" fn(Option<sys::retro_input_poll_t>));
+    sym!(retro_set_input_state: extern "C" fn(Option<sys::retro_input_state_t>));
+    sym!(retro_init: extern "C" fn());
+    sym!(retro_deinit: extern "C" fn());
+    sym!(retro_load_game: unsafe extern "C" fn(*const sys::retro_game_info) -> bool);
+    sym!(retro_reset: extern "C" fn());
+    sym!(retro_run: extern "C" fn());
+    sym!(retro_serialize_size: extern "C" fn() -> usize);
+    sym!(retro_serialize: unsafe extern "C" fn(*mut c_void, usize) -> bool);
+    sym!(retro_unserialize: unsafe extern "C" fn(*const c_void, usize) -> bool);
+
+    assert_eq!(retro_api_version(), sys::RETRO_API_VERSION);
+
+    let mut info = MaybeUninit::uninit();
+    // SAFETY: The info is valid for writes.
+    let info = unsafe {
+        retro_get_system_info(info.as_mut_ptr());
+        info.assume_init()
+    };
+    // SAFETY: The name is a static string.
+    assert_eq!(unsafe { CStr::from_ptr(info.library_name) }, c"Milton");
+
+    let mut av = MaybeUninit::uninit();
+    // SAFETY: The info is valid for writes.
+    let av = unsafe {
+        retro_get_system_av_info(av.as_mut_ptr());
+        av.assume_init()
+    };
+    assert_eq!((av.geometry.base_width, av.geometry.base_height), (16, 16));
+
+    retro_set_environment(Some(environment));
+    retro_set_video_refresh(Some(video));
+    retro_set_audio_sample_batch(Some(audio));
+    retro_set_input_poll(Some(input_poll));
+    retro_set_input_state(Some(input_state));
+    retro_init();
+
+    let load = |rom: &[u8]| {
+        let game = sys::retro_game_info {
+            path: std::ptr::null(),
+            data: rom.as_ptr().cast(),
+            size: rom.len(),
+            meta: std::ptr::null(),
+        };
+        // SAFETY: The game is valid.
+        unsafe { retro_load_game(&raw const game) }
+    };
+    assert!(!load(&[0; 0x801]));
+    assert!(load(&[0; 0x800]));
+    assert_eq!(
+        FORMAT.load(Ordering::SeqCst),
+        sys::RETRO_PIXEL_FORMAT_XRGB8888 as usize
+    );
+    assert_eq!(DESCRIPTORS.load(Ordering::SeqCst), 13);
+
+    for _ in 0..10 {
+        retro_run();
+    }
+    let frames = std::mem::take(&mut *VIDEO.lock().unwrap());
+    assert_eq!(frames.len(), 10);
+    assert!(frames.iter().flatten().all(|&pixel| pixel == 0x00c8_ccb4));
+    // About 735 audio frames per video frame.
+    assert!((7_300..7_400).contains(&AUDIO.load(Ordering::SeqCst)));
+    assert_eq!(QUERIES.load(Ordering::SeqCst), 10 * 13);
+
+    let size = retro_serialize_size();
+    let (mut a, mut b, mut c) = (vec![0u8; size], vec![0u8; size], vec![0u8; size]);
+    // SAFETY: The buffers are valid for reads and writes of their sizes.
+    unsafe {
+        assert!(retro_serialize(a.as_mut_ptr().cast(), size));
+        retro_run();
+        assert!(retro_serialize(b.as_mut_ptr().cast(), size));
+
+        assert!(retro_unserialize(a.as_ptr().cast(), size));
+        retro_run();
+        assert!(retro_serialize(c.as_mut_ptr().cast(), size));
+
+        assert!(!retro_serialize(c.as_mut_ptr().cast(), size - 1));
+        assert!(!retro_unserialize(c.as_ptr().cast(), 4));
+    }
+    assert_eq!(b, c);
+
+    *CORE.lock().unwrap() = Some(Reentry {
+        serialize_size: retro_serialize_size,
+        serialize: retro_serialize,
+        reset: retro_reset,
+    });
+    retro_run();
+    *CORE.lock().unwrap() = None;
+    assert_eq!(VIDEO.lock().unwrap().len(), 3);
+
+    // A program which selects the left column of the keypad, pulses the rotary
+    // charge and stores K into RAM, so that both inputs change the save state.
+    //
+    // start: TCY 10, SETR, TCY 2, SETR, RSTR, TKA, TCY 0, TAM, BR start
+    let mut rom = [0; 0x800];
+    for (addr, opcode) in [
+        (0x00, 0x45),
+        (0x01, 0x0d),
+        (0x03, 0x44),
+        (0x07, 0x0d),
+        (0x0f, 0x0c),
+        (0x1f, 0x08),
+        (0x3f, 0x40),
+        (0x3e, 0x27),
+        (0x3d, 0x80),
+    ] {
+        rom[addr] = opcode;
+    }
+    assert!(load(&rom));
+
+    let mut start = vec![0u8; size];
+    // SAFETY: The state is valid for writes of its size.
+    assert!(unsafe { retro_serialize(start.as_mut_ptr().cast(), size) });
+    let run = |pressed: bool, x: i16| {
+        PRESSED.store(pressed, Ordering::SeqCst);
+        ANALOG_X.store(x, Ordering::SeqCst);
+
+        let mut state = vec![0u8; size];
+        // SAFETY: The states are valid for reads and writes of their sizes.
+        unsafe {
+            assert!(retro_unserialize(start.as_ptr().cast(), size));
+            retro_run();
+            assert!(retro_serialize(state.as_mut_ptr().cast(), size));
+        }
+        state
+    };
+
+    // The rotary controller is only turned by the analog stick, and stays turned,
+    // so it must come last.
+    let idle = run(false, 0);
+    assert_eq!(run(false, 0), idle);
+    assert_ne!(run(true, 0), idle);
+    assert_ne!(run(false, i16::MAX), idle);
+
+    retro_deinit();
+}
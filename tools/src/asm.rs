@@ -0,0 +1,837 @@
+//! A macro assembler for the TMS1000 family of micro-processors.
+//!
+//! # Syntax
+//!
+//! Every line consists of an optional label, followed by an instruction, directive or
+//! macro invocation, comments begin with `;`.
+//!
+//! ```text
+//! .equ    SPEED, 3            ; a named constant
+//!
+//! .macro  WAIT value          ; a macro with a single parameter
+//!         TCY value
+//! loop\@: DYN                 ; `\@` is unique to every macro invocation
+//!         BR loop\@
+//! .endm
+//!
+//! .page   2                   ; place the following code in page 2
+//! start:  WAIT SPEED
+//!         BR start
+//! ```
+//!
+//! The directives are:
+//!
+//! - `.page N`, place the following code at the start of page `N`, pages `16..=31`
+//!   belong to the second chapter of the TMS1100.
+//! - `.equ NAME, VALUE`, define a named constant.
+//! - `.byte VALUE, ...`, place raw opcodes.
+//! - `.macro NAME PARAM, ...` and `.endm`, define a macro.
+//!
+//! # Placement
+//!
+//! Code is placed in the order the `PC` program counter visits a page, see
+//! [`next_pc`], not in linear byte order. A page holds 64 instructions, there is
+//! no way for execution to fall through into another page.
+//!
+//! # Branches
+//!
+//! A `BR` or `CALL` to a label in another page is expanded into an `LDP` (and a
+//! `COMC` for another chapter) before the branch. As every branch is conditional,
+//! the page and chapter buffers are restored afterwards, so that the code following
+//! the branch behaves the same whether or not it was taken. Branches within
+//! subroutines must stay within the same page, as `LDP` would modify the return
+//! page.
+//!
+//! A branch to an absolute `chapter:page:address` target, e.g. `BR 0:3:2a`, is
+//! placed as-is, which allows re-assembling the output of the disassembler.
+
+use std::{collections::HashMap, fmt, fmt::Write};
+
+use milton_core::tms1100::{
+    dasm::{Instruction, Operand},
+    mem::{Rom, RomAddr},
+    next_pc,
+    pla::Fixed,
+    Model,
+};
+
+use arbitrary_int::{u1, u2, u3, u4, u6};
+
+/// The maximum depth of nested macro invocations.
+const MAX_DEPTH: usize = 16;
+
+/// The kind of error which occurred while assembling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// A line could not be parsed.
+    Syntax,
+    /// An unknown mnemonic, directive or macro was used.
+    UnknownMnemonic(String),
+    /// An unknown label or constant was used.
+    UnknownSymbol(String),
+    /// A label, constant or macro was defined more than once.
+    DuplicateSymbol(String),
+    /// An operand is out of range for its instruction.
+    InvalidOperand,
+    /// A page does not exist on the micro-processor.
+    InvalidPage(usize),
+    /// A page contains more than 64 instructions.
+    PageOverflow(usize),
+    /// A macro was never terminated with `.endm`.
+    UnterminatedMacro,
+    /// Macros were nested too deeply, e.g. a macro invoking itself.
+    MacroDepth,
+}
+
+/// An error which occurred while assembling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    /// The (1-based) line of the source the error occurred on.
+    pub line: usize,
+    /// The kind of error.
+    pub kind: ErrorKind,
+}
+
+impl Error {
+    /// Create a new error on the given line.
+    fn new(line: usize, kind: ErrorKind) -> Self {
+        Self { line, kind }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            ErrorKind::Syntax => write!(f, "syntax error"),
+            ErrorKind::UnknownMnemonic(name) => write!(f, "unknown mnemonic `{name}`"),
+            ErrorKind::UnknownSymbol(name) => write!(f, "unknown symbol `{name}`"),
+            ErrorKind::DuplicateSymbol(name) => write!(f, "`{name}` is already defined"),
+            ErrorKind::InvalidOperand => write!(f, "operand out of range"),
+            ErrorKind::InvalidPage(page) => write!(f, "page {page} does not exist"),
+            ErrorKind::PageOverflow(page) => {
+                write!(f, "page {page} holds more than 64 instructions")
+            }
+            ErrorKind::UnterminatedMacro => write!(f, "macro is missing `.endm`"),
+            ErrorKind::MacroDepth => write!(f, "macros are nested too deeply"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// The output of the assembler.
+#[derive(Debug, Clone)]
+pub struct Assembly {
+    /// The assembled ROM image.
+    pub rom: Rom,
+    /// The address of every label, in the order they were defined.
+    pub labels: Vec<(String, RomAddr)>,
+}
+
+impl Assembly {
+    /// Return the symbol file of this assembly.
+    ///
+    /// Every line contains the `chapter:page:address` of a label, followed by
+    /// its name, sorted by address.
+    #[must_use]
+    pub fn symbols(&self) -> String {
+        let mut labels = self.labels.clone();
+        labels.sort_by_key(|(_, addr)| addr.full().value());
+
+        let mut out = String::new();
+        for (name, addr) in labels {
+            let _ = writeln!(out, "{addr} {name}");
+        }
+        out
+    }
+}
+
+/// The target of a branch or call.
+#[derive(Debug, Clone)]
+enum Target {
+    /// A label, which may lie in another page or chapter.
+    Label(String),
+    /// An absolute address, which is placed as-is.
+    Absolute(u6),
+}
+
+/// An item placed within a page.
+#[derive(Debug, Clone)]
+enum Item {
+    /// A label definition.
+    Label(String),
+    /// A single opcode.
+    Opcode(u8),
+    /// A branch (`BR`) or call (`CALL`) to a target.
+    Branch {
+        /// The opcode of the branch, without its address.
+        base: u8,
+        /// The target of the branch.
+        target: Target,
+    },
+}
+
+/// A line of source, after macro expansion.
+struct Line {
+    /// The (1-based) line within the original source.
+    number: usize,
+    /// The text of the line, without comments.
+    text: String,
+}
+
+/// A macro definition.
+struct Macro {
+    /// The names of the parameters.
+    params: Vec<String>,
+    /// The lines of the body.
+    body: Vec<String>,
+}
+
+/// Assemble the given source for a micro-processor model.
+///
+/// # Errors
+///
+/// If the source contains an error, e.g. an unknown mnemonic or a page containing
+/// too many instructions, the first error is returned.
+pub fn assemble(source: &str, model: Model) -> Result<Assembly, Error> {
+    let lines = expand(source)?;
+    let (items, constants) = parse(&lines, model)?;
+
+    layout(&items, &constants, model)
+}
+
+/// The items of every page, along with the line they originate from.
+type Pages = Vec<Vec<(usize, Item)>>;
+
+/// Parse the (expanded) lines of source into the items of every page, along with
+/// the named constants.
+fn parse(lines: &[Line], model: Model) -> Result<(Pages, HashMap<String, u32>), Error> {
+    let pages = model.rom_size() / 64;
+    let mut constants: HashMap<String, u32> = HashMap::new();
+    let mut items: Pages = vec![Vec::new(); pages];
+    let mut page = 0;
+
+    for line in lines {
+        let mut text = line.text.trim();
+        let err = |kind| Error::new(line.number, kind);
+
+        // An optional label, preceding the statement.
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if is_ident(label) {
+                items[page].push((line.number, Item::Label(label.to_string())));
+                text = rest.trim();
+            }
+        }
+
+        let Some(name) = text.split_whitespace().next() else {
+            continue;
+        };
+        let args = split_args(text[name.len()..].trim());
+
+        match name.to_ascii_lowercase().as_str() {
+            ".page" => {
+                let [arg] = args.as_slice() else {
+                    return Err(err(ErrorKind::Syntax));
+                };
+                let value = eval(arg, &constants).map_err(err)?;
+                page = usize::try_from(value).unwrap_or(usize::MAX);
+                if page >= pages {
+                    return Err(err(ErrorKind::InvalidPage(page)));
+                }
+            }
+            ".equ" => {
+                let [name, value] = args.as_slice() else {
+                    return Err(err(ErrorKind::Syntax));
+                };
+                if !is_ident(name) {
+                    return Err(err(ErrorKind::Syntax));
+                }
+                let value = eval(value, &constants).map_err(err)?;
+                if constants.insert((*name).to_string(), value).is_some() {
+                    return Err(err(ErrorKind::DuplicateSymbol((*name).to_string())));
+                }
+            }
+            ".byte" => {
+                for arg in args {
+                    let value = eval(arg, &constants).map_err(err)?;
+                    let opcode = u8::try_from(value).map_err(|_| err(ErrorKind::InvalidOperand))?;
+                    items[page].push((line.number, Item::Opcode(opcode)));
+                }
+            }
+            mnemonic => {
+                let item = instruction(model, mnemonic, &args, &constants).map_err(err)?;
+                items[page].push((line.number, item));
+            }
+        }
+    }
+
+    Ok((items, constants))
+}
+
+/// Place the items of every page in execution order, resolving every branch.
+fn layout(
+    items: &Pages,
+    constants: &HashMap<String, u32>,
+    model: Model,
+) -> Result<Assembly, Error> {
+    // The page of every label, which determines the size of the branches to it.
+    let mut pages_of: HashMap<&str, usize> = HashMap::new();
+    for (page, items) in items.iter().enumerate() {
+        for (number, item) in items {
+            if let Item::Label(name) = item {
+                if constants.contains_key(name) || pages_of.insert(name, page).is_some() {
+                    return Err(Error::new(
+                        *number,
+                        ErrorKind::DuplicateSymbol(name.clone()),
+                    ));
+                }
+            }
+        }
+    }
+
+    // The address of every label.
+    let mut labels: Vec<(String, RomAddr)> = Vec::new();
+    for (page, items) in items.iter().enumerate() {
+        let mut slot = 0;
+        for (number, item) in items {
+            match item {
+                Item::Label(name) => labels.push((name.clone(), slot_addr(page, slot))),
+                Item::Opcode(_) => slot += 1,
+                Item::Branch { target, .. } => {
+                    let far = far_sequence(model, page, target, &pages_of)
+                        .map_err(|kind| Error::new(*number, kind))?;
+                    slot += 1 + far.0.len() + far.1.len();
+                }
+            }
+        }
+        if slot > 64 {
+            return Err(Error::new(
+                items.last().map_or(0, |(number, _)| *number),
+                ErrorKind::PageOverflow(page),
+            ));
+        }
+    }
+
+    let addr_of: HashMap<&str, RomAddr> = labels
+        .iter()
+        .map(|(name, addr)| (name.as_str(), *addr))
+        .collect();
+
+    let mut rom = Rom::new();
+    for (page, items) in items.iter().enumerate() {
+        let mut slot = 0;
+        let mut place = |opcode: u8| {
+            rom.data[usize::from(slot_addr(page, slot).full().value())] = opcode;
+            slot += 1;
+        };
+
+        for (number, item) in items {
+            match item {
+                Item::Label(_) => {}
+                Item::Opcode(opcode) => place(*opcode),
+                Item::Branch { base, target } => {
+                    let (before, after) = far_sequence(model, page, target, &pages_of)
+                        .map_err(|kind| Error::new(*number, kind))?;
+                    let addr = match target {
+                        Target::Label(name) => addr_of[name.as_str()].addr(),
+                        Target::Absolute(addr) => *addr,
+                    };
+
+                    before.into_iter().for_each(&mut place);
+                    place(base | addr.value());
+                    after.into_iter().for_each(&mut place);
+                }
+            }
+        }
+    }
+
+    Ok(Assembly { rom, labels })
+}
+
+/// Return the address of the nth instruction slot of a page.
+///
+/// Pages `16..=31` belong to the second chapter.
+#[allow(clippy::cast_possible_truncation)]
+fn slot_addr(page: usize, slot: usize) -> RomAddr {
+    let mut pc = u6::new(0);
+    for _ in 0..slot % 64 {
+        pc = next_pc(pc);
+    }
+
+    RomAddr::new(
+        u1::new((page >> 4) as u8 & 1),
+        u4::new(page as u8 & 0xf),
+        pc,
+    )
+}
+
+/// Return the opcodes placed before and after a branch from the given page.
+///
+/// A branch to a label within another page loads that page with `LDP`, and
+/// toggles the chapter with `COMC` if needed, the page and chapter are restored
+/// after the branch, in case it was not taken.
+fn far_sequence(
+    model: Model,
+    page: usize,
+    target: &Target,
+    pages_of: &HashMap<&str, usize>,
+) -> Result<(Vec<u8>, Vec<u8>), ErrorKind> {
+    let Target::Label(name) = target else {
+        return Ok((Vec::new(), Vec::new()));
+    };
+    let target = *pages_of
+        .get(name.as_str())
+        .ok_or_else(|| ErrorKind::UnknownSymbol(name.clone()))?;
+
+    let (mut before, mut after) = (Vec::new(), Vec::new());
+    if target & 0xf != page & 0xf {
+        before.push(encode(model, "LDP", Operand::Page(page_nibble(target)))?);
+        after.push(encode(model, "LDP", Operand::Page(page_nibble(page)))?);
+    }
+    if target >> 4 != page >> 4 {
+        let comc = encode(model, "COMC", Operand::None)?;
+        before.push(comc);
+        after.insert(0, comc);
+    }
+
+    Ok((before, after))
+}
+
+/// Return the lower 4 bits of a page index.
+#[allow(clippy::cast_possible_truncation)]
+fn page_nibble(page: usize) -> u4 {
+    u4::new(page as u8 & 0xf)
+}
+
+/// Return the opcode of the given mnemonic and operand.
+fn encode(model: Model, mnemonic: &str, operand: Operand) -> Result<u8, ErrorKind> {
+    let addr = RomAddr::new(u1::new(0), u4::new(0), u6::new(0));
+
+    (0..=u8::MAX)
+        .find(|&opcode| {
+            let inst = Instruction::decode(model, addr, opcode);
+            inst.mnemonic == mnemonic && inst.operand == operand
+        })
+        .ok_or(ErrorKind::InvalidOperand)
+}
+
+/// Parse an instruction into an item.
+fn instruction(
+    model: Model,
+    mnemonic: &str,
+    args: &[&str],
+    constants: &HashMap<String, u32>,
+) -> Result<Item, ErrorKind> {
+    let mnemonic = mnemonic.to_ascii_uppercase();
+    let addr = RomAddr::new(u1::new(0), u4::new(0), u6::new(0));
+
+    // Any opcode of the mnemonic, which determines the kind of operand.
+    let Some(sample) = (0..=u8::MAX)
+        .map(|opcode| Instruction::decode(model, addr, opcode))
+        .find(|inst| inst.mnemonic == mnemonic)
+    else {
+        return Err(ErrorKind::UnknownMnemonic(mnemonic));
+    };
+
+    if let Some(Fixed::Br | Fixed::Call) = model.fixed(sample.opcode) {
+        let [arg] = args else {
+            return Err(ErrorKind::Syntax);
+        };
+        let target = match parse_absolute(arg) {
+            Some(addr) => Target::Absolute(addr),
+            None if is_ident(arg) => Target::Label((*arg).to_string()),
+            None => return Err(ErrorKind::Syntax),
+        };
+
+        return Ok(Item::Branch {
+            base: sample.opcode & 0xc0,
+            target,
+        });
+    }
+
+    let operand = match (sample.operand, args) {
+        (Operand::None, []) => Operand::None,
+        (Operand::None, _) | (_, [] | [_, _, ..]) => return Err(ErrorKind::Syntax),
+        (kind, [arg]) => {
+            let value = eval(arg, constants)?;
+            let value = u8::try_from(value).map_err(|_| ErrorKind::InvalidOperand)?;
+            let operand = match kind {
+                Operand::Constant(_) => u4::try_new(value).map(Operand::Constant).ok(),
+                Operand::Page(_) => u4::try_new(value).map(Operand::Page).ok(),
+                Operand::Bit(_) => u2::try_new(value).map(Operand::Bit).ok(),
+                Operand::Register(_) => u3::try_new(value).map(Operand::Register).ok(),
+                Operand::None | Operand::Target(_) => None,
+            };
+            operand.ok_or(ErrorKind::InvalidOperand)?
+        }
+    };
+
+    encode(model, &mnemonic, operand).map(Item::Opcode)
+}
+
+/// Parse an absolute `chapter:page:address` branch target, returning its address.
+fn parse_absolute(arg: &str) -> Option<u6> {
+    let mut parts = arg.split(':');
+    let (Some(chapter), Some(page), Some(addr), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+
+    u8::from_str_radix(chapter, 16).ok().filter(|&c| c < 2)?;
+    u8::from_str_radix(page, 16).ok().filter(|&p| p < 16)?;
+    u6::try_new(u8::from_str_radix(addr, 16).ok()?).ok()
+}
+
+/// Evaluate an expression, a sum of numbers and constants, e.g. `SPEED + 1`.
+fn eval(expr: &str, constants: &HashMap<String, u32>) -> Result<u32, ErrorKind> {
+    let mut total: i64 = 0;
+    let mut sign = 1;
+    let mut expect_term = true;
+
+    for token in tokens(expr) {
+        match token {
+            "+" | "-" if !expect_term => {
+                sign = if token == "-" { -1 } else { 1 };
+                expect_term = true;
+            }
+            _ if expect_term => {
+                let value = if let Some(hex) =
+                    token.strip_prefix("0x").or_else(|| token.strip_prefix('$'))
+                {
+                    u32::from_str_radix(hex, 16).map_err(|_| ErrorKind::Syntax)?
+                } else if let Some(bin) = token.strip_prefix("0b") {
+                    u32::from_str_radix(bin, 2).map_err(|_| ErrorKind::Syntax)?
+                } else if token.starts_with(|c: char| c.is_ascii_digit()) {
+                    token.parse().map_err(|_| ErrorKind::Syntax)?
+                } else {
+                    *constants
+                        .get(token)
+                        .ok_or_else(|| ErrorKind::UnknownSymbol(token.to_string()))?
+                };
+                total += sign * i64::from(value);
+                expect_term = false;
+            }
+            _ => return Err(ErrorKind::Syntax),
+        }
+    }
+
+    if expect_term {
+        return Err(ErrorKind::Syntax);
+    }
+    u32::try_from(total).map_err(|_| ErrorKind::InvalidOperand)
+}
+
+/// Split an expression into its terms and operators.
+fn tokens(expr: &str) -> impl Iterator<Item = &str> {
+    let mut rest = expr.trim();
+    std::iter::from_fn(move || {
+        rest = rest.trim_start();
+        let first = rest.chars().next()?;
+        let len = if first == '+' || first == '-' {
+            1
+        } else {
+            rest.find(|c: char| c == '+' || c == '-' || c.is_whitespace())
+                .unwrap_or(rest.len())
+        };
+        let (token, remaining) = rest.split_at(len);
+        rest = remaining;
+        Some(token)
+    })
+}
+
+/// Split the arguments of a statement, which are separated by commas.
+fn split_args(args: &str) -> Vec<&str> {
+    if args.is_empty() {
+        return Vec::new();
+    }
+    args.split(',').map(str::trim).collect()
+}
+
+/// Check if the given text is a valid identifier, e.g. a label or constant name.
+fn is_ident(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Strip comments from the source and expand every macro invocation.
+fn expand(source: &str) -> Result<Vec<Line>, Error> {
+    let mut macros: HashMap<String, Macro> = HashMap::new();
+    let mut lines = Vec::new();
+    let mut invocations = 0;
+
+    let mut source = source
+        .lines()
+        .enumerate()
+        .map(|(idx, text)| (idx + 1, text.split(';').next().unwrap_or_default()));
+
+    while let Some((number, text)) = source.next() {
+        let mut words = text.split_whitespace();
+        if words
+            .next()
+            .is_some_and(|word| word.eq_ignore_ascii_case(".macro"))
+        {
+            let rest = text.trim_start()[".macro".len()..].trim();
+            let name = rest.split_whitespace().next().unwrap_or_default();
+            if !is_ident(name) {
+                return Err(Error::new(number, ErrorKind::Syntax));
+            }
+            let params = split_args(rest[name.len()..].trim())
+                .into_iter()
+                .map(str::to_string)
+                .collect();
+
+            let mut body = Vec::new();
+            loop {
+                let Some((_, text)) = source.next() else {
+                    return Err(Error::new(number, ErrorKind::UnterminatedMacro));
+                };
+                if text.trim().eq_ignore_ascii_case(".endm") {
+                    break;
+                }
+                body.push(text.to_string());
+            }
+
+            if macros
+                .insert(name.to_string(), Macro { params, body })
+                .is_some()
+            {
+                return Err(Error::new(
+                    number,
+                    ErrorKind::DuplicateSymbol(name.to_string()),
+                ));
+            }
+            continue;
+        }
+
+        invoke(&macros, number, text, 0, &mut invocations, &mut lines)?;
+    }
+
+    Ok(lines)
+}
+
+/// Expand a single line, which may invoke a macro.
+fn invoke(
+    macros: &HashMap<String, Macro>,
+    number: usize,
+    text: &str,
+    depth: usize,
+    invocations: &mut usize,
+    lines: &mut Vec<Line>,
+) -> Result<(), Error> {
+    // Split off a leading label, which stays on its own line.
+    let mut statement = text.trim();
+    if let Some((label, rest)) = statement.split_once(':') {
+        if is_ident(label.trim()) {
+            lines.push(Line {
+                number,
+                text: format!("{}:", label.trim()),
+            });
+            statement = rest.trim();
+        }
+    }
+
+    let name = statement.split_whitespace().next().unwrap_or_default();
+    let Some(mac) = macros.get(name) else {
+        lines.push(Line {
+            number,
+            text: statement.to_string(),
+        });
+        return Ok(());
+    };
+
+    if depth >= MAX_DEPTH {
+        return Err(Error::new(number, ErrorKind::MacroDepth));
+    }
+
+    let args = split_args(statement[name.len()..].trim());
+    if args.len() != mac.params.len() {
+        return Err(Error::new(number, ErrorKind::Syntax));
+    }
+
+    *invocations += 1;
+    let unique = format!("_{invocations}");
+
+    for body in &mac.body {
+        let text = substitute(&body.replace("\\@", &unique), &mac.params, &args);
+        invoke(macros, number, &text, depth + 1, invocations, lines)?;
+    }
+
+    Ok(())
+}
+
+/// Replace every identifier matching a parameter with its argument.
+fn substitute(text: &str, params: &[String], args: &[&str]) -> String {
+    let mut out = String::new();
+    let mut ident = String::new();
+
+    let flush = |ident: &mut String, out: &mut String| {
+        match params.iter().position(|param| param == ident) {
+            Some(idx) => out.push_str(args[idx]),
+            None => out.push_str(ident),
+        }
+        ident.clear();
+    };
+
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' || c == '.' {
+            ident.push(c);
+        } else {
+            flush(&mut ident, &mut out);
+            out.push(c);
+        }
+    }
+    flush(&mut ident, &mut out);
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use milton_core::tms1100::{
+        dasm::Disassembler,
+        pla::{instructions::*, Entry},
+    };
+
+    /// Assemble a single TMS1100 statement, returning the first opcode.
+    fn opcode(source: &str) -> u8 {
+        assemble(source, Model::Tms1100).unwrap().rom.data[0]
+    }
+
+    #[test]
+    fn opcode_table() {
+        let table = [
+            ("MNEA", MTP | ATN | NE, None),
+            ("ALEM", MTP | NATN | CIN | C8, None),
+            ("XMA", MTP | STO | AUTA, None),
+            ("DMAN", MTP | FTN | C8 | AUTA, None),
+            ("TKA", CKP | AUTA, None),
+            ("COMX", 0, Some(Fixed::Comx)),
+            ("COMC", 0, Some(Fixed::Comc)),
+            ("KNEZ", CKP | NE, None),
+            ("LDP 7", 0, Some(Fixed::Ldp)),
+            ("TAMIYC", STO | YTP | CIN | C8 | AUTY, None),
+            ("LDX 5", 0, Some(Fixed::Ldx)),
+            ("RBIT 1", 0, Some(Fixed::Rbit)),
+            ("TBIT1 3", CKP | CKN | MTP | NE, None),
+            ("CPAIZ", NATN | CIN | C8 | AUTA, None),
+            ("TCMIY 9", CKM | YTP | CIN | AUTY, None),
+            ("AC1AC 14", CKP | ATN | CIN | C8 | AUTA, None),
+            ("CLA", CKP | CIN | C8 | AUTA, None),
+            ("BR 0:0:2a", 0, Some(Fixed::Br)),
+            ("CALL 0:0:01", 0, Some(Fixed::Call)),
+        ];
+
+        for (source, entry, fixed) in table {
+            let op = opcode(source);
+            assert_eq!(Entry::decode(op), Entry::from(entry), "{source}");
+            assert_eq!(Fixed::decode(op), fixed, "{source}");
+        }
+
+        assert_eq!(opcode("TCY 3"), 0x4c);
+        assert_eq!(opcode("BR 0:0:2a"), 0xaa);
+    }
+
+    #[test]
+    fn every_opcode_round_trips() {
+        let addr = RomAddr::new(u1::new(0), u4::new(0), u6::new(0));
+        for op in 0..=u8::MAX {
+            let inst = Instruction::decode(Model::Tms1100, addr, op);
+            assert_eq!(opcode(&format!("{} {}", inst.mnemonic, inst.operand)), op);
+        }
+    }
+
+    #[test]
+    fn program_round_trips() {
+        let source = "
+            .equ    START, 3
+
+            .macro  COUNT value
+            again\\@: TCMIY value
+                    IMAC
+            .endm
+
+            .page   0
+            reset:  TCY START
+                    COUNT 5
+                    COUNT START + 1
+                    CALL far        ; another chapter
+                    BR reset
+
+            .page   17
+            far:    TDO
+                    BR near
+            near:   RETN
+        ";
+        let assembly = assemble(source, Model::Tms1100).unwrap();
+
+        let labels: Vec<String> = assembly.symbols().lines().map(str::to_string).collect();
+        assert_eq!(
+            labels,
+            [
+                "0:0:00 reset",
+                "0:0:01 again_1",
+                "0:0:07 again_2",
+                "1:1:00 far",
+                "1:1:03 near"
+            ]
+        );
+
+        // The call is surrounded by the page and chapter changes.
+        let listing: Vec<String> = Disassembler::new(&assembly.rom, Model::Tms1100)
+            .take(12)
+            .map(|inst| format!("{} {}", inst.mnemonic, inst.operand))
+            .collect();
+        assert_eq!(
+            listing,
+            [
+                "TCY 3",
+                "TCMIY 5",
+                "IMAC ",
+                "TCMIY 4",
+                "IMAC ",
+                "LDP 1",
+                "COMC ",
+                "CALL 1:1:00",
+                "COMC ",
+                "LDP 0",
+                "BR 0:0:00",
+                "MNEA "
+            ]
+        );
+
+        // Re-assembling the disassembly results in the same ROM.
+        let mut source = String::new();
+        for inst in Disassembler::new(&assembly.rom, Model::Tms1100) {
+            if inst.addr.addr().value() == 0 {
+                let page = inst.addr.chapter().value() << 4 | inst.addr.page().value();
+                writeln!(source, ".page {page}").unwrap();
+            }
+            writeln!(source, "{} {}", inst.mnemonic, inst.operand).unwrap();
+        }
+        let again = assemble(&source, Model::Tms1100).unwrap();
+        assert_eq!(again.rom.data, assembly.rom.data);
+    }
+
+    #[test]
+    fn rejects_invalid_source() {
+        let err = |source: &str| assemble(source, Model::Tms1100).unwrap_err();
+
+        assert_eq!(err("NOPE").kind, ErrorKind::UnknownMnemonic("NOPE".into()));
+        assert_eq!(err("\n BR nowhere").line, 2);
+        assert_eq!(err("TCY 16").kind, ErrorKind::InvalidOperand);
+        assert_eq!(err(".page 32").kind, ErrorKind::InvalidPage(32));
+        assert_eq!(err(&"CLA\n".repeat(65)).kind, ErrorKind::PageOverflow(0));
+        assert_eq!(
+            err(".macro LOOP\nLOOP\n.endm\nLOOP").kind,
+            ErrorKind::MacroDepth
+        );
+    }
+}
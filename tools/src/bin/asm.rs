@@ -0,0 +1,54 @@
+//! A command-line assembler for Microvision cartridge ROMs.
+//!
+//! # Usage
+//!
+//! `milton-asm <SOURCE> <ROM> [SYMBOLS]`, which assembles TMS1100 source into a
+//! ROM image, optionally writing a symbol file of every label.
+
+use std::{env, fs, process::ExitCode};
+
+use milton_core::tms1100::Model;
+use milton_tools::asm;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let (source, rom, symbols) = match args.as_slice() {
+        [source, rom] => (source, rom, None),
+        [source, rom, symbols] => (source, rom, Some(symbols)),
+        _ => {
+            eprintln!("usage: milton-asm <SOURCE> <ROM> [SYMBOLS]");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let text = match fs::read_to_string(source) {
+        Ok(text) => text,
+        Err(err) => {
+            eprintln!("error: unable to read {source}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let assembly = match asm::assemble(&text, Model::Tms1100) {
+        Ok(assembly) => assembly,
+        Err(err) => {
+            eprintln!("{source}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Err(err) = fs::write(rom, assembly.rom.data) {
+        eprintln!("error: unable to write {rom}: {err}");
+        return ExitCode::FAILURE;
+    }
+
+    if let Some(symbols) = symbols {
+        if let Err(err) = fs::write(symbols, assembly.symbols()) {
+            eprintln!("error: unable to write {symbols}: {err}");
+            return ExitCode::FAILURE;
+        }
+    }
+
+    ExitCode::SUCCESS
+}
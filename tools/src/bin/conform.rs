@@ -0,0 +1,64 @@
+//! A command-line conformance checker for Microvision cartridge ROMs.
+//!
+//! # Usage
+//!
+//! `milton-conform <ROM> <TRACE>`, which runs a TMS1100 ROM in lockstep with a
+//! reference trace, e.g. produced by MAME, and reports the first divergence.
+
+use std::{env, fs, process::ExitCode};
+
+use milton_core::tms1100::{mem::Rom, Model};
+use milton_tools::conform::Reference;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let [rom, trace] = args.as_slice() else {
+        eprintln!("usage: milton-conform <ROM> <TRACE>");
+        return ExitCode::FAILURE;
+    };
+
+    let data = match fs::read(rom) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("error: unable to read {rom}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if data.len() > Model::Tms1100.rom_size() {
+        eprintln!(
+            "error: the rom is {} bytes, larger than the {} bytes of the Tms1100",
+            data.len(),
+            Model::Tms1100.rom_size()
+        );
+        return ExitCode::FAILURE;
+    }
+
+    let text = match fs::read_to_string(trace) {
+        Ok(text) => text,
+        Err(err) => {
+            eprintln!("error: unable to read {trace}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let reference = match Reference::parse(&text) {
+        Ok(reference) => reference,
+        Err(err) => {
+            eprintln!("{trace}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut image = Rom::new();
+    image.copy(&data);
+
+    if let Err(err) = reference.check(&image, Model::Tms1100) {
+        eprintln!("{trace}: {err}");
+        return ExitCode::FAILURE;
+    }
+
+    println!("{} instructions match", reference.len());
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,92 @@
+//! A command-line disassembler for Microvision cartridge ROMs.
+//!
+//! # Usage
+//!
+//! `milton-dasm <ROM> [MODEL]`, where the model defaults to `tms1100`.
+
+use std::{
+    env, fs,
+    io::{self, BufWriter, Write},
+    process::ExitCode,
+};
+
+use milton_core::tms1100::{dasm::Disassembler, mem::Rom, Model};
+
+/// Parse the name of a TMS1000 family model.
+fn parse_model(name: &str) -> Option<Model> {
+    let model = match name.to_ascii_lowercase().as_str() {
+        "tms1000" => Model::Tms1000,
+        "tms1070" => Model::Tms1070,
+        "tms1100" => Model::Tms1100,
+        "tms1200" => Model::Tms1200,
+        "tms1270" => Model::Tms1270,
+        "tms1300" => Model::Tms1300,
+        "tms1370" => Model::Tms1370,
+        _ => return None,
+    };
+
+    Some(model)
+}
+
+/// Write the disassembly of a ROM, in execution order, separating every page.
+fn write_listing(rom: &Rom, model: Model, out: &mut impl Write) -> io::Result<()> {
+    for inst in Disassembler::new(rom, model) {
+        if inst.addr.addr().value() == 0 {
+            if inst.addr.full().value() != 0 {
+                writeln!(out)?;
+            }
+            writeln!(
+                out,
+                "; chapter {}, page {:x}",
+                inst.addr.chapter().value(),
+                inst.addr.page().value()
+            )?;
+        }
+        writeln!(out, "{inst}")?;
+    }
+
+    out.flush()
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let (path, model) = match args.as_slice() {
+        [path] => (path, Some(Model::Tms1100)),
+        [path, model] => (path, parse_model(model)),
+        _ => {
+            eprintln!("usage: milton-dasm <ROM> [MODEL]");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let Some(model) = model else {
+        eprintln!("error: unknown model, expected one of tms1000, tms1070, tms1100, tms1200, tms1270, tms1300 or tms1370");
+        return ExitCode::FAILURE;
+    };
+
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("error: unable to read {path}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if data.len() > model.rom_size() {
+        eprintln!(
+            "error: the rom is {} bytes, larger than the {} bytes of the {model:?}",
+            data.len(),
+            model.rom_size()
+        );
+        return ExitCode::FAILURE;
+    }
+
+    let mut rom = Rom::new();
+    rom.copy(&data);
+
+    // Writing stops silently if stdout is closed, e.g. when piped into `head`.
+    let _ = write_listing(&rom, model, &mut BufWriter::new(io::stdout().lock()));
+
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,84 @@
+//! A GDB Remote Serial Protocol server for Microvision cartridge ROMs.
+//!
+//! # Usage
+//!
+//! `milton-gdb <ROM> [ADDRESS]`, which debugs a cartridge, either a container or a raw
+//! ROM dump of a TMS1000 family micro-processor, waiting for a single GDB client to
+//! connect on the given address, `127.0.0.1:1234` by default.
+//! If the address is `-`, the protocol is spoken over the standard input and output
+//! instead, e.g. `target remote | milton-gdb game.bin -` from within GDB.
+
+use std::{env, fs, net::TcpListener, process::ExitCode};
+
+use milton_core::{cartridge::Cartridge, Console};
+use milton_tools::{
+    gdb::{Server, Stdio},
+    run,
+};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let (path, addr) = match args.as_slice() {
+        [path] => (path, "127.0.0.1:1234"),
+        [path, addr] => (path, addr.as_str()),
+        _ => {
+            eprintln!("usage: milton-gdb <ROM> [ADDRESS]");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("error: unable to read {path}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let cart = run::open(&data)
+        .map_err(|err| err.to_string())
+        .and_then(|opened| {
+            if let Some(warning) = opened.warning() {
+                eprintln!("{warning}");
+            }
+            Cartridge::try_new(opened.rom, opened.settings).map_err(|err| err.to_string())
+        });
+    let cart = match cart {
+        Ok(cart) => cart,
+        Err(err) => {
+            eprintln!("error: {path}: {err}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if cart.settings.cpu.model().is_none() {
+        eprintln!("error: {path}: only TMS1000 family micro-processors can be debugged");
+        return ExitCode::FAILURE;
+    }
+
+    let res = if addr == "-" {
+        Server::new(Stdio::new(), Console::new(), cart).serve()
+    } else {
+        let listener = match TcpListener::bind(addr) {
+            Ok(listener) => listener,
+            Err(err) => {
+                eprintln!("error: unable to listen on {addr}: {err}");
+                return ExitCode::FAILURE;
+            }
+        };
+
+        eprintln!("waiting for gdb on {addr}");
+        listener.accept().and_then(|(stream, _)| {
+            stream.set_nodelay(true)?;
+            Server::new(stream, Console::new(), cart).serve()
+        })
+    };
+
+    if let Err(err) = res {
+        eprintln!("error: {err}");
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
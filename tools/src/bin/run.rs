@@ -0,0 +1,177 @@
+//! A headless runner for Microvision cartridges, e.g. for scripts and CI.
+//!
+//! # Usage
+//!
+//! `milton-run <ROM> [OPTIONS]`, which runs a cartridge, either a container or a
+//! raw ROM dump, with the following options:
+//!
+//! - `--frames N` or `--micros N`, run for N frames (60 by default) or N
+//!   microseconds.
+//! - `--script FILE`, apply the scripted input and assertions of a file, see
+//!   [`milton_tools::run`].
+//! - `--cpu MODEL`, `--rotary` and `--pla normal|reversed`, override the settings
+//!   of the cartridge, which otherwise come from the container, the database of
+//!   known games, or the defaults.
+//! - `--pbm FILE`, `--png FILE`, `--wav FILE` and `--dump FILE`, write the final
+//!   frame, the final LCD panel, the audio and a RAM/register dump, where `-` is
+//!   the standard output.
+//! - `--trace FILE`, write every instruction executed by a TMS1100.
+//!
+//! The exit code is 0 if every assertion passed, 1 if an assertion failed and 2 on
+//! any other error.
+
+use std::{env, fs, io::Write, process::ExitCode};
+
+use milton_core::cartridge::{settings::OutputPla, Cartridge};
+use milton_tools::run::{self, Runner, Script, Stop, TextTracer};
+
+/// The sample rate of the rendered audio.
+const RATE: u32 = 44_100;
+
+/// The options of a run.
+#[derive(Default)]
+struct Options {
+    rom: Option<String>,
+    stop: Option<Stop>,
+    script: Option<String>,
+    settings: Vec<(String, Option<String>)>,
+    outputs: Vec<(String, String)>,
+}
+
+/// Parse the command-line options.
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("{arg} expects a value"))
+        };
+        let number = |value: String| {
+            value
+                .parse::<usize>()
+                .map_err(|_| format!("{arg} expects a number"))
+        };
+
+        match arg.as_str() {
+            "--frames" => options.stop = Some(Stop::Frames(number(value()?)?)),
+            "--micros" => options.stop = Some(Stop::Micros(number(value()?)?)),
+            "--script" => options.script = Some(value()?),
+            "--cpu" | "--pla" => options.settings.push((arg.clone(), Some(value()?))),
+            "--rotary" => options.settings.push((arg.clone(), None)),
+            "--pbm" | "--png" | "--wav" | "--dump" | "--trace" => {
+                options.outputs.push((arg.clone(), value()?));
+            }
+            _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+            _ if options.rom.is_none() => options.rom = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument {arg}")),
+        }
+    }
+
+    Ok(options)
+}
+
+/// Load a cartridge, either a container or a raw ROM dump.
+fn load_cartridge(
+    data: &[u8],
+    overrides: &[(String, Option<String>)],
+) -> Result<Cartridge, String> {
+    let opened = run::open(data).map_err(|err| err.to_string())?;
+    if let Some(title) = opened.title {
+        eprintln!("found {title}");
+    }
+    if let Some(warning) = opened.warning() {
+        eprintln!("{warning}");
+    }
+    let mut settings = opened.settings;
+
+    for (option, value) in overrides {
+        match (option.as_str(), value.as_deref()) {
+            ("--cpu", Some(name)) => {
+                settings.cpu = run::parse_cpu(name).ok_or_else(|| format!("unknown cpu {name}"))?;
+            }
+            ("--pla", Some("normal")) => settings.output_pla = OutputPla::NORMAL,
+            ("--pla", Some("reversed")) => settings.output_pla = OutputPla::REVERSED,
+            ("--rotary", None) => settings.rotary_enabled = true,
+            (option, _) => return Err(format!("invalid value for {option}")),
+        }
+    }
+
+    Cartridge::try_new(opened.rom, settings).map_err(|err| err.to_string())
+}
+
+/// Write an output file, where `-` is the standard output.
+fn write_output(path: &str, data: &[u8]) -> Result<(), String> {
+    let res = if path == "-" {
+        std::io::stdout().lock().write_all(data)
+    } else {
+        fs::write(path, data)
+    };
+
+    res.map_err(|err| format!("unable to write {path}: {err}"))
+}
+
+/// Run a cartridge, returning if every assertion passed.
+fn run(options: &Options) -> Result<bool, String> {
+    let Some(path) = &options.rom else {
+        return Err("usage: milton-run <ROM> [OPTIONS]".into());
+    };
+
+    let data = fs::read(path).map_err(|err| format!("unable to read {path}: {err}"))?;
+    let cart = load_cartridge(&data, &options.settings).map_err(|err| format!("{path}: {err}"))?;
+
+    let script = match &options.script {
+        Some(path) => {
+            let text =
+                fs::read_to_string(path).map_err(|err| format!("unable to read {path}: {err}"))?;
+            Script::parse(&text).map_err(|err| format!("{path}: {err}"))?
+        }
+        None => Script::default(),
+    };
+
+    let mut runner = Runner::new(cart, RATE);
+    let stop = options.stop.unwrap_or(Stop::Frames(60));
+    let mut tracer = TextTracer::default();
+    let tracing = options
+        .outputs
+        .iter()
+        .any(|(option, _)| option == "--trace");
+
+    let failures = if tracing {
+        runner.run(&script, stop, &mut tracer)
+    } else {
+        runner.run(&script, stop, &mut ())
+    };
+
+    for failure in &failures {
+        eprintln!("assertion failed: {failure}");
+    }
+
+    for (option, path) in &options.outputs {
+        let data = match option.as_str() {
+            "--pbm" => run::pbm(&runner.framebuffer),
+            "--png" => run::png(&runner.console.lcd),
+            "--wav" => run::wav(&runner.audio, RATE),
+            "--dump" => runner.dump().into_bytes(),
+            _ => std::mem::take(&mut tracer.text).into_bytes(),
+        };
+        write_output(path, &data)?;
+    }
+
+    Ok(failures.is_empty())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match parse_options(&args).and_then(|options| run(&options)) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::from(2)
+        }
+    }
+}
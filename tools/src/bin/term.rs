@@ -0,0 +1,156 @@
+//! An interactive terminal frontend for Microvision cartridges.
+//!
+//! # Usage
+//!
+//! `milton-term <ROM>`, which plays a cartridge, either a container or a raw ROM
+//! dump, within the terminal, see [`milton_tools::term`] for the controls.
+//!
+//! The terminal is switched to raw mode using `stty`, and must support 24-bit
+//! colours.
+
+use std::{
+    env, fs,
+    io::{self, Read, Write},
+    process::{Command as Process, ExitCode, Stdio},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use milton_core::{cartridge::Cartridge, common::FRAME_CLOCKS, Console};
+use milton_tools::{
+    run,
+    term::{Decoder, Terminal},
+};
+
+/// The duration of a single frame.
+const FRAME: Duration = Duration::from_micros(1_000_000 / 60);
+
+/// A terminal in raw mode, which is restored when dropped.
+struct RawMode {
+    /// The settings of the terminal before entering raw mode, as printed by `stty -g`.
+    saved: String,
+}
+
+impl RawMode {
+    /// Switch the terminal to raw mode, and to the alternate screen.
+    fn enter() -> Result<Self, String> {
+        let output = Process::new("stty")
+            .arg("-g")
+            .stdin(Stdio::inherit())
+            .output()
+            .map_err(|err| format!("unable to run stty: {err}"))?;
+        if !output.status.success() {
+            return Err("the standard input is not a terminal".into());
+        }
+
+        stty(&["raw", "-echo"])?;
+        print!("\x1b[?1049h\x1b[?25l\x1b[2J");
+
+        Ok(Self {
+            saved: String::from_utf8_lossy(&output.stdout).trim().into(),
+        })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        print!("\x1b[0m\x1b[?25h\x1b[?1049l");
+        let _ = io::stdout().flush();
+        let _ = stty(&[&self.saved]);
+    }
+}
+
+/// Run `stty` on the terminal with the given arguments.
+fn stty(args: &[&str]) -> Result<(), String> {
+    let status = Process::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .status()
+        .map_err(|err| format!("unable to run stty: {err}"))?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err("unable to configure the terminal".into())
+    }
+}
+
+/// Play a cartridge until the user quits.
+fn play(mut cart: Cartridge) -> Result<(), String> {
+    let _raw = RawMode::enter()?;
+
+    // Reading the standard input blocks, so the bytes are forwarded by a thread.
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        for byte in io::stdin().lock().bytes() {
+            match byte {
+                Ok(byte) if tx.send(byte).is_ok() => {}
+                _ => break,
+            }
+        }
+    });
+
+    let (mut console, mut term) = (Console::new(), Terminal::new());
+    let mut decoder = Decoder::new();
+    let mut stdout = io::stdout().lock();
+    let mut deadline = Instant::now();
+
+    loop {
+        while let Ok(byte) = rx.try_recv() {
+            if let Some(command) = decoder.decode(byte) {
+                if !term.controls.apply(command) {
+                    return Ok(());
+                }
+            }
+        }
+
+        for _ in 0..FRAME_CLOCKS {
+            console.clock(&mut cart, term.interface());
+        }
+        console.sync(term.interface());
+        term.controls.end_frame();
+        decoder.end_frame();
+
+        let frame = term.render(|x, y| console.lcd.brightness(x, y));
+        stdout
+            .write_all(frame.as_bytes())
+            .and_then(|()| stdout.flush())
+            .map_err(|err| format!("unable to write to the terminal: {err}"))?;
+
+        deadline += FRAME;
+        match deadline.checked_duration_since(Instant::now()) {
+            Some(wait) => thread::sleep(wait),
+            // Do not try to catch up after falling behind.
+            None => deadline = Instant::now(),
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let [path] = args.as_slice() else {
+        eprintln!("usage: milton-term <ROM>");
+        return ExitCode::FAILURE;
+    };
+
+    let res = fs::read(path)
+        .map_err(|err| format!("unable to read {path}: {err}"))
+        .and_then(|data| {
+            let opened = run::open(&data).map_err(|err| format!("{path}: {err}"))?;
+            if let Some(warning) = opened.warning() {
+                eprintln!("{warning}");
+            }
+            Cartridge::try_new(opened.rom, opened.settings).map_err(|err| format!("{path}: {err}"))
+        })
+        .and_then(play);
+
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
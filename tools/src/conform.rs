@@ -0,0 +1,381 @@
+//! A differential conformance harness, comparing the TMS1100 against reference traces.
+//!
+//! # Reference traces
+//!
+//! A reference trace lists every executed instruction, one per line, in the format
+//! of the execution tracer, see [`Record`]:
+//!
+//! ```text
+//! 007: TAM      A=1 X=0 Y=4 PC=0F SR=00 PA=0 PB=0 CA=0 CB=0 CS=0 SL=0 R=0000 O=00 M[0:4]=1
+//! ```
+//!
+//! Every line begins with the full ROM address and the disassembled instruction,
+//! which is followed by any amount of `NAME=VALUE` fields holding the state after the
+//! instruction executed. Only the given fields are compared, which allows comparing
+//! against the trace output of MAME, which only lists the instructions, as well as
+//! hand-made golden traces. If any field is given, the RAM write of the instruction
+//! (`M[X:Y]=VALUE`) is compared as well, i.e. a missing field means nothing has been
+//! written.
+//!
+//! Blank lines, lines beginning with `#` and anything following a `;` are ignored,
+//! which allows annotating every line of a hand-made trace.
+
+use std::fmt;
+
+use crate::Headless;
+
+use milton_core::{
+    cartridge::{
+        settings::{ChargeInfo, OutputPla, Settings},
+        Cartridge,
+    },
+    common::Interface,
+    tms1100::{
+        mem::{Ram, Rom},
+        trace::{Record, Tracer},
+        Model,
+    },
+    Console,
+};
+
+/// The names of the register fields.
+const REGISTERS: [&str; 10] = ["A", "X", "Y", "PC", "SR", "PA", "PB", "CA", "CB", "CS"];
+
+/// A part of the micro-processor state which can diverge from a reference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Field {
+    /// The address of the executed instruction, i.e. the branch behaviour.
+    Address,
+    /// The executed instruction.
+    Instruction,
+    /// A register, e.g. `A` or `PC`.
+    Register(String),
+    /// The `SL` status latch.
+    Status,
+    /// The `R` output pins.
+    R,
+    /// The `O` output pins.
+    O,
+    /// The RAM written by the instruction.
+    Ram,
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Address => write!(f, "address (branch behaviour)"),
+            Self::Instruction => write!(f, "instruction"),
+            Self::Register(name) => write!(f, "register {name}"),
+            Self::Status => write!(f, "status latch"),
+            Self::R => write!(f, "r pins"),
+            Self::O => write!(f, "o pins"),
+            Self::Ram => write!(f, "ram write"),
+        }
+    }
+}
+
+/// The first divergence from a reference trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// The (1-based) line of the reference trace.
+    pub line: usize,
+    /// The diverging field.
+    pub field: Field,
+    /// The expected value of the field.
+    pub expected: String,
+    /// The value of the field found in the emulated trace.
+    pub found: String,
+    /// The full line of the emulated trace.
+    pub trace: String,
+}
+
+/// An error encountered while checking a reference trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The reference trace contains a malformed line.
+    Syntax {
+        /// The (1-based) line the error is on.
+        line: usize,
+    },
+    /// The emulated trace diverged from the reference trace.
+    Diverged(Box<Divergence>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax { line } => write!(f, "line {line}: malformed trace line"),
+            Self::Diverged(div) => write!(
+                f,
+                "line {}: the {} diverged, expected {}, found {}\n  found: {}",
+                div.line, div.field, div.expected, div.found, div.trace
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A single line of a trace.
+#[derive(Debug, Clone)]
+struct Step {
+    /// The full ROM address of the instruction.
+    addr: u32,
+    /// The normalized instruction, e.g. `TCY 3`.
+    inst: String,
+    /// The fields holding the state after the instruction executed.
+    fields: Vec<(String, String)>,
+}
+
+impl Step {
+    /// Parse a single line of a trace.
+    fn parse(text: &str) -> Option<Self> {
+        let (addr, rest) = text.split_once(':')?;
+        let addr = u32::from_str_radix(addr.trim(), 16).ok()?;
+
+        let (mut inst, mut fields) = (Vec::new(), Vec::new());
+        for token in rest.split_whitespace() {
+            match token.split_once('=') {
+                Some((name, val)) => {
+                    let name = name.to_ascii_uppercase();
+                    let known = REGISTERS.contains(&name.as_str())
+                        || matches!(name.as_str(), "SL" | "R" | "O")
+                        || (name.starts_with("M[") && name.ends_with(']'));
+                    if !known || val.is_empty() {
+                        return None;
+                    }
+                    fields.push((name, val.to_ascii_uppercase()));
+                }
+                None if fields.is_empty() => inst.push(token.to_ascii_uppercase()),
+                None => return None,
+            }
+        }
+
+        if inst.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            addr,
+            inst: inst.join(" "),
+            fields,
+        })
+    }
+
+    /// Return the value of the given field.
+    fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(other, _)| other == name)
+            .map(|(_, val)| val.as_str())
+    }
+
+    /// Return the RAM write of this step, formatted as `M[X:Y]=VALUE`.
+    fn ram(&self) -> String {
+        self.fields
+            .iter()
+            .find(|(name, _)| name.starts_with("M["))
+            .map_or_else(|| "none".into(), |(name, val)| format!("{name}={val}"))
+    }
+
+    /// Compare the given (emulated) step against this (expected) step.
+    fn compare(&self, found: &Self) -> Option<(Field, String, String)> {
+        if self.addr != found.addr {
+            return Some((
+                Field::Address,
+                format!("{:03X}", self.addr),
+                format!("{:03X}", found.addr),
+            ));
+        }
+        if self.inst != found.inst {
+            return Some((Field::Instruction, self.inst.clone(), found.inst.clone()));
+        }
+
+        for (name, expected) in &self.fields {
+            let field = match name.as_str() {
+                "SL" => Field::Status,
+                "R" => Field::R,
+                "O" => Field::O,
+                name if name.starts_with("M[") => continue,
+                name => Field::Register(name.into()),
+            };
+
+            let found = found.field(name).unwrap_or_default();
+            let same = match (parse_hex(expected), parse_hex(found)) {
+                (Some(expected), Some(found)) => expected == found,
+                _ => expected == found,
+            };
+            if !same {
+                return Some((field, expected.clone(), found.into()));
+            }
+        }
+
+        let (expected, found) = (self.ram(), found.ram());
+        if !self.fields.is_empty() && expected != found {
+            return Some((Field::Ram, expected, found));
+        }
+
+        None
+    }
+}
+
+/// A reference trace.
+#[derive(Debug, Clone)]
+pub struct Reference {
+    /// The steps of the trace, along with their (1-based) line.
+    steps: Vec<(usize, Step)>,
+}
+
+impl Reference {
+    /// Parse a reference trace.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the trace contains a malformed line.
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut steps = Vec::new();
+
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap_or_default().trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let step = Step::parse(line).ok_or(Error::Syntax { line: idx + 1 })?;
+            steps.push((idx + 1, step));
+        }
+
+        Ok(Self { steps })
+    }
+
+    /// Return the amount of instructions in this trace.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// Check if this trace is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Run the given ROM, in lockstep with this trace, from a reset with cleared RAM.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error at the first divergence from this trace.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the output of the tracer can not be parsed, which
+    /// would be a bug.
+    pub fn check(&self, rom: &Rom, model: Model) -> Result<(), Error> {
+        let mut cart = Cartridge {
+            rom: rom.clone(),
+            ram: Ram::new(),
+            settings: Settings {
+                cpu: model.into(),
+                charge_info: ChargeInfo::default(),
+                output_pla: OutputPla::default(),
+                micro_pla: None,
+                rotary_enabled: false,
+            },
+        };
+        let mut console = Console::new();
+        let mut last = Last(None);
+        let (mut display, mut buzzer) = (Headless, Headless);
+
+        for (line, expected) in &self.steps {
+            let record = loop {
+                let hardware = Interface {
+                    display: &mut display,
+                    buzzer: &mut buzzer,
+                    keypad: &Headless,
+                    rotary: &Headless,
+                };
+                console.clock_traced(&mut cart, hardware, &mut last);
+
+                if let Some(record) = last.0.take() {
+                    break record;
+                }
+            };
+
+            let trace = record.to_string();
+            let found = Step::parse(&trace).expect("the tracer output is well-formed");
+
+            if let Some((field, expected, found)) = expected.compare(&found) {
+                return Err(Error::Diverged(Box::new(Divergence {
+                    line: *line,
+                    field,
+                    expected,
+                    found,
+                    trace,
+                })));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Parse a hexadecimal number.
+fn parse_hex(text: &str) -> Option<u32> {
+    u32::from_str_radix(text, 16).ok()
+}
+
+/// A tracer keeping the last record.
+struct Last(Option<Record>);
+
+impl Tracer for Last {
+    fn trace(&mut self, record: &Record) {
+        self.0 = Some(*record);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    #[test]
+    fn reports_first_divergence() {
+        let rom = asm::assemble("start: TCY 3\nTCMIY 5\nIMAC\nBR start", Model::Tms1100)
+            .unwrap()
+            .rom;
+
+        // Only the instructions, as traced by MAME.
+        let reference =
+            Reference::parse("000: TCY 3\n001: TCMIY 5\n003: IMAC\n007: BR $00").unwrap();
+        assert_eq!(reference.len(), 4);
+        assert_eq!(reference.check(&rom, Model::Tms1100), Ok(()));
+
+        let reference = Reference::parse(
+            "# a comment\n000: TCY 3 Y=3 ; Y <- 3\n001: TCMIY 5 Y=4 M[0:3]=5\n003: IMAC A=2",
+        )
+        .unwrap();
+        let Err(Error::Diverged(div)) = reference.check(&rom, Model::Tms1100) else {
+            panic!("expected a divergence");
+        };
+        assert_eq!(div.line, 4);
+        assert_eq!(div.field, Field::Register("A".into()));
+        assert_eq!((div.expected.as_str(), div.found.as_str()), ("2", "1"));
+
+        let reference = Reference::parse("000: TCY 3\n001: TCMIY 5 Y=4").unwrap();
+        let Err(Error::Diverged(div)) = reference.check(&rom, Model::Tms1100) else {
+            panic!("expected a divergence");
+        };
+        assert_eq!(div.field, Field::Ram);
+
+        let reference = Reference::parse("000: TCY 3\n003: IMAC").unwrap();
+        let Err(Error::Diverged(div)) = reference.check(&rom, Model::Tms1100) else {
+            panic!("expected a divergence");
+        };
+        assert_eq!(div.field, Field::Address);
+
+        assert_eq!(
+            Reference::parse("000: TCY 3\nnonsense").unwrap_err(),
+            Error::Syntax { line: 2 }
+        );
+    }
+}
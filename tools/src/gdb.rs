@@ -0,0 +1,829 @@
+//! A GDB Remote Serial Protocol server for the TMS1100.
+//!
+//! # Registers
+//!
+//! Every register of the [`Tms1100`] is exposed as an 8-bit register, in the order of
+//! [`REGISTERS`]. The additional, read-only, 16-bit `addr` register holds the full ROM
+//! address of the current instruction, as the `PC` register has already been
+//! incremented once an instruction is executed.
+//!
+//! # Memory
+//!
+//! GDB only knows a single address space, therefore the ROM and RAM are mapped into
+//! it as follows:
+//!
+//! - `0x0000..0x0800`, the ROM, indexed by the full [`RomAddr`].
+//! - `0x1000..0x1080`, the RAM, indexed by the full [`RamAddr`], one nibble per byte.
+//!
+//! # Execution
+//!
+//! Breakpoints (`Z0`/`Z1`) are placed on ROM addresses, watchpoints (`Z2`, `Z3` and
+//! `Z4`) on RAM addresses. Single-stepping (`s`) executes a whole instruction, while
+//! continuing (`c`) runs frame by frame, as fast as possible, until a breakpoint or
+//! watchpoint is reached or GDB interrupts the target.
+
+use std::{
+    fmt::Write as _,
+    io::{self, Read, Stdin, Stdout, Write},
+    net::TcpStream,
+};
+
+use crate::Headless;
+
+use milton_core::{
+    cartridge::Cartridge,
+    common::Interface,
+    debug::{Access, Debugger, Step, Stop},
+    tms1100::{
+        mem::{RamAddr, RomAddr},
+        Tms1100,
+    },
+    Console, Cpu,
+};
+
+use arbitrary_int::{u1, u3, u4, u6};
+
+/// The base address of the ROM.
+pub const ROM_BASE: u32 = 0x0000;
+
+/// The base address of the RAM.
+pub const RAM_BASE: u32 = 0x1000;
+
+/// The names of the 8-bit registers, in the order they are exposed to GDB.
+pub const REGISTERS: [&str; 11] = [
+    "a", "x", "y", "pc", "sr", "pa", "pb", "ca", "cb", "cs", "status",
+];
+
+/// The maximum size of a packet.
+const PACKET_SIZE: usize = 0x1000;
+
+/// The target description, announcing the registers to GDB.
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.milton.tms1100">
+    <reg name="a" bitsize="8" regnum="0"/>
+    <reg name="x" bitsize="8"/>
+    <reg name="y" bitsize="8"/>
+    <reg name="pc" bitsize="8"/>
+    <reg name="sr" bitsize="8"/>
+    <reg name="pa" bitsize="8"/>
+    <reg name="pb" bitsize="8"/>
+    <reg name="ca" bitsize="8"/>
+    <reg name="cb" bitsize="8"/>
+    <reg name="cs" bitsize="8"/>
+    <reg name="status" bitsize="8"/>
+    <reg name="addr" bitsize="16" type="code_ptr"/>
+  </feature>
+</target>
+"#;
+
+/// A connection to a GDB client.
+pub trait Connection: Read + Write {
+    /// Read a single byte sent by the client, if one is available, without blocking.
+    ///
+    /// This allows the client to interrupt the target while it is running.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the connection fails.
+    fn poll(&mut self) -> io::Result<Option<u8>>;
+}
+
+impl Connection for TcpStream {
+    fn poll(&mut self) -> io::Result<Option<u8>> {
+        self.set_nonblocking(true)?;
+        let mut byte = [0];
+        let res = self.read(&mut byte);
+        self.set_nonblocking(false)?;
+
+        match res {
+            Ok(len) => Ok((len == 1).then_some(byte[0])),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// A connection over the standard input and output streams.
+///
+/// As the standard input can not be polled, the target can not be interrupted.
+#[derive(Debug)]
+pub struct Stdio {
+    /// The standard input stream.
+    stdin: Stdin,
+    /// The standard output stream.
+    stdout: Stdout,
+}
+
+impl Stdio {
+    /// Create a new connection over the standard input and output streams.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            stdin: io::stdin(),
+            stdout: io::stdout(),
+        }
+    }
+}
+
+impl Read for Stdio {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stdin.read(buf)
+    }
+}
+
+impl Write for Stdio {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stdout.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()
+    }
+}
+
+impl Connection for Stdio {
+    fn poll(&mut self) -> io::Result<Option<u8>> {
+        Ok(None)
+    }
+}
+
+/// A GDB Remote Serial Protocol server, debugging a single console.
+#[derive(Debug)]
+pub struct Server<C: Connection> {
+    /// The connection to the client.
+    conn: C,
+    /// The debugged console.
+    pub console: Console,
+    /// The cartridge inserted into the debugged console.
+    pub cart: Cartridge,
+    /// The debugger controlling the console.
+    pub debugger: Debugger,
+    /// A flag determining if packets are acknowledged.
+    ack: bool,
+    /// The reply to the last reason the target stopped.
+    stop: String,
+    /// A byte received while checking for an interrupt, which is read before any
+    /// other byte of the connection.
+    pending: Option<u8>,
+}
+
+impl<C: Connection> Server<C> {
+    /// Create a new server, debugging the given console and cartridge.
+    pub fn new(conn: C, console: Console, cart: Cartridge) -> Self {
+        Self {
+            conn,
+            console,
+            cart,
+            debugger: Debugger::new(),
+            ack: true,
+            stop: "S05".into(),
+            pending: None,
+        }
+    }
+
+    /// Serve the client until it detaches, kills the target or disconnects.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the connection fails.
+    pub fn serve(&mut self) -> io::Result<()> {
+        while let Some(packet) = self.recv()? {
+            match packet.as_str() {
+                "k" => break,
+                "D" => {
+                    self.send("OK")?;
+                    break;
+                }
+                "QStartNoAckMode" => {
+                    self.send("OK")?;
+                    self.ack = false;
+                }
+                _ => {
+                    let reply = self.handle(&packet)?;
+                    self.send(&reply)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read a single byte from the connection, or [None] if it has been closed.
+    fn byte(&mut self) -> io::Result<Option<u8>> {
+        if let Some(byte) = self.pending.take() {
+            return Ok(Some(byte));
+        }
+
+        let mut byte = [0];
+        match self.conn.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    /// Receive the next packet, or [None] if the connection has been closed.
+    fn recv(&mut self) -> io::Result<Option<String>> {
+        loop {
+            // Skip acknowledgements and interrupts outside of packets.
+            loop {
+                match self.byte()? {
+                    Some(b'$') => break,
+                    Some(_) => {}
+                    None => return Ok(None),
+                }
+            }
+
+            // The checksum is computed over the data as sent, i.e. escaped.
+            let (mut data, mut sum) = (Vec::new(), 0u8);
+            let mut escaped = false;
+            loop {
+                let Some(byte) = self.byte()? else {
+                    return Ok(None);
+                };
+                if byte == b'#' && !escaped {
+                    break;
+                }
+
+                sum = sum.wrapping_add(byte);
+                if escaped {
+                    data.push(byte ^ 0x20);
+                    escaped = false;
+                } else if byte == b'}' {
+                    escaped = true;
+                } else {
+                    data.push(byte);
+                }
+            }
+
+            let (Some(hi), Some(lo)) = (self.byte()?, self.byte()?) else {
+                return Ok(None);
+            };
+            let valid = std::str::from_utf8(&[hi, lo])
+                .ok()
+                .and_then(|text| u8::from_str_radix(text, 16).ok())
+                == Some(sum);
+
+            if self.ack {
+                self.conn.write_all(if valid { b"+" } else { b"-" })?;
+                self.conn.flush()?;
+            }
+            if valid || !self.ack {
+                return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+            }
+        }
+    }
+
+    /// Send a packet, resending it until it has been acknowledged.
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${data}#{:02x}", checksum(data.as_bytes()));
+
+        loop {
+            self.conn.write_all(packet.as_bytes())?;
+            self.conn.flush()?;
+
+            if !self.ack {
+                return Ok(());
+            }
+            loop {
+                match self.byte()? {
+                    Some(b'+') | None => return Ok(()),
+                    Some(b'-') => break,
+                    Some(_) => {}
+                }
+            }
+        }
+    }
+
+    /// Handle a single packet, returning the reply.
+    fn handle(&mut self, packet: &str) -> io::Result<String> {
+        let (kind, args) = packet.split_at(packet.len().min(1));
+
+        let reply = match kind {
+            "?" => self.stop.clone(),
+            "q" => query(args),
+            "H" => "OK".into(),
+            "g" => self.read_registers(),
+            "G" => self.write_registers(args),
+            "p" => self.read_register(args),
+            "P" => self.write_register(args),
+            "m" => self.read_memory(args),
+            "M" => self.write_memory(args),
+            "Z" => self.breakpoint(args, true),
+            "z" => self.breakpoint(args, false),
+            "s" => self.resume(Step::Instruction)?,
+            "c" => self.resume(Step::Frame)?,
+            _ => String::new(),
+        };
+
+        Ok(reply)
+    }
+
+    /// Return the debugged micro-processor, if it is a TMS1100.
+    fn cpu(&mut self) -> Option<&mut Tms1100> {
+        match &mut self.console.cpu {
+            Cpu::Tms1100(cpu) => Some(cpu),
+            Cpu::I8021(_) => None,
+        }
+    }
+
+    /// Handle a `g` packet, reading all registers.
+    fn read_registers(&mut self) -> String {
+        let Some(cpu) = self.cpu() else {
+            return "E01".into();
+        };
+
+        let mut reply = String::new();
+        for idx in 0..REGISTERS.len() {
+            let _ = write!(reply, "{:02x}", register(cpu, idx));
+        }
+        let addr = cpu.addr().full().value();
+        let _ = write!(reply, "{:02x}{:02x}", addr & 0xff, addr >> 8);
+        reply
+    }
+
+    /// Handle a `G` packet, writing all registers.
+    fn write_registers(&mut self, args: &str) -> String {
+        let Some(values) = decode_hex(args) else {
+            return "E01".into();
+        };
+        let Some(cpu) = self.cpu() else {
+            return "E01".into();
+        };
+        if values.len() < REGISTERS.len() {
+            return "E01".into();
+        }
+
+        for (idx, val) in values.into_iter().take(REGISTERS.len()).enumerate() {
+            set_register(cpu, idx, val);
+        }
+        "OK".into()
+    }
+
+    /// Handle a `p` packet, reading a single register.
+    fn read_register(&mut self, args: &str) -> String {
+        let (Some(idx), Some(cpu)) = (parse_hex(args), self.cpu()) else {
+            return "E01".into();
+        };
+
+        match idx as usize {
+            idx if idx < REGISTERS.len() => format!("{:02x}", register(cpu, idx)),
+            idx if idx == REGISTERS.len() => {
+                let addr = cpu.addr().full().value();
+                format!("{:02x}{:02x}", addr & 0xff, addr >> 8)
+            }
+            _ => "E01".into(),
+        }
+    }
+
+    /// Handle a `P` packet, writing a single register.
+    fn write_register(&mut self, args: &str) -> String {
+        let Some((idx, val)) = args.split_once('=') else {
+            return "E01".into();
+        };
+        let (Some(idx), Some(val), Some(cpu)) = (parse_hex(idx), decode_hex(val), self.cpu())
+        else {
+            return "E01".into();
+        };
+
+        match (idx as usize, val.first()) {
+            (idx, Some(&val)) if idx < REGISTERS.len() => {
+                set_register(cpu, idx, val);
+                "OK".into()
+            }
+            // The `addr` register is read-only.
+            (idx, _) if idx == REGISTERS.len() => "OK".into(),
+            _ => "E01".into(),
+        }
+    }
+
+    /// Handle a `m` packet, reading memory.
+    fn read_memory(&self, args: &str) -> String {
+        let Some((addr, len)) = parse_range(args) else {
+            return "E01".into();
+        };
+
+        let mut reply = String::new();
+        for addr in addr..addr.saturating_add(len) {
+            let Some(val) = self.peek(addr) else {
+                break;
+            };
+            let _ = write!(reply, "{val:02x}");
+        }
+
+        if reply.is_empty() && len != 0 {
+            "E14".into()
+        } else {
+            reply
+        }
+    }
+
+    /// Handle a `M` packet, writing memory.
+    fn write_memory(&mut self, args: &str) -> String {
+        let Some((range, data)) = args.split_once(':') else {
+            return "E01".into();
+        };
+        let (Some((addr, len)), Some(data)) = (parse_range(range), decode_hex(data)) else {
+            return "E01".into();
+        };
+        if data.len() != len as usize {
+            return "E01".into();
+        }
+
+        for (addr, val) in (addr..).zip(data) {
+            if !self.poke(addr, val) {
+                return "E14".into();
+            }
+        }
+        "OK".into()
+    }
+
+    /// Read a byte from the given address.
+    fn peek(&self, addr: u32) -> Option<u8> {
+        rom_addr(addr).map_or_else(
+            || ram_addr(addr).map(|addr| self.cart.ram.read(addr).value()),
+            |addr| Some(self.cart.rom.read(addr)),
+        )
+    }
+
+    /// Write a byte to the given address, returning `false` if it is not mapped.
+    fn poke(&mut self, addr: u32, val: u8) -> bool {
+        if let Some(addr) = rom_addr(addr) {
+            self.cart.rom.data[addr.full().value() as usize] = val;
+            true
+        } else if let Some(addr) = ram_addr(addr) {
+            self.cart.ram.write(addr, u4::new(val & 0xf));
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Handle a `Z` or `z` packet, inserting or removing a break/watchpoint.
+    fn breakpoint(&mut self, args: &str, insert: bool) -> String {
+        let mut parts = args.split(',');
+        let (Some(kind), Some(addr)) = (parts.next(), parts.next().and_then(parse_hex)) else {
+            return "E01".into();
+        };
+
+        let access = match kind {
+            "0" | "1" => {
+                let Some(addr) = rom_addr(addr) else {
+                    return "E01".into();
+                };
+                if !insert {
+                    self.debugger.remove_breakpoint(addr);
+                    return "OK".into();
+                }
+                return match self.debugger.add_breakpoint(addr) {
+                    Ok(()) => "OK".into(),
+                    Err(_) => "E02".into(),
+                };
+            }
+            "2" => Access::Write,
+            "3" => Access::Read,
+            "4" => Access::ReadWrite,
+            _ => return String::new(),
+        };
+
+        let Some(addr) = ram_addr(addr) else {
+            return "E01".into();
+        };
+        if !insert {
+            self.debugger.remove_watchpoint(addr);
+            return "OK".into();
+        }
+        match self.debugger.add_watchpoint(addr, access) {
+            Ok(()) => "OK".into(),
+            Err(_) => "E02".into(),
+        }
+    }
+
+    /// Handle a `s` or `c` packet, running the console until it stops.
+    fn resume(&mut self, step: Step) -> io::Result<String> {
+        if self.cpu().is_none() {
+            return Ok("E01".into());
+        }
+
+        let (mut display, mut buzzer) = (Headless, Headless);
+        let reply = loop {
+            let hardware = Interface {
+                display: &mut display,
+                buzzer: &mut buzzer,
+                keypad: &Headless,
+                rotary: &Headless,
+            };
+            let reason = self
+                .debugger
+                .run(&mut self.console, &mut self.cart, hardware, step);
+
+            if reason != Stop::Done || step != Step::Frame {
+                break stop_reply(reason);
+            }
+            if self.interrupted()? {
+                break "S02".into();
+            }
+        };
+
+        self.stop.clone_from(&reply);
+        Ok(reply)
+    }
+
+    /// Check if the client has requested to interrupt the target, without blocking.
+    ///
+    /// Any other byte is kept for the next packet. While such a byte is pending, the
+    /// connection is not checked.
+    fn interrupted(&mut self) -> io::Result<bool> {
+        if self.pending.is_some() {
+            return Ok(false);
+        }
+
+        match self.conn.poll()? {
+            Some(0x03) => Ok(true),
+            byte => {
+                self.pending = byte;
+                Ok(false)
+            }
+        }
+    }
+}
+
+/// Handle a general query packet.
+fn query(args: &str) -> String {
+    if args.starts_with("Supported") {
+        return format!(
+            "PacketSize={PACKET_SIZE:x};qXfer:features:read+;swbreak+;hwbreak+;QStartNoAckMode+"
+        );
+    }
+    if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+        return xfer(TARGET_XML, range).unwrap_or_else(|| "E01".into());
+    }
+
+    match args {
+        "Attached" => "1".into(),
+        "C" => "QC1".into(),
+        "fThreadInfo" => "m1".into(),
+        "sThreadInfo" => "l".into(),
+        _ => String::new(),
+    }
+}
+
+/// Compute the checksum of the given packet data.
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, &byte| sum.wrapping_add(byte))
+}
+
+/// Parse a hexadecimal number.
+fn parse_hex(text: &str) -> Option<u32> {
+    u32::from_str_radix(text, 16).ok()
+}
+
+/// Parse an `addr,length` memory range.
+fn parse_range(text: &str) -> Option<(u32, u32)> {
+    let (addr, len) = text.split_once(',')?;
+    Some((parse_hex(addr)?, parse_hex(len)?))
+}
+
+/// Decode a string of hexadecimal byte pairs.
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(text.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+/// Reply to a `qXfer` read of the given `offset,length` range of a document.
+fn xfer(document: &str, range: &str) -> Option<String> {
+    let (offset, len) = parse_range(range)?;
+    let rest = document.get(offset as usize..)?;
+
+    if rest.len() > len as usize {
+        Some(format!("m{}", rest.get(..len as usize)?))
+    } else {
+        Some(format!("l{rest}"))
+    }
+}
+
+/// Return the ROM address mapped at the given address.
+fn rom_addr(addr: u32) -> Option<RomAddr> {
+    let addr = addr.checked_sub(ROM_BASE).filter(|&addr| addr < 0x800)?;
+
+    #[allow(clippy::cast_possible_truncation)]
+    Some(RomAddr::new(
+        u1::new((addr >> 10) as u8 & 1),
+        u4::new((addr >> 6) as u8 & 0xf),
+        u6::new(addr as u8 & 0x3f),
+    ))
+}
+
+/// Return the RAM address mapped at the given address.
+fn ram_addr(addr: u32) -> Option<RamAddr> {
+    let addr = addr.checked_sub(RAM_BASE).filter(|&addr| addr < 0x80)?;
+
+    #[allow(clippy::cast_possible_truncation)]
+    Some(RamAddr::new(
+        u3::new((addr >> 4) as u8),
+        u4::new(addr as u8 & 0xf),
+    ))
+}
+
+/// Read the register with the given index.
+fn register(cpu: &Tms1100, idx: usize) -> u8 {
+    let regs = &cpu.regs;
+
+    match idx {
+        0 => regs.a.value(),
+        1 => regs.x.value(),
+        2 => regs.y.value(),
+        3 => regs.pc.value(),
+        4 => regs.sr.value(),
+        5 => regs.pa.value(),
+        6 => regs.pb.value(),
+        7 => regs.ca.value(),
+        8 => regs.cb.value(),
+        9 => regs.cs.value(),
+        _ => cpu.flags.status.into(),
+    }
+}
+
+/// Write the register with the given index, truncating the value to its width.
+fn set_register(cpu: &mut Tms1100, idx: usize, val: u8) {
+    let regs = &mut cpu.regs;
+
+    match idx {
+        0 => regs.a = u4::new(val & 0xf),
+        1 => regs.x = u3::new(val & 0x7),
+        2 => regs.y = u4::new(val & 0xf),
+        3 => regs.pc = u6::new(val & 0x3f),
+        4 => regs.sr = u6::new(val & 0x3f),
+        5 => regs.pa = u4::new(val & 0xf),
+        6 => regs.pb = u4::new(val & 0xf),
+        7 => regs.ca = u1::new(val & 1),
+        8 => regs.cb = u1::new(val & 1),
+        9 => regs.cs = u1::new(val & 1),
+        _ => cpu.flags.status = val & 1 != 0,
+    }
+}
+
+/// Return the stop reply packet for the given reason to stop.
+fn stop_reply(stop: Stop) -> String {
+    match stop {
+        Stop::Breakpoint(_) => "T05swbreak:;".into(),
+        Stop::Watchpoint { addr, access, .. } => {
+            let kind = match access {
+                Access::Read => "rwatch",
+                Access::Write => "watch",
+                Access::ReadWrite => "awatch",
+            };
+            format!("T05{kind}:{:x};", RAM_BASE + u32::from(addr.full().value()))
+        }
+        Stop::Done | Stop::R { .. } | Stop::O { .. } => "S05".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{net::TcpListener, thread};
+
+    use crate::asm;
+
+    use milton_core::{cartridge::settings::Settings, tms1100::Model};
+
+    /// A scripted GDB client.
+    struct Client(TcpStream);
+
+    impl Client {
+        /// Send a packet and return the reply.
+        fn request(&mut self, data: &str) -> String {
+            let packet = format!("${data}#{:02x}", checksum(data.as_bytes()));
+            self.0.write_all(packet.as_bytes()).unwrap();
+            self.reply()
+        }
+
+        /// Read a single byte.
+        fn byte(&mut self) -> u8 {
+            let mut byte = [0];
+            self.0.read_exact(&mut byte).unwrap();
+            byte[0]
+        }
+
+        /// Receive a reply, acknowledging it.
+        fn reply(&mut self) -> String {
+            assert_eq!(self.byte(), b'+');
+            assert_eq!(self.byte(), b'$');
+
+            let mut data = Vec::new();
+            loop {
+                match self.byte() {
+                    b'#' => break,
+                    byte => data.push(byte),
+                }
+            }
+            let sum = [self.byte(), self.byte()];
+            assert_eq!(
+                sum.to_vec(),
+                format!("{:02x}", checksum(&data)).into_bytes()
+            );
+
+            self.0.write_all(b"+").unwrap();
+            String::from_utf8(data).unwrap()
+        }
+    }
+
+    /// Create a cartridge running a small counting program.
+    fn cartridge() -> Cartridge {
+        let source = "
+            start:  TCY 3
+                    TCMIY 5
+                    IMAC
+                    TAM
+                    TDO
+                    SETR
+                    BR start
+        ";
+        let asm = asm::assemble(source, Model::Tms1100).unwrap();
+        Cartridge::try_new(&asm.rom.data, Settings::default()).unwrap()
+    }
+
+    #[test]
+    fn scripted_session() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            stream.set_nodelay(true).unwrap();
+            let mut server = Server::new(stream, Console::new(), cartridge());
+            server.serve().unwrap();
+        });
+
+        let mut gdb = Client(TcpStream::connect(("127.0.0.1", port)).unwrap());
+        gdb.0.set_nodelay(true).unwrap();
+
+        assert!(gdb
+            .request("qSupported:swbreak+")
+            .contains("qXfer:features:read+"));
+        assert!(gdb
+            .request("qXfer:features:read:target.xml:0,20")
+            .starts_with("m<?xml"));
+        assert_eq!(gdb.request("?"), "S05");
+
+        // Run until the breakpoint at TAM.
+        assert_eq!(gdb.request("Z0,7,1"), "OK");
+        assert_eq!(gdb.request("c"), "T05swbreak:;");
+        let regs = gdb.request("g");
+        assert_eq!(regs.len(), REGISTERS.len() * 2 + 4);
+        assert_eq!(&regs[22..], "0700");
+        assert_eq!(gdb.request("p0"), "01");
+
+        // TAM stores A (1) at M(0, 4).
+        assert_eq!(gdb.request("z0,7,1"), "OK");
+        assert_eq!(gdb.request("s"), "S05");
+        assert_eq!(gdb.request("pb"), "0f00");
+        assert_eq!(gdb.request("m1004,1"), "01");
+
+        // Memory and registers can be modified.
+        assert_eq!(gdb.request("M1004,1:0a"), "OK");
+        assert_eq!(gdb.request("m1003,2"), "050a");
+        assert_eq!(gdb.request("P0=09"), "OK");
+        assert_eq!(gdb.request("p0"), "09");
+        assert_eq!(gdb.request("m0,4"), "4c6a003e");
+        assert_eq!(gdb.request("m2000,1"), "E14");
+
+        // Run until the next write to M(0, 4).
+        assert_eq!(gdb.request("Z2,1004,1"), "OK");
+        assert_eq!(gdb.request("c"), "T05watch:1004;");
+        assert_eq!(gdb.request("z2,1004,1"), "OK");
+
+        // Run until interrupted.
+        gdb.0.write_all(b"$c#63").unwrap();
+        gdb.0.write_all(&[0x03]).unwrap();
+        assert_eq!(gdb.reply(), "S02");
+        assert_eq!(gdb.request("?"), "S02");
+
+        gdb.0.write_all(b"$k#6b").unwrap();
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn pending_byte() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut gdb = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+        let mut server = Server::new(stream, Console::new(), cartridge());
+
+        // A packet sent while the target runs is not discarded.
+        gdb.write_all(b"$?#3f").unwrap();
+        while server.pending.is_none() {
+            assert!(!server.interrupted().unwrap());
+        }
+        assert_eq!(server.recv().unwrap().as_deref(), Some("?"));
+    }
+}
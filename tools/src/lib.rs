@@ -0,0 +1,34 @@
+//! Development tools for Microvision cartridges, e.g. an assembler or a debugger.
+
+pub mod asm;
+pub mod conform;
+pub mod gdb;
+pub mod run;
+pub mod term;
+
+use milton_core::{buzzer, display, keypad, rotary};
+
+/// A hardware interface which ignores all output and supplies no input.
+#[derive(Debug)]
+pub struct Headless;
+
+impl display::Api for Headless {
+    fn enable_pixel(&mut self, _: usize, _: usize) {}
+}
+
+impl buzzer::Api for Headless {
+    fn enable(&mut self, _: usize) {}
+    fn disable(&mut self) {}
+}
+
+impl keypad::Api for Headless {
+    fn get(&self, _: keypad::Key) -> bool {
+        false
+    }
+}
+
+impl rotary::Api for Headless {
+    fn turn(&self) -> rotary::Percentage {
+        rotary::Percentage::new(50)
+    }
+}
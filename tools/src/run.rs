@@ -0,0 +1,647 @@
+//! A headless runner, running cartridges for a fixed amount of time with scripted
+//! input, e.g. for game-level regression tests.
+//!
+//! # Scripts
+//!
+//! A script lists actions, one per line, each prefixed with the frame it happens at,
+//! i.e. the amount of frames which have been run. Blank lines and lines beginning
+//! with `#` are ignored:
+//!
+//! ```text
+//! # Hold the key in column 1, row 3, for half a second.
+//! 10 press 1x3
+//! 40 release 1x3
+//! 40 rotary 75
+//! # Check the state after 100 frames.
+//! 100 assert ram 0:4 5
+//! 100 assert pixel 3 2 on
+//! ```
+//!
+//! Pixels are checked within the last complete frame, RAM can only be checked on
+//! TMS1000 family cartridges.
+
+use std::fmt::{self, Write as _};
+
+use milton_core::{
+    cartridge::{
+        container::{self, Container},
+        database,
+        settings::{CpuType, Settings},
+        Cartridge,
+    },
+    common::{Interface, FRAME_CLOCKS},
+    display::{Framebuffer, Lcd},
+    keypad::{self, Key},
+    rotary::{self, Percentage},
+    tms1100::{
+        mem::RamAddr,
+        trace::{Record, Tracer},
+    },
+    Console, Cpu,
+};
+
+use arbitrary_int::{u3, u4};
+
+/// Parse the name of a micro-processor type, e.g. `tms1100` or `i8021`.
+#[must_use]
+pub fn parse_cpu(name: &str) -> Option<CpuType> {
+    let cpu = match name.to_ascii_lowercase().as_str() {
+        "tms1100" => CpuType::Tms1100,
+        "i8021" => CpuType::I8021,
+        "tms1000" => CpuType::Tms1000,
+        "tms1070" => CpuType::Tms1070,
+        "tms1200" => CpuType::Tms1200,
+        "tms1270" => CpuType::Tms1270,
+        "tms1300" => CpuType::Tms1300,
+        "tms1370" => CpuType::Tms1370,
+        _ => return None,
+    };
+
+    Some(cpu)
+}
+
+/// A cartridge file, opened by [`open`].
+#[derive(Debug, Clone, Copy)]
+pub struct Opened<'a> {
+    /// The ROM data.
+    pub rom: &'a [u8],
+    /// The cartridge-specific settings.
+    pub settings: Settings,
+    /// The title of the game, if known.
+    pub title: Option<&'a str>,
+    /// The CRC-32 of a raw ROM dump missing from the database of known games, whose
+    /// settings are therefore the defaults.
+    pub unknown: Option<u32>,
+}
+
+impl Opened<'_> {
+    /// Return the warning to report to the user if the ROM dump is not known.
+    #[must_use]
+    pub fn warning(&self) -> Option<String> {
+        self.unknown.map(|crc32| {
+            format!("warning: unknown rom dump with crc32 {crc32:08x}, using the default settings")
+        })
+    }
+}
+
+/// Open a cartridge file, either a container or a raw ROM dump.
+///
+/// The settings of a raw ROM dump come from the database of known games, or are the
+/// defaults if the ROM is not known, which callers should report to the user.
+///
+/// # Errors
+///
+/// This function will return an error if the file is a malformed container.
+pub fn open(data: &[u8]) -> Result<Opened<'_>, container::Error> {
+    if data.starts_with(&container::MAGIC) {
+        let container = Container::parse(data)?;
+        return Ok(Opened {
+            rom: container.rom,
+            settings: container.settings,
+            title: container.title,
+            unknown: None,
+        });
+    }
+
+    let game = database::lookup(data);
+    Ok(Opened {
+        rom: data,
+        settings: game.map_or_else(Settings::default, database::Game::settings),
+        title: game.map(|game| game.title),
+        unknown: game.is_none().then(|| database::crc32(data)),
+    })
+}
+
+/// A scripted action.
+#[derive(Debug, Clone, Copy)]
+pub enum Action {
+    /// Press a key.
+    Press(Key),
+    /// Release a key.
+    Release(Key),
+    /// Turn the rotary controller.
+    Rotary(Percentage),
+    /// Check the value of a RAM nibble.
+    AssertRam {
+        /// The RAM address.
+        addr: RamAddr,
+        /// The expected value.
+        val: u4,
+    },
+    /// Check if a pixel is enabled.
+    AssertPixel {
+        /// The X screen coordinate.
+        x: usize,
+        /// The Y screen coordinate.
+        y: usize,
+        /// The expected state.
+        on: bool,
+    },
+}
+
+/// An error caused by a malformed script line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntaxError {
+    /// The (1-based) line the error is on.
+    pub line: usize,
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: malformed script line", self.line)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// A script of actions.
+#[derive(Debug, Clone, Default)]
+pub struct Script {
+    /// The actions, along with their frame and (1-based) line, ordered by frame.
+    actions: Vec<(usize, usize, Action)>,
+}
+
+impl Script {
+    /// Parse a script.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the script contains a malformed line.
+    pub fn parse(text: &str) -> Result<Self, SyntaxError> {
+        let mut actions = Vec::new();
+
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (frame, action) = parse_action(&fields).ok_or(SyntaxError { line: idx + 1 })?;
+            actions.push((frame, idx + 1, action));
+        }
+
+        // Actions happening at the same frame keep their order.
+        actions.sort_by_key(|(frame, ..)| *frame);
+        Ok(Self { actions })
+    }
+}
+
+/// Parse the fields of a script line.
+fn parse_action(fields: &[&str]) -> Option<(usize, Action)> {
+    let (frame, fields) = fields.split_first()?;
+    let action = match fields {
+        ["press", key] => Action::Press(parse_key(key)?),
+        ["release", key] => Action::Release(parse_key(key)?),
+        ["rotary", amount] => Action::Rotary(Percentage::try_new(amount.parse().ok()?).ok()?),
+        ["assert", "ram", addr, val] => {
+            let (x, y) = addr.split_once(':')?;
+            Action::AssertRam {
+                addr: RamAddr::new(
+                    u3::try_new(x.parse().ok()?).ok()?,
+                    u4::try_new(u8::from_str_radix(y, 16).ok()?).ok()?,
+                ),
+                val: u4::try_new(u8::from_str_radix(val, 16).ok()?).ok()?,
+            }
+        }
+        ["assert", "pixel", x, y, state] => {
+            let (x, y) = (x.parse().ok()?, y.parse().ok()?);
+            if x >= 16 || y >= 16 {
+                return None;
+            }
+            let on = match *state {
+                "on" => true,
+                "off" => false,
+                _ => return None,
+            };
+            Action::AssertPixel { x, y, on }
+        }
+        _ => return None,
+    };
+
+    Some((frame.parse().ok()?, action))
+}
+
+/// Parse a key location, as `COLxROW`, e.g. `1x3`.
+fn parse_key(text: &str) -> Option<Key> {
+    let (col, row) = text.split_once('x')?;
+    Key::at(col.parse().ok()?, row.parse().ok()?)
+}
+
+/// The point at which a run stops.
+#[derive(Debug, Clone, Copy)]
+pub enum Stop {
+    /// Stop once the given amount of frames have been run.
+    Frames(usize),
+    /// Stop once the given amount of microseconds have elapsed.
+    Micros(usize),
+}
+
+/// A failed assertion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    /// The (1-based) line of the assertion.
+    pub line: usize,
+    /// A description of the failure.
+    pub message: String,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// The scripted input of the keypad and rotary controller.
+#[derive(Debug)]
+struct Input {
+    /// The state of every key, in the order of [`Key::ALL`].
+    keys: [bool; 12],
+    /// The turn of the rotary controller.
+    rotary: Percentage,
+}
+
+impl keypad::Api for Input {
+    fn get(&self, key: Key) -> bool {
+        self.keys[key.index()]
+    }
+}
+
+impl rotary::Api for Input {
+    fn turn(&self) -> Percentage {
+        self.rotary
+    }
+}
+
+/// A headless runner.
+#[derive(Debug)]
+pub struct Runner {
+    /// The emulated console.
+    pub console: Console,
+    /// The inserted cartridge.
+    pub cart: Cartridge,
+    /// The display.
+    pub framebuffer: Framebuffer,
+    /// The rendered audio samples.
+    pub audio: Vec<i16>,
+    /// The sample rate of the rendered audio.
+    rate: u32,
+    /// The scripted input.
+    input: Input,
+    /// The amount of frames run.
+    frame: usize,
+    /// The amount of clocks run within the current frame.
+    clocks: usize,
+}
+
+impl Runner {
+    /// Create a new runner, rendering audio at the given sample rate.
+    #[must_use]
+    pub fn new(cart: Cartridge, rate: u32) -> Self {
+        Self {
+            console: Console::new(),
+            cart,
+            framebuffer: Framebuffer::new(),
+            audio: Vec::new(),
+            rate,
+            input: Input {
+                keys: [false; 12],
+                rotary: Percentage::new(50),
+            },
+            frame: 0,
+            clocks: 0,
+        }
+    }
+
+    /// Return the amount of frames run.
+    #[must_use]
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Run a single clock, passing every instruction executed by a TMS1100 to the
+    /// given tracer.
+    pub fn clock<T: Tracer>(&mut self, tracer: &mut T) {
+        let mut buzzer = crate::Headless;
+        let hardware = Interface {
+            display: &mut self.framebuffer,
+            buzzer: &mut buzzer,
+            keypad: &self.input,
+            rotary: &self.input,
+        };
+        self.console.clock_traced(&mut self.cart, hardware, tracer);
+
+        self.clocks += 1;
+        if self.clocks == FRAME_CLOCKS {
+            let hardware = Interface {
+                display: &mut self.framebuffer,
+                buzzer: &mut buzzer,
+                keypad: &self.input,
+                rotary: &self.input,
+            };
+            self.console.sync(hardware);
+
+            let pcm = &mut self.console.buzzer.pcm;
+            let start = self.audio.len();
+            self.audio.resize(start + pcm.available(self.rate), 0);
+            pcm.render(self.rate, &mut self.audio[start..]);
+
+            self.clocks = 0;
+            self.frame += 1;
+        }
+    }
+
+    /// Run the given script until the given stop, passing every instruction
+    /// executed by a TMS1100 to the given tracer.
+    ///
+    /// This returns every failed assertion, including assertions at frames which
+    /// have not been reached.
+    pub fn run<T: Tracer>(&mut self, script: &Script, stop: Stop, tracer: &mut T) -> Vec<Failure> {
+        let mut failures = Vec::new();
+        let mut actions = script.actions.iter().peekable();
+
+        loop {
+            if self.clocks == 0 {
+                while let Some((_, line, action)) =
+                    actions.next_if(|(frame, ..)| *frame <= self.frame)
+                {
+                    if let Err(message) = self.apply(*action) {
+                        failures.push(Failure {
+                            line: *line,
+                            message,
+                        });
+                    }
+                }
+            }
+
+            let done = match stop {
+                Stop::Frames(frames) => self.frame >= frames,
+                Stop::Micros(micros) => self.console.elapsed.value() >= micros,
+            };
+            if done {
+                break;
+            }
+
+            self.clock(tracer);
+        }
+
+        for (frame, line, action) in actions {
+            if matches!(
+                action,
+                Action::AssertRam { .. } | Action::AssertPixel { .. }
+            ) {
+                failures.push(Failure {
+                    line: *line,
+                    message: format!("frame {frame} was never reached"),
+                });
+            }
+        }
+
+        failures
+    }
+
+    /// Apply an action, returning a description of the failure of an assertion.
+    fn apply(&mut self, action: Action) -> Result<(), String> {
+        match action {
+            Action::Press(key) => self.input.keys[key.index()] = true,
+            Action::Release(key) => self.input.keys[key.index()] = false,
+            Action::Rotary(amount) => self.input.rotary = amount,
+            Action::AssertRam { addr, val } => {
+                if !matches!(self.console.cpu, Cpu::Tms1100(_)) {
+                    return Err("the cartridge has no TMS1000 family RAM".into());
+                }
+
+                let found = self.cart.ram.read(addr);
+                if found != val {
+                    return Err(format!("M[{addr}] is {found:X}, expected {val:X}"));
+                }
+            }
+            Action::AssertPixel { x, y, on } => {
+                if self.framebuffer.get(x, y) != on {
+                    let state = if on { "off" } else { "on" };
+                    return Err(format!("pixel ({x}, {y}) is {state}"));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Return a dump of the RAM and registers.
+    #[must_use]
+    pub fn dump(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "frame: {}", self.frame);
+        let _ = writeln!(out, "elapsed: {}us", self.console.elapsed.value());
+
+        match &self.console.cpu {
+            Cpu::Tms1100(cpu) => {
+                let _ = writeln!(out, "cpu: {:?}", cpu.model);
+                let regs = &cpu.regs;
+                let _ = writeln!(
+                    out,
+                    "A={:X} X={:X} Y={:X} PC={:02X} SR={:02X} PA={:X} PB={:X} CA={:X} CB={:X} CS={:X} SL={} R={:04X} O={:02X}",
+                    regs.a.value(),
+                    regs.x.value(),
+                    regs.y.value(),
+                    regs.pc.value(),
+                    regs.sr.value(),
+                    regs.pa.value(),
+                    regs.pb.value(),
+                    regs.ca.value(),
+                    regs.cb.value(),
+                    regs.cs.value(),
+                    u8::from(cpu.flags.status),
+                    cpu.r.value(),
+                    cpu.o.value().value(),
+                );
+
+                for x in 0..1 << cpu.model.x_bits() {
+                    let _ = write!(out, "M[{x}]:");
+                    for y in 0..16 {
+                        let addr = RamAddr::new(u3::new(x), u4::new(y));
+                        let _ = write!(out, " {:X}", self.cart.ram.read(addr).value());
+                    }
+                    out.push('\n');
+                }
+            }
+            Cpu::I8021(cpu) => {
+                let _ = writeln!(out, "cpu: I8021");
+                let _ = writeln!(
+                    out,
+                    "A={:02X} PC={:03X} SP={} C={} AC={} P0={:02X} P1={:02X} P2={:02X} T={:02X}",
+                    cpu.regs.a,
+                    cpu.regs.pc.value(),
+                    cpu.regs.sp.value(),
+                    u8::from(cpu.flags.carry),
+                    u8::from(cpu.flags.aux_carry),
+                    cpu.p0.value(),
+                    cpu.p1.value(),
+                    cpu.p2.value(),
+                    cpu.timer.value,
+                );
+
+                for (idx, row) in cpu.ram.chunks(16).enumerate() {
+                    let _ = write!(out, "RAM[{:02X}]:", idx * 16);
+                    for byte in row {
+                        let _ = write!(out, " {byte:02X}");
+                    }
+                    out.push('\n');
+                }
+            }
+        }
+
+        out
+    }
+}
+
+/// A tracer writing every record as a line of text.
+#[derive(Debug, Default)]
+pub struct TextTracer {
+    /// The written records.
+    pub text: String,
+}
+
+impl Tracer for TextTracer {
+    fn trace(&mut self, record: &Record) {
+        let _ = writeln!(self.text, "{record}");
+    }
+}
+
+/// Encode a framebuffer as a plain PBM image, where enabled pixels are black.
+#[must_use]
+pub fn pbm(framebuffer: &Framebuffer) -> Vec<u8> {
+    let mut out = String::from("P1\n16 16\n");
+    for row in framebuffer.rows() {
+        let bits: Vec<&str> = (0..16)
+            .map(|x| if row >> x & 1 != 0 { "1" } else { "0" })
+            .collect();
+        out.push_str(&bits.join(" "));
+        out.push('\n');
+    }
+
+    out.into_bytes()
+}
+
+/// Encode the brightness of every pixel of an LCD panel as a grayscale PNG image,
+/// where pixels which are fully on are black.
+#[must_use]
+pub fn png(lcd: &Lcd) -> Vec<u8> {
+    /// Append a PNG chunk.
+    fn chunk(out: &mut Vec<u8>, tag: [u8; 4], data: &[u8]) {
+        // Every chunk is far smaller than 4gb.
+        let len = u32::try_from(data.len()).unwrap_or(u32::MAX);
+        out.extend_from_slice(&len.to_be_bytes());
+        let start = out.len();
+        out.extend_from_slice(&tag);
+        out.extend_from_slice(data);
+        let crc = database::crc32(&out[start..]);
+        out.extend_from_slice(&crc.to_be_bytes());
+    }
+
+    // Every row begins with its filter type, none.
+    let mut raw = Vec::with_capacity(16 * 17);
+    for y in 0..16 {
+        raw.push(0);
+        raw.extend((0..16).map(|x| 255 - lcd.brightness(x, y)));
+    }
+
+    // A zlib stream holding a single stored (uncompressed) deflate block.
+    // The image is far smaller than the 64kb limit of a stored block.
+    let len = u16::try_from(raw.len()).unwrap_or(u16::MAX);
+    let mut zlib = vec![0x78, 0x01, 0x01];
+    zlib.extend_from_slice(&len.to_le_bytes());
+    zlib.extend_from_slice(&(!len).to_le_bytes());
+    zlib.extend_from_slice(&raw);
+    let (a, b) = raw.iter().fold((1u32, 0u32), |(a, b), byte| {
+        let a = (a + u32::from(*byte)) % 65521;
+        (a, (b + a) % 65521)
+    });
+    zlib.extend_from_slice(&(b << 16 | a).to_be_bytes());
+
+    let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+    // A 16x16 image, with 8-bit grayscale pixels.
+    chunk(
+        &mut out,
+        *b"IHDR",
+        &[0, 0, 0, 16, 0, 0, 0, 16, 8, 0, 0, 0, 0],
+    );
+    chunk(&mut out, *b"IDAT", &zlib);
+    chunk(&mut out, *b"IEND", &[]);
+    out
+}
+
+/// Encode mono 16-bit samples as a WAV file.
+///
+/// # Panics
+///
+/// This function will panic if there are more samples than fit in a WAV file.
+#[must_use]
+pub fn wav(samples: &[i16], rate: u32) -> Vec<u8> {
+    let size = u32::try_from(samples.len() * 2).expect("the samples fit in a wav file");
+
+    let mut out = Vec::with_capacity(44 + samples.len() * 2);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + size).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, mono.
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&rate.to_le_bytes());
+    out.extend_from_slice(&(rate * 2).to_le_bytes());
+    // 2 bytes per frame, 16 bits per sample.
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&size.to_le_bytes());
+    for sample in samples {
+        out.extend_from_slice(&sample.to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm;
+
+    use milton_core::{cartridge::settings::Settings, tms1100::Model};
+
+    #[test]
+    fn runs_script() {
+        let source = "start: TCY 4\nTCMIY 5\nloop: BR loop";
+        let rom = asm::assemble(source, Model::Tms1100).unwrap().rom;
+        let cart = Cartridge::new(&rom.data, Settings::default());
+        let mut runner = Runner::new(cart, 8000);
+
+        let script = Script::parse(
+            "\
+            # Comments are ignored.
+            0 press 1x3
+            2 assert ram 0:4 5
+            2 assert ram 0:5 5
+            2 assert pixel 0 0 on
+            9 assert pixel 0 0 off
+            ",
+        )
+        .unwrap();
+
+        let failures = runner.run(&script, Stop::Frames(2), &mut ());
+        let lines: Vec<usize> = failures.iter().map(|failure| failure.line).collect();
+        assert_eq!(lines, [4, 5, 6]);
+        assert_eq!(failures[0].message, "M[0:5] is 0, expected 5");
+        assert_eq!(failures[2].message, "frame 9 was never reached");
+
+        assert_eq!(runner.frame(), 2);
+        assert_eq!(runner.audio.len(), 2 * 8000 * FRAME_CLOCKS / 100_000);
+        assert!(runner.dump().contains("M[0]: 0 0 0 0 5 0"));
+
+        assert_eq!(
+            Script::parse("1 press 3x0").unwrap_err(),
+            SyntaxError { line: 1 }
+        );
+        assert_eq!(&wav(&runner.audio, 8000)[..4], b"RIFF");
+        assert_eq!(pbm(&runner.framebuffer).len(), 9 + 16 * 32);
+    }
+}
@@ -0,0 +1,328 @@
+//! An interactive terminal frontend, e.g. for playing over SSH.
+//!
+//! # Rendering
+//!
+//! The 16x16 LCD is rendered with upper half block characters (`▀`), two pixels
+//! per character cell, using 24-bit ANSI colours shaded by the decay of every
+//! pixel, see [`Lcd::brightness`](milton_core::display::Lcd::brightness).
+//!
+//! # Controls
+//!
+//! | Host keys | Microvision                                   |
+//! |-----------|-----------------------------------------------|
+//! | `1 2 3`   | The keys in columns `0` to `2` on row `0`.    |
+//! | `q w e`   | The keys in columns `0` to `2` on row `1`.    |
+//! | `a s d`   | The keys in columns `0` to `2` on row `2`.    |
+//! | `z x c`   | The keys in columns `0` to `2` on row `3`.    |
+//! | `[ ]`     | Turn the rotary controller down/up by 5%.     |
+//! | `Ctrl-C`  | Quit.                                         |
+//!
+//! Terminals only report key presses (and their auto-repeat), not releases, so a
+//! key is held for [`HOLD_FRAMES`] after it was last reported. The escape sequences
+//! of other keys, e.g. the arrow keys, are ignored, see [`Decoder`].
+
+use std::fmt::Write as _;
+
+use milton_core::{
+    buzzer,
+    common::Interface,
+    display::Framebuffer,
+    keypad::{self, Key},
+    overlay::shade,
+    rotary::{self, Percentage},
+};
+
+/// The amount of frames a key is held after it was last reported.
+pub const HOLD_FRAMES: u8 = 12;
+
+/// The amount the rotary controller is turned by a single key press.
+pub const ROTARY_STEP: usize = 5;
+
+/// A command issued by a host key.
+#[derive(Debug, Clone, Copy)]
+pub enum Command {
+    /// Press a key of the keypad.
+    Press(Key),
+    /// Turn the rotary controller down.
+    RotaryDown,
+    /// Turn the rotary controller up.
+    RotaryUp,
+    /// Quit the frontend.
+    Quit,
+}
+
+impl Command {
+    /// Return the command issued by a byte read from the terminal, if any.
+    ///
+    /// This does not handle escape sequences, see [`Decoder`].
+    #[must_use]
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        const KEYS: [[u8; 3]; 4] = [*b"123", *b"qwe", *b"asd", *b"zxc"];
+
+        let command = match byte {
+            b'[' => Self::RotaryDown,
+            b']' => Self::RotaryUp,
+            // Ctrl-C, as the terminal is in raw mode.
+            0x03 => Self::Quit,
+            byte => {
+                let (row, col) = KEYS.iter().enumerate().find_map(|(row, keys)| {
+                    let col = keys.iter().position(|&key| key == byte)?;
+                    Some((row, col))
+                })?;
+                Self::Press(Key::at(col, row)?)
+            }
+        };
+
+        Some(command)
+    }
+}
+
+/// The state of a [`Decoder`] within an escape sequence.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+enum Escape {
+    /// Outside of an escape sequence.
+    #[default]
+    None,
+    /// After the `ESC` byte.
+    Start,
+    /// Within a control sequence (`ESC [`), e.g. the arrow keys.
+    Csi,
+    /// After a single shift (`ESC O`), e.g. the function keys.
+    Ss3,
+}
+
+/// A decoder of the bytes read from the terminal into commands, which skips the
+/// escape sequences sent by keys without a command, e.g. `ESC [ A` for the up
+/// arrow key.
+///
+/// A lone `ESC` byte, i.e. the `Esc` key, starts an escape sequence, which would
+/// swallow the next key, so it is dropped by [`Decoder::end_frame`] if no other
+/// byte followed it within the same frame. The terminal sends every sequence at
+/// once, so a sequence is only ever split by a (rare) late read.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Decoder {
+    /// The state within the current escape sequence.
+    escape: Escape,
+}
+
+impl Decoder {
+    /// Create a new decoder, outside of an escape sequence.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode the next byte read from the terminal, returning the command it
+    /// issues, if any.
+    pub fn decode(&mut self, byte: u8) -> Option<Command> {
+        self.escape = match (self.escape, byte) {
+            (Escape::None, 0x1b) => Escape::Start,
+            (Escape::None, _) => return Command::from_byte(byte),
+            // Parameter and intermediate bytes continue a control sequence, until its
+            // final byte.
+            (Escape::Start, b'[') | (Escape::Csi, 0x20..=0x3f) => Escape::Csi,
+            (Escape::Start, b'O') => Escape::Ss3,
+            // The final byte of a sequence, or a two byte sequence, e.g. `Alt`.
+            (Escape::Start | Escape::Csi | Escape::Ss3, _) => Escape::None,
+        };
+
+        None
+    }
+
+    /// End the current frame, dropping a lone `ESC` byte.
+    pub fn end_frame(&mut self) {
+        if self.escape == Escape::Start {
+            self.escape = Escape::None;
+        }
+    }
+}
+
+/// The keypad and rotary controller, driven by host keys.
+#[derive(Debug, Clone)]
+pub struct Controls {
+    /// The amount of frames every key is still held, in the order of [`Key::ALL`].
+    held: [u8; 12],
+    /// The turn of the rotary controller.
+    rotary: Percentage,
+}
+
+impl Controls {
+    /// Create new controls, with no keys held and the rotary controller centered.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            held: [0; 12],
+            rotary: Percentage::new(50),
+        }
+    }
+
+    /// Apply a command, returning `false` if the frontend should quit.
+    pub fn apply(&mut self, command: Command) -> bool {
+        let turn = self.rotary.value();
+        match command {
+            Command::Press(key) => self.held[key.index()] = HOLD_FRAMES,
+            Command::RotaryDown => self.rotary = Percentage::new(turn.saturating_sub(ROTARY_STEP)),
+            Command::RotaryUp => self.rotary = Percentage::new((turn + ROTARY_STEP).min(100)),
+            Command::Quit => return false,
+        }
+
+        true
+    }
+
+    /// End a frame, releasing any keys which have been held long enough.
+    pub fn end_frame(&mut self) {
+        for held in &mut self.held {
+            *held = held.saturating_sub(1);
+        }
+    }
+}
+
+impl keypad::Api for Controls {
+    fn get(&self, key: Key) -> bool {
+        self.held[key.index()] != 0
+    }
+}
+
+impl rotary::Api for Controls {
+    fn turn(&self) -> Percentage {
+        self.rotary
+    }
+}
+
+/// The buzzer, shown as text.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Speaker {
+    /// The pitch of the buzzer (in Hz), if enabled.
+    pub pitch: Option<usize>,
+}
+
+impl buzzer::Api for Speaker {
+    fn enable(&mut self, pitch: usize) {
+        self.pitch = Some(pitch);
+    }
+
+    fn disable(&mut self) {
+        self.pitch = None;
+    }
+}
+
+/// The hardware interface of the terminal frontend.
+#[derive(Debug, Clone)]
+pub struct Terminal {
+    /// The display.
+    pub framebuffer: Framebuffer,
+    /// The buzzer.
+    pub speaker: Speaker,
+    /// The keypad and rotary controller.
+    pub controls: Controls,
+}
+
+impl Terminal {
+    /// Create a new terminal frontend.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            framebuffer: Framebuffer::new(),
+            speaker: Speaker::default(),
+            controls: Controls::new(),
+        }
+    }
+
+    /// Return the hardware interface of this terminal frontend.
+    pub fn interface(&mut self) -> Interface<'_, Framebuffer, Speaker, Controls, Controls> {
+        Interface {
+            display: &mut self.framebuffer,
+            buzzer: &mut self.speaker,
+            keypad: &self.controls,
+            rotary: &self.controls,
+        }
+    }
+
+    /// Render a frame, using the given brightness (`0..=255`) of every pixel.
+    ///
+    /// The frame begins by moving the cursor to the upper left corner, and every
+    /// line ends with a carriage return, as the terminal is in raw mode.
+    #[must_use]
+    pub fn render(&self, brightness: impl Fn(usize, usize) -> u8) -> String {
+        let mut out = String::from("\x1b[H");
+
+        for y in (0..16).step_by(2) {
+            out.push_str("  ");
+            for x in 0..16 {
+                let [r, g, b] = shade(brightness(x, y));
+                let _ = write!(out, "\x1b[38;2;{r};{g};{b}m");
+                let [r, g, b] = shade(brightness(x, y + 1));
+                let _ = write!(out, "\x1b[48;2;{r};{g};{b}m▀▀");
+            }
+            out.push_str("\x1b[0m\r\n");
+        }
+
+        let pitch = self
+            .speaker
+            .pitch
+            .map_or_else(|| "silent".into(), |pitch| format!("{pitch} Hz"));
+        let _ = write!(
+            out,
+            "\r\n  buzzer: {pitch:<8} rotary: {:>3}%\x1b[K\r\n",
+            self.controls.rotary.value()
+        );
+        out.push_str("  keys: 123 qwe asd zxc, rotary: [ ], quit: Ctrl-C\x1b[K\r\n");
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use milton_core::{buzzer::Api as _, keypad::Api as _, rotary::Api as _};
+
+    #[test]
+    fn controls_and_renders() {
+        let mut term = Terminal::new();
+        let controls = &mut term.controls;
+
+        for byte in *b"3zx]]" {
+            assert!(controls.apply(Command::from_byte(byte).unwrap()));
+        }
+        assert!(Command::from_byte(b'!').is_none());
+        assert!(Command::from_byte(b'Z').is_none());
+        assert!(!controls.apply(Command::from_byte(0x03).unwrap()));
+
+        assert!(controls.get(Key::At2x0) && controls.get(Key::At0x3) && controls.get(Key::At1x3));
+        assert!(!controls.get(Key::At0x0));
+        assert_eq!(controls.turn().value(), 60);
+
+        for _ in 0..HOLD_FRAMES {
+            controls.end_frame();
+        }
+        assert!(!controls.get(Key::At2x0));
+
+        term.speaker.enable(440);
+        let frame = term.render(|x, y| if (x, y) == (0, 1) { 255 } else { 0 });
+        assert_eq!(frame.matches('▀').count(), 16 * 16);
+        assert!(frame.contains("\x1b[38;2;200;204;180m\x1b[48;2;32;34;28m▀▀"));
+        assert!(frame.contains("buzzer: 440 Hz") && frame.contains("rotary:  60%"));
+    }
+
+    #[test]
+    fn skips_escape_sequences() {
+        let mut decoder = Decoder::new();
+
+        // The up and right arrow keys, Ctrl+Right, F1 and Alt+Q, then `a`.
+        let mut commands = b"\x1b[A\x1b[C\x1b[1;5C\x1bOP\x1bqa"
+            .iter()
+            .filter_map(|&byte| decoder.decode(byte));
+        assert!(matches!(commands.next(), Some(Command::Press(Key::At0x2))));
+        assert!(commands.next().is_none());
+
+        // The `Esc` key, then `a` in the next frame.
+        assert!(decoder.decode(0x1b).is_none());
+        decoder.end_frame();
+        assert!(matches!(
+            decoder.decode(b'a'),
+            Some(Command::Press(Key::At0x2))
+        ));
+    }
+}
@@ -0,0 +1,37 @@
+//! Check the corpus in `tests/conformance` against the emulator.
+//!
+//! Every `NAME.asm` program is assembled and run in lockstep with the reference
+//! trace `NAME.trace`, the expected behaviour of the program on a TMS1100.
+
+use std::{fs, path::Path};
+
+use milton_core::tms1100::Model;
+use milton_tools::{asm, conform::Reference};
+
+#[test]
+fn corpus() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance");
+    let mut checked = 0;
+
+    for entry in fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "asm") {
+            continue;
+        }
+
+        let source = fs::read_to_string(&path).unwrap();
+        let assembly = asm::assemble(&source, Model::Tms1100)
+            .unwrap_or_else(|err| panic!("{}: {err}", path.display()));
+
+        let trace = fs::read_to_string(path.with_extension("trace")).unwrap();
+        let reference = Reference::parse(&trace).unwrap();
+        assert!(!reference.is_empty(), "{}: empty trace", path.display());
+
+        if let Err(err) = reference.check(&assembly.rom, Model::Tms1100) {
+            panic!("{}: {err}", path.display());
+        }
+        checked += 1;
+    }
+
+    assert!(checked > 0, "no programs in {}", dir.display());
+}
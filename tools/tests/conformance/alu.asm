@@ -0,0 +1,27 @@
+; Arithmetic, comparisons and the status latch.
+
+start:  TCY 0
+        CLA
+        AC1AC 7         ; A = 8, without a carry
+        YNEA            ; Y != A, latched into SL
+        TDO             ; O = SL:A
+        TAM
+        AMAAC           ; A = 0, with a carry
+        ALEM            ; A <= M
+        MNEA            ; M != A
+        DMAN            ; A = M - 1
+        SAMAN           ; A = M - A
+        CPAIZ           ; A = -A
+        IMAC            ; A = M + 1
+        YNEA            ; Y != A
+        KNEZ            ; no keys pressed, status is reset
+        BR fail
+        YNEC 0          ; Y == 0, status is reset
+        BR fail
+        MNEZ            ; M != 0
+        BR pass
+fail:   BR fail
+pass:   TKA
+        YNEA            ; Y == A, SL is reset
+        TDO
+done:   BR done
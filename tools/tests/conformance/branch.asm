@@ -0,0 +1,26 @@
+; Conditional branches, subroutine calls and returns across pages and chapters.
+
+start:  TCY 5
+        YNEC 5          ; status is reset, the branch is not taken
+        BR start
+        YNEC 4          ; status is set, the branch is taken
+        BR near
+        BR start
+near:   CALL sub        ; a call to another page
+        TYA
+        CALL far        ; a call to another chapter
+        TYA
+done:   BR done
+
+.page 1
+sub:    IYC             ; without a carry, the branch is not taken
+        BR skip
+        DYN             ; with a carry, the branch is taken
+        BR skip         ; within a subroutine, branches stay within the page
+        DYN
+skip:   IYC
+        RETN
+
+.page 16
+far:    TCY 9
+        RETN
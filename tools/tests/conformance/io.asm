@@ -0,0 +1,24 @@
+; The R and O output pins.
+
+start:  TCY 0
+        SETR            ; R0
+        TCY 10
+        SETR            ; R10
+        TCY 11
+        SETR            ; only 11 R pins exist
+        LDX 4
+        TCY 3
+        SETR            ; with the MSB of X set, R is not modified
+        LDX 0
+        SETR            ; R3
+        TCY 0
+        RSTR
+        CLA
+        AC1AC 14        ; A = 15
+        TDO             ; SL is reset on power-on
+        YNEA
+        TDO             ; O = 0x1f
+        TAY
+        YNEA
+        TDO             ; O = 0x0f
+done:   BR done
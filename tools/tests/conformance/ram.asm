@@ -0,0 +1,28 @@
+; RAM addressing, transfers and bit operations.
+
+start:  TCY 2
+        TCMIY 7         ; M[0:2] = 7
+        TCMIY 9         ; M[0:3] = 9
+        DYN
+        TMA
+        TMY             ; Y = 9
+        TYA
+        TCY 2
+        XMA             ; swap A (9) and M[0:2] (7)
+        TAMIYC          ; M[0:2] = 7, Y = 3
+        TAMDYN          ; M[0:3] = 7, Y = 2
+        TAMZA           ; M[0:2] = 7, A = 0
+        TAY             ; Y = 0
+        IYC
+        SBIT 0
+        SBIT 3          ; M[0:1] = 9
+        TBIT1 3
+        RBIT 0          ; M[0:1] = 8
+        TBIT1 0
+        LDX 5
+        TCMIY 1         ; M[5:1] = 1
+        COMX            ; X = 1
+        TCMIY 2         ; M[1:2] = 2
+        LDX 7
+        COMX            ; X = 3
+done:   BR done